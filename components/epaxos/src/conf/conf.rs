@@ -5,7 +5,10 @@ use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
 
+use crate::conf::key_hash_slot;
 use crate::conf::ConfError;
+use crate::conf::KeyRange;
+use crate::conf::HASH_SLOTS;
 use crate::qpaxos::ReplicaId;
 
 use serde::{Deserialize, Serialize};
@@ -23,6 +26,14 @@ pub struct Node {
     pub api_addr: SocketAddr,
     pub api_uaddr: Option<String>,
     pub replication: SocketAddr,
+
+    /// storage is the storage engine of the node, `mem` or `rocksdb`. The command line option
+    /// `--storage` overrides it.
+    pub storage: Option<String>,
+
+    /// data_dir is where the node stores data with `rocksdb`. The command line option
+    /// `--data-dir` overrides it.
+    pub data_dir: Option<String>,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -34,9 +45,44 @@ pub struct ReplicaInfo {
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub struct GroupInfo {
     /// range defines the starting and ending key this group serves.
-    /// It is a left-close right-open range.
-    pub range: (String, String),
+    /// It is a left-close right-open range of bytes.
+    pub range: KeyRange,
     pub replicas: BTreeMap<ReplicaId, NodeId>,
+
+    /// slots is the first and the last hash slot this group serves, in redis-cluster mode.
+    /// If every group has `slots`, a key is routed by its hash slot instead of `range`, and the
+    /// layout is exposed with `CLUSTER SLOTS` for cluster-aware clients.
+    #[serde(default)]
+    pub slots: Option<(u16, u16)>,
+}
+
+/// ReadConsistency is how a read-only request is served by a node hosting a replica of the group.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadConsistency {
+    /// a read is served locally once the replica has executed every conflicting instance seen
+    /// by a quorum, which it asks for on every read. It sees every write completed before it.
+    Linearizable,
+
+    /// like `Linearizable`, but what a quorum has seen is asked for at most once in
+    /// `read_stale_ms`. A read may miss writes completed within the last `read_stale_ms`.
+    BoundedStale,
+
+    /// a read is served locally at once, and may miss writes not yet executed by the replica.
+    Stale,
+}
+
+impl Default for ReadConsistency {
+    fn default() -> Self {
+        ReadConsistency::Linearizable
+    }
+}
+
+/// default value of `ClusterInfo.read_stale_ms`.
+pub const DEFAULT_READ_STALE_MS: u64 = 1_000;
+
+fn default_read_stale_ms() -> u64 {
+    DEFAULT_READ_STALE_MS
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -54,6 +100,16 @@ pub struct ClusterInfo {
     /// No two groups have the same replica id.
     pub groups: Vec<GroupInfo>,
 
+    /// read_consistency is one of `linearizable`, `bounded_stale` and `stale`, `linearizable` by
+    /// default.
+    #[serde(default)]
+    pub read_consistency: ReadConsistency,
+
+    /// read_stale_ms is how long a node reuses what a quorum has seen for reads, in
+    /// `bounded_stale` mode.
+    #[serde(default = "default_read_stale_ms")]
+    pub read_stale_ms: u64,
+
     #[serde(skip)]
     pub replicas: BTreeMap<ReplicaId, ReplicaInfo>,
 }
@@ -109,15 +165,34 @@ impl ClusterInfo {
         self.nodes.get(nid)
     }
 
+    /// is_slot_mode returns true if keys are routed by hash slot, i.e., every group has `slots`.
+    pub fn is_slot_mode(&self) -> bool {
+        self.groups.len() > 0 && self.groups.iter().all(|g| g.slots.is_some())
+    }
+
     /// get_group_for_key returns the GroupInfo of which the range covers the specified key.
-    pub fn get_group_for_key(&self, key: &str) -> Option<&GroupInfo> {
-        for g in self.groups.iter() {
-            if g.range.0.as_str() <= key && g.range.1.as_str() > key {
-                return Some(g);
-            }
+    /// In slot mode it returns the one of which the slots cover the hash slot of the key.
+    pub fn get_group_for_key(&self, key: &[u8]) -> Option<&GroupInfo> {
+        if self.is_slot_mode() {
+            let slot = key_hash_slot(key);
+            return self.groups.iter().find(|g| match g.slots {
+                Some((a, b)) => a <= slot && slot <= b,
+                None => false,
+            });
         }
 
-        None
+        self.groups.iter().find(|g| g.range.contains(key))
+    }
+
+    /// get_group_from returns the first group of which the range has keys not smaller than
+    /// `key`, i.e., the group covering `key`, or the next one if no group covers it.
+    /// Groups are in key order, thus it is how to walk through the whole key space.
+    /// Ranges do not apply in slot mode, where it should not be used.
+    pub fn get_group_from(&self, key: &[u8]) -> Option<&GroupInfo> {
+        self.groups.iter().find(|g| match g.range.end {
+            Some(ref e) => key < &e[..],
+            None => true,
+        })
     }
 
     /// get_group returns the GroupInfo where the specified replica in.
@@ -139,21 +214,52 @@ impl ClusterInfo {
             return Ok(());
         }
         for g in self.groups.iter() {
-            let a = &g.range.0;
-            let b = &g.range.1;
-            if a >= b {
-                return Err(ConfError::GroupOutOfOrder(a.clone(), b.clone()));
+            if g.range.is_empty() {
+                return Err(ConfError::GroupOutOfOrder(
+                    g.range.start_str(),
+                    g.range.end_str(),
+                ));
             }
         }
 
         for i in 0..self.groups.len() - 1 {
-            let x = &self.groups[i];
-            let y = &self.groups[i + 1];
+            let x = &self.groups[i].range;
+            let y = &self.groups[i + 1].range;
+
+            if !x.is_before(y) {
+                return Err(ConfError::GroupOutOfOrder(x.end_str(), y.start_str()));
+            }
+        }
+
+        self.check_slots()
+    }
+
+    /// check_slots checks that slots of groups are valid and do not overlap.
+    /// Either every group or none has `slots`.
+    pub fn check_slots(&self) -> Result<(), ConfError> {
+        let mut slots: Vec<(u16, u16)> = self.groups.iter().filter_map(|g| g.slots).collect();
+        if slots.len() == 0 {
+            return Ok(());
+        }
+
+        if slots.len() != self.groups.len() {
+            let g = self.groups.iter().find(|g| g.slots.is_none()).unwrap();
+            return Err(ConfError::GroupWithoutSlots(
+                g.range.start_str(),
+                g.range.end_str(),
+            ));
+        }
+
+        for (a, b) in slots.iter() {
+            if a > b || *b >= HASH_SLOTS {
+                return Err(ConfError::BadSlots(*a, *b));
+            }
+        }
 
-            let a = &x.range.1;
-            let b = &y.range.0;
-            if a > b {
-                return Err(ConfError::GroupOutOfOrder(a.clone(), b.clone()));
+        slots.sort();
+        for i in 1..slots.len() {
+            if slots[i - 1].1 >= slots[i].0 {
+                return Err(ConfError::BadSlots(slots[i].0, slots[i].1));
             }
         }
 
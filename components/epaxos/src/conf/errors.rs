@@ -22,6 +22,10 @@ quick_error! {
         DupReplica(rid: ReplicaId) {}
 
         GroupOutOfOrder(a: String, b: String) {}
+
+        BadSlots(start: u16, end: u16) {}
+
+        GroupWithoutSlots(a: String, b: String) {}
     }
 }
 
@@ -34,6 +38,8 @@ impl PartialEq<ConfError> for ConfError {
             (Self::OrphanReplica(a, b), Self::OrphanReplica(x, y)) => a == x && b == y,
             (Self::DupReplica(a), Self::DupReplica(b)) => a == b,
             (Self::GroupOutOfOrder(a, b), Self::GroupOutOfOrder(x, y)) => a == x && b == y,
+            (Self::BadSlots(a, b), Self::BadSlots(x, y)) => a == x && b == y,
+            (Self::GroupWithoutSlots(a, b), Self::GroupWithoutSlots(x, y)) => a == x && b == y,
             _ => false,
         }
     }
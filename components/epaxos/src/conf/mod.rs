@@ -1,8 +1,12 @@
 mod conf;
 mod errors;
+mod range;
+mod slots;
 
 pub use conf::*;
 pub use errors::*;
+pub use range::*;
+pub use slots::*;
 
 #[cfg(test)]
 mod test_conf;
@@ -0,0 +1,207 @@
+use std::fmt;
+
+use serde::de::{Error, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// KeyRange is a left-close right-open range of keys. Keys are arbitrary bytes and are compared
+/// as bytes.
+/// A `None` start is unbounded, i.e., it includes the smallest key, and a `None` end includes
+/// the largest key.
+///
+/// In conf yaml it is a sequence of two boundaries. A boundary is one of:
+/// - a string, which is the utf-8 bytes of it, e.g., `abc`;
+/// - a map of hex encoded bytes, e.g., `{hex: 00ff}`;
+/// - null, i.e., `~`, for an unbounded start or end.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyRange {
+    pub start: Option<Vec<u8>>,
+    pub end: Option<Vec<u8>>,
+}
+
+impl KeyRange {
+    pub fn new(start: Option<&[u8]>, end: Option<&[u8]>) -> Self {
+        KeyRange {
+            start: start.map(|x| x.to_vec()),
+            end: end.map(|x| x.to_vec()),
+        }
+    }
+
+    /// contains returns true if `key` is in this range.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        let after_start = match self.start {
+            Some(ref s) => &s[..] <= key,
+            None => true,
+        };
+        let before_end = match self.end {
+            Some(ref e) => key < &e[..],
+            None => true,
+        };
+        after_start && before_end
+    }
+
+    /// is_empty returns true if no key is in this range.
+    pub fn is_empty(&self) -> bool {
+        match (&self.start, &self.end) {
+            (Some(s), Some(e)) => s >= e,
+            _ => false,
+        }
+    }
+
+    /// is_before returns true if every key in this range is smaller than every key in `other`.
+    pub fn is_before(&self, other: &KeyRange) -> bool {
+        match (&self.end, &other.start) {
+            (Some(e), Some(s)) => e <= s,
+            _ => false,
+        }
+    }
+
+    pub fn start_str(&self) -> String {
+        fmt_bound(&self.start, "-inf")
+    }
+
+    pub fn end_str(&self) -> String {
+        fmt_bound(&self.end, "+inf")
+    }
+}
+
+impl fmt::Display for KeyRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {})", self.start_str(), self.end_str())
+    }
+}
+
+/// fmt_bound formats a boundary for humans: a utf-8 boundary is shown as is, otherwise it is
+/// shown in hex.
+fn fmt_bound(b: &Option<Vec<u8>>, unbounded: &str) -> String {
+    match b {
+        None => unbounded.to_owned(),
+        Some(v) => match std::str::from_utf8(v) {
+            Ok(s) => s.to_owned(),
+            Err(_) => format!("0x{}", hex_encode(v)),
+        },
+    }
+}
+
+/// BoundConf is a boundary in conf yaml.
+enum BoundConf {
+    Str(String),
+    Hex(String),
+}
+
+impl Serialize for BoundConf {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BoundConf::Str(v) => s.serialize_str(v),
+            BoundConf::Hex(v) => {
+                let mut m = s.serialize_map(Some(1))?;
+                m.serialize_entry("hex", v)?;
+                m.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BoundConf {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(BoundVisitor)
+    }
+}
+
+struct BoundVisitor;
+
+impl<'de> Visitor<'de> for BoundVisitor {
+    type Value = BoundConf;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string or a map of hex")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<BoundConf, E> {
+        Ok(BoundConf::Str(v.to_owned()))
+    }
+
+    // a plain scalar such as `123` is parsed as a number. Quote it to keep leading zeros.
+    fn visit_i64<E: Error>(self, v: i64) -> Result<BoundConf, E> {
+        Ok(BoundConf::Str(v.to_string()))
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<BoundConf, E> {
+        Ok(BoundConf::Str(v.to_string()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut m: A) -> Result<BoundConf, A::Error> {
+        let k: String = m
+            .next_key()?
+            .ok_or_else(|| A::Error::custom("empty boundary"))?;
+        if k != "hex" {
+            return Err(A::Error::unknown_field(&k, &["hex"]));
+        }
+
+        // read as a string, thus `0080` keeps the leading zeros.
+        let v: String = m.next_value()?;
+        if m.next_key::<String>()?.is_some() {
+            return Err(A::Error::custom("boundary has more than one field"));
+        }
+        Ok(BoundConf::Hex(v))
+    }
+}
+
+impl BoundConf {
+    fn from_bytes(b: &[u8]) -> Self {
+        match std::str::from_utf8(b) {
+            Ok(s) => BoundConf::Str(s.to_owned()),
+            Err(_) => BoundConf::Hex(hex_encode(b)),
+        }
+    }
+
+    fn into_bytes(self) -> Result<Vec<u8>, String> {
+        match self {
+            BoundConf::Str(s) => Ok(s.into_bytes()),
+            BoundConf::Hex(hex) => hex_decode(&hex),
+        }
+    }
+}
+
+impl Serialize for KeyRange {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let start = self.start.as_ref().map(|x| BoundConf::from_bytes(x));
+        let end = self.end.as_ref().map(|x| BoundConf::from_bytes(x));
+        (start, end).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyRange {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let (start, end) = <(Option<BoundConf>, Option<BoundConf>)>::deserialize(d)?;
+
+        let conv = |b: Option<BoundConf>| match b {
+            Some(v) => v.into_bytes().map(Some).map_err(D::Error::custom),
+            None => Ok(None),
+        };
+
+        Ok(KeyRange {
+            start: conv(start)?,
+            end: conv(end)?,
+        })
+    }
+}
+
+pub fn hex_encode(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+pub fn hex_decode(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 != 0 {
+        return Err(format!("odd length hex: {}", s));
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .ok_or(format!("invalid hex: {}", s))
+        })
+        .collect()
+}
@@ -0,0 +1,36 @@
+/// number of hash slots in redis-cluster mode.
+pub const HASH_SLOTS: u16 = 16384;
+
+/// key_hash_slot returns the redis-cluster hash slot of a key, i.e., CRC16 of the key modulo
+/// 16384.
+/// If the key contains a non-empty hash tag, i.e., a sub-string between the first `{` and the
+/// first `}` after it, only the tag is hashed, thus keys with the same tag are in the same slot.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    let mut k = key;
+
+    if let Some(s) = key.iter().position(|x| *x == b'{') {
+        if let Some(e) = key[s + 1..].iter().position(|x| *x == b'}') {
+            if e > 0 {
+                k = &key[s + 1..s + 1 + e];
+            }
+        }
+    }
+
+    crc16(k) % HASH_SLOTS
+}
+
+/// crc16 is the CRC16-CCITT (XMODEM) used by redis-cluster.
+pub fn crc16(buf: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for b in buf.iter() {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
@@ -74,6 +74,65 @@ groups: []
     }
 }
 
+#[test]
+fn test_conf_read_consistency() {
+    let nodes = "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:4441
+groups: []
+";
+
+    let ci = ClusterInfo::from_str(nodes).unwrap();
+    assert_eq!(ReadConsistency::Linearizable, ci.read_consistency);
+    assert_eq!(DEFAULT_READ_STALE_MS, ci.read_stale_ms);
+
+    let cases = vec![
+        ("linearizable", ReadConsistency::Linearizable),
+        ("bounded_stale", ReadConsistency::BoundedStale),
+        ("stale", ReadConsistency::Stale),
+    ];
+    for (name, want) in cases.iter() {
+        let cont = format!("{}read_consistency: {}\nread_stale_ms: 200\n", nodes, name);
+        let ci = ClusterInfo::from_str(&cont).unwrap();
+        assert_eq!(*want, ci.read_consistency);
+        assert_eq!(200, ci.read_stale_ms);
+    }
+
+    let cont = format!("{}read_consistency: strong\n", nodes);
+    match ClusterInfo::from_str(&cont) {
+        Err(ConfError::BadYaml(_)) => {}
+        r => panic!("expect BadYaml but: {:?}", r),
+    }
+}
+
+#[test]
+fn test_conf_node_storage() {
+    let cont = "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:4441
+        storage: rocksdb
+        data_dir: /var/lib/cele
+    127.0.0.1:4442:
+        api_addr: 127.0.0.1:3332
+        replication: 127.0.0.1:4442
+groups: []
+";
+
+    let ci = ClusterInfo::from_str(cont).unwrap();
+
+    let n1 = ci.get("127.0.0.1:4441").unwrap();
+    assert_eq!(Some("rocksdb"), n1.storage.as_deref());
+    assert_eq!(Some("/var/lib/cele"), n1.data_dir.as_deref());
+
+    let n2 = ci.get("127.0.0.1:4442").unwrap();
+    assert_eq!(None, n2.storage);
+    assert_eq!(None, n2.data_dir);
+}
+
 #[test]
 fn test_conf_orphan_replica() {
     let cont = "
@@ -129,6 +188,8 @@ groups:
             api_addr: "192.168.0.1:3332".parse().unwrap(),
             api_uaddr: None,
             replication: "192.168.0.1:4442".parse().unwrap(),
+            storage: None,
+            data_dir: None,
         }
     );
 }
@@ -297,15 +358,217 @@ groups:
 ";
     let (_f, ci) = load_conf(cont).unwrap();
 
-    let g = ci.get_group_for_key("a");
+    let g = ci.get_group_for_key(b"a");
     assert_eq!(&ci.groups[0], g.unwrap());
 
-    let g = ci.get_group_for_key("d".into());
+    let g = ci.get_group_for_key(b"d");
     assert!(g.is_none());
 
-    let g = ci.get_group_for_key("g");
+    let g = ci.get_group_for_key(b"g");
     assert_eq!(&ci.groups[1], g.unwrap());
 
-    let g = ci.get_group_for_key("h");
+    let g = ci.get_group_for_key(b"h");
     assert!(g.is_none());
+
+    assert_eq!(&ci.groups[0], ci.get_group_from(b"").unwrap());
+    assert_eq!(&ci.groups[0], ci.get_group_from(b"c").unwrap());
+    assert_eq!(&ci.groups[1], ci.get_group_from(b"d").unwrap());
+    assert_eq!(&ci.groups[1], ci.get_group_from(b"g").unwrap());
+    assert!(ci.get_group_from(b"h").is_none());
+}
+
+#[test]
+fn test_key_hash_slot() {
+    assert_eq!(0x31c3, crc16(b"123456789"));
+
+    assert_eq!(12182, key_hash_slot(b"foo"));
+    assert_eq!(11058, key_hash_slot(b"somekey"));
+
+    // hash tag
+    assert_eq!(3443, key_hash_slot(b"{user1000}.following"));
+    assert_eq!(3443, key_hash_slot(b"{user1000}.followers"));
+
+    // empty or unclosed hash tag: the whole key is hashed.
+    assert_eq!(9500, key_hash_slot(b"{}foo"));
+    assert_eq!(key_hash_slot(b"{foo"), crc16(b"{foo") % HASH_SLOTS);
+}
+
+#[test]
+fn test_conf_groups_get_by_key_slot() {
+    let cont = "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:5551
+groups:
+-   range:
+    -   a
+    -   d
+    slots:
+    -   0
+    -   8191
+    replicas:
+        1: 127.0.0.1:4441
+-   range:
+    -   d
+    -   z
+    slots:
+    -   8192
+    -   16382
+    replicas:
+        2: 127.0.0.1:4441
+";
+    let (_f, ci) = load_conf(cont).unwrap();
+    assert!(ci.is_slot_mode());
+
+    // slot 12182
+    let g = ci.get_group_for_key(b"foo");
+    assert_eq!(&ci.groups[1], g.unwrap());
+
+    // slot 3443
+    let g = ci.get_group_for_key(b"{user1000}.following");
+    assert_eq!(&ci.groups[0], g.unwrap());
+
+    // slot 16383 is not served.
+    assert_eq!(16383, key_hash_slot(b"k10322"));
+    assert!(ci.get_group_for_key(b"k10322").is_none());
+}
+
+#[test]
+fn test_conf_bad_slots() {
+    let cases = vec![
+        ("[0, 100]", "[100, 200]", ConfError::BadSlots(100, 200)),
+        ("[0, 100]", "[200, 16384]", ConfError::BadSlots(200, 16384)),
+        ("[10, 0]", "[200, 300]", ConfError::BadSlots(10, 0)),
+        (
+            "[0, 100]",
+            "",
+            ConfError::GroupWithoutSlots("b".into(), "c".into()),
+        ),
+    ];
+
+    for (s1, s2, want) in cases {
+        let slots2 = if s2 == "" {
+            "".to_string()
+        } else {
+            format!("\n    slots: {}", s2)
+        };
+        let cont = format!(
+            "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:5551
+groups:
+-   range: [a, b]
+    slots: {}
+    replicas:
+        1: 127.0.0.1:4441
+-   range: [b, c]{}
+    replicas:
+        2: 127.0.0.1:4441
+",
+            s1, slots2
+        );
+
+        let r = load_conf(&cont);
+        assert_eq!(want, r.err().unwrap());
+    }
+}
+
+#[test]
+fn test_key_range() {
+    let r = KeyRange::new(Some(b"b"), Some(b"d"));
+    assert!(!r.contains(b"a"));
+    assert!(r.contains(b"b"));
+    assert!(r.contains(b"c\xff"));
+    assert!(!r.contains(b"d"));
+    assert_eq!("[b, d)", format!("{}", r));
+
+    let r = KeyRange::new(None, Some(&[0xff, 0x00]));
+    assert!(r.contains(b""));
+    assert!(r.contains(&[0xfe]));
+    assert!(!r.contains(&[0xff, 0x00]));
+    assert_eq!("[-inf, 0xff00)", format!("{}", r));
+
+    let r = KeyRange::new(Some(b"x"), None);
+    assert!(r.contains(&[0xff, 0xff, 0xff]));
+    assert!(!r.is_empty());
+    assert!(!r.is_before(&KeyRange::new(Some(b"z"), None)));
+
+    assert!(KeyRange::new(Some(b"b"), Some(b"b")).is_empty());
+    assert!(KeyRange::new(None, Some(b"b")).is_before(&KeyRange::new(Some(b"b"), None)));
+
+    assert_eq!("00ff", hex_encode(&[0x00, 0xff]));
+    assert_eq!(Ok(vec![0x00, 0xff]), hex_decode("00FF"));
+    assert!(hex_decode("0").is_err());
+    assert!(hex_decode("zz").is_err());
+}
+
+#[test]
+fn test_conf_groups_binary_range() {
+    let cont = "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:5551
+groups:
+-   range: [~, {hex: 80}]
+    replicas:
+        1: 127.0.0.1:4441
+-   range: [{hex: 80}, {hex: ff00}]
+    replicas:
+        2: 127.0.0.1:4441
+-   range: [{hex: ff00}, ~]
+    replicas:
+        3: 127.0.0.1:4441
+";
+    let (_f, ci) = load_conf(cont).unwrap();
+    assert_eq!(KeyRange::new(None, Some(&[0x80])), ci.groups[0].range);
+
+    assert_eq!(&ci.groups[0], ci.get_group_for_key(b"").unwrap());
+    assert_eq!(&ci.groups[0], ci.get_group_for_key(b"foo").unwrap());
+    assert_eq!(&ci.groups[1], ci.get_group_for_key(&[0x80]).unwrap());
+    assert_eq!(&ci.groups[1], ci.get_group_for_key(&[0xfe, 0xff]).unwrap());
+    assert_eq!(&ci.groups[2], ci.get_group_for_key(&[0xff, 0x00]).unwrap());
+    assert_eq!(&ci.groups[2], ci.get_group_for_key(&[0xff; 10]).unwrap());
+
+    // serialized back to the same conf.
+    let y = serde_yaml::to_string(&ci).unwrap();
+    assert_eq!(ci, ClusterInfo::from_str(&y).unwrap());
+
+    // unbounded end not at last
+    let cont = "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:5551
+groups:
+-   range: [a, ~]
+    replicas:
+        1: 127.0.0.1:4441
+-   range: [b, c]
+    replicas:
+        2: 127.0.0.1:4441
+";
+    let r = load_conf(cont);
+    assert_eq!(
+        ConfError::GroupOutOfOrder("+inf".into(), "b".into()),
+        r.err().unwrap()
+    );
+
+    let cont = "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:5551
+groups:
+-   range: [a, {hex: zz}]
+    replicas:
+        1: 127.0.0.1:4441
+";
+    match load_conf(cont) {
+        Err(ConfError::BadYaml(_)) => {}
+        r => panic!("expect BadYaml but: {:?}", r),
+    }
 }
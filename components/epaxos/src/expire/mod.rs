@@ -0,0 +1,13 @@
+//! expire removes keys whose time to live has passed.
+//!
+//! The expire-at of a key is replicated along with the commands setting it, and a key is
+//! considered absent by a command if it expires no later than `Command.now_ms`, which is stamped
+//! by the proposer. Thus every replica sees an expired key the same way, no matter when it
+//! executes the command.
+//!
+//! An expired key is not removed from storage until it is written, or `sweep_expired` proposes a
+//! `DeleteExpired` command for it. Only the replica with the smallest id in a group sweeps, to
+//! avoid proposing the same deletion several times.
+
+mod sweep;
+pub use sweep::*;
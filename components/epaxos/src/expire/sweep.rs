@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use crate::conf::GroupInfo;
+use crate::qpaxos::now_ms;
+use crate::qpaxos::Command;
+use crate::qpaxos::OpCode;
+use crate::replica::list_expired;
+use crate::replica::Replica;
+use crate::replication::propose;
+use crate::ServerData;
+
+/// default max number of keys to delete in one round of sweep.
+pub const DEFAULT_SWEEP_LIMIT: usize = 128;
+
+/// sweep_expired deletes at most `limit` expired keys, those served by a group in which a local
+/// replica has the smallest replica id.
+/// The keys of a group are deleted in one instance.
+pub async fn sweep_expired(sd: &ServerData, limit: usize) {
+    let now = now_ms();
+
+    // keys of groups swept by other nodes are skipped before the limit applies.
+    let expired = list_expired(&sd.storage, now, limit, |k| sweeper(sd, k).is_some());
+
+    let mut batches = BTreeMap::new();
+    for (key, _) in expired {
+        let (g, r) = match sweeper(sd, &key) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let cmd = Command {
+            now_ms: now,
+            ..Command::of(OpCode::DeleteExpired, &key, &[])
+        };
+
+        batches
+            .entry(r.replica_id)
+            .or_insert_with(|| (g, r, vec![]))
+            .2
+            .push(cmd);
+    }
+
+    for (rid, (g, r, cmds)) in batches.iter() {
+        if let Err(e) = propose(cmds, g, r).await {
+            println!("{:?} while sweep expired keys of replica {}", e, rid);
+        }
+    }
+}
+
+/// sweeper returns the group serving `key` and the local replica of it, if the replica has the
+/// smallest replica id in the group.
+fn sweeper<'a>(sd: &'a ServerData, key: &[u8]) -> Option<(&'a GroupInfo, &'a Replica)> {
+    let (g, r) = sd.get_local_replica_for_key(key).ok()?;
+    if g.replicas.keys().next() == Some(&r.replica_id) {
+        Some((g, r))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,92 @@
+use std::iter::Peekable;
+
+use prost::Message;
+use storage::DBColumnFamily;
+
+use crate::iters::BaseIter;
+use crate::iters::Iter;
+use crate::qpaxos::CollectionMeta;
+use crate::replica::expire_key;
+use crate::replica::meta_key;
+use crate::replica::parse_ms;
+use crate::replica::type_name;
+use crate::replica::META_PREFIX;
+use crate::Storage;
+
+/// KeyIter iterates user keys not smaller than a start key, in key order, along with the type
+/// of every key, such as `string` or `hash`.
+/// Strings and the metas of collections are merged. A key expired as of `now_ms` is skipped.
+pub struct KeyIter {
+    strs: Peekable<BaseIter>,
+    metas: Peekable<BaseIter>,
+    storage: Storage,
+    now_ms: i64,
+}
+
+impl KeyIter {
+    pub fn new(storage: &Storage, start: &[u8], now_ms: i64) -> Self {
+        let strs = storage.get_iter(start.to_vec(), true, false, DBColumnFamily::Default);
+        let metas = storage.get_iter(meta_key(start), true, false, DBColumnFamily::Collection);
+        KeyIter {
+            strs: strs.peekable(),
+            metas: metas.peekable(),
+            storage: storage.clone(),
+            now_ms,
+        }
+    }
+
+    fn peek_meta(&mut self) -> Option<&[u8]> {
+        match self.metas.peek() {
+            Some((k, _)) if k.starts_with(META_PREFIX) => Some(&k[META_PREFIX.len()..]),
+            _ => None,
+        }
+    }
+
+    fn is_expired(&self, key: &[u8]) -> bool {
+        let v = self
+            .storage
+            .get(DBColumnFamily::Status, &expire_key(key))
+            .unwrap_or(None);
+
+        match v.and_then(|x| parse_ms(&x)) {
+            Some(at) => at <= self.now_ms,
+            None => false,
+        }
+    }
+}
+
+impl Iterator for KeyIter {
+    type Item = (Vec<u8>, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let s = self.strs.peek().map(|(k, _)| k.clone());
+            let m = self.peek_meta().map(|x| x.to_vec());
+
+            let (key, typ) = match (s, m) {
+                (None, None) => return None,
+                (Some(s), Some(m)) if m < s => {
+                    let (_, v) = self.metas.next().unwrap();
+                    (m, decode_type(&v))
+                }
+                (Some(s), _) => {
+                    self.strs.next();
+                    (s, "string")
+                }
+                (None, Some(m)) => {
+                    let (_, v) = self.metas.next().unwrap();
+                    (m, decode_type(&v))
+                }
+            };
+
+            if !self.is_expired(&key) {
+                return Some((key, typ));
+            }
+        }
+    }
+}
+
+fn decode_type(v: &[u8]) -> &'static str {
+    let meta = CollectionMeta::decode(v).ok();
+    type_name(&None, &meta)
+}
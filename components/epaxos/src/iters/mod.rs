@@ -1,5 +1,11 @@
 mod iters;
 pub use iters::*;
 
+mod keys;
+pub use keys::*;
+
 #[cfg(test)]
 mod test_iters;
+
+#[cfg(test)]
+mod test_keys;
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use crate::qpaxos::{Command, Instance, OpCode};
+use crate::testutil;
+use crate::*;
+use storage::MemEngine;
+
+#[test]
+fn test_key_iter() {
+    let sto: Storage = Arc::new(MemEngine::new().unwrap());
+    let rp = testutil::new_replica(1, vec![1, 2, 3], vec![], sto.clone());
+
+    let at = |cmd: Command| Command { now_ms: 10, ..cmd };
+    let m = |op: OpCode, key: &str, field: &str| {
+        at(Command::member(op, key.as_bytes(), field.as_bytes(), b"1"))
+    };
+
+    let inst = Instance {
+        instance_id: Some((2, 1).into()),
+        cmds: vec![
+            at(Command::from(("Set", "b", "x"))),
+            at(Command::from(("Set", "d", "x"))),
+            at(Command::from(("Set", "dd", "x"))),
+            m(OpCode::HSet, "a", "f"),
+            m(OpCode::RPush, "c", ""),
+            m(OpCode::SAdd, "ca", "m"),
+            m(OpCode::ZAdd, "e", "m"),
+            at(Command::from(("Set", "x", "x"))),
+            at(Command::expire(b"x", 100)),
+            m(OpCode::HSet, "y", "f"),
+            at(Command::expire(b"y", 100)),
+        ],
+        ..Default::default()
+    };
+    rp.execute_commands(vec![inst]).unwrap();
+
+    let keys = |start: &str, now_ms: i64| -> Vec<(String, &'static str)> {
+        KeyIter::new(&sto, start.as_bytes(), now_ms)
+            .map(|(k, t)| (String::from_utf8(k).unwrap(), t))
+            .collect()
+    };
+
+    let all = vec![
+        ("a".to_string(), "hash"),
+        ("b".to_string(), "string"),
+        ("c".to_string(), "list"),
+        ("ca".to_string(), "set"),
+        ("d".to_string(), "string"),
+        ("dd".to_string(), "string"),
+        ("e".to_string(), "zset"),
+        ("x".to_string(), "string"),
+        ("y".to_string(), "hash"),
+    ];
+
+    assert_eq!(all, keys("", 99));
+    assert_eq!(all[2..], keys("c", 99)[..]);
+    assert_eq!(all[3..], keys("c\x00", 99)[..]);
+    assert_eq!(all[6..], keys("da", 99)[..]);
+
+    // expired keys are skipped
+    assert_eq!(all[..7], keys("", 100)[..]);
+    assert_eq!(Vec::<(String, &str)>::new(), keys("x", 100));
+}
@@ -12,8 +12,11 @@ mod service;
 
 #[macro_use]
 pub mod qpaxos;
+pub mod expire;
+pub mod read;
 pub mod replica;
 pub mod replication;
+pub mod txn;
 
 pub use replication::*;
 pub use serverdata::*;
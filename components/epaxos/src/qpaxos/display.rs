@@ -4,6 +4,7 @@ use crate::qpaxos::Instance;
 use crate::qpaxos::InstanceId;
 use crate::qpaxos::InstanceIdVec;
 use crate::qpaxos::OpCode;
+use crate::qpaxos::REVERSE;
 use std::fmt;
 
 trait ToStringExt {
@@ -52,11 +53,94 @@ impl ToStringExt for Command {
             v if v == (OpCode::Delete as i32) => {
                 format!("Delete:{}", String::from_utf8_lossy(&self.key),)
             }
-            _ => format!("UnknownCmd"),
+            v if v == (OpCode::Incr as i32) => format!(
+                "Incr:{}+={}",
+                String::from_utf8_lossy(&self.key),
+                String::from_utf8_lossy(&self.value),
+            ),
+            v if v == (OpCode::SetIfAbsent as i32) => format!(
+                "SetIfAbsent:{}={}",
+                String::from_utf8_lossy(&self.key),
+                String::from_utf8_lossy(&self.value),
+            ),
+            v if v == (OpCode::CompareAndSet as i32) => format!(
+                "CompareAndSet:{}={}",
+                String::from_utf8_lossy(&self.key),
+                String::from_utf8_lossy(&self.value),
+            ),
+            v if v == (OpCode::Append as i32) => format!(
+                "Append:{}+{}",
+                String::from_utf8_lossy(&self.key),
+                String::from_utf8_lossy(&self.value),
+            ),
+            v if v == (OpCode::Watch as i32) => {
+                format!("Watch:{}", String::from_utf8_lossy(&self.key))
+            }
+            v if v == (OpCode::TxnPrepare as i32) => {
+                format!("TxnPrepare:{}", String::from_utf8_lossy(&self.key))
+            }
+            v if v == (OpCode::TxnCommit as i32) => format!(
+                "TxnCommit:{}#{}",
+                String::from_utf8_lossy(&self.key),
+                self.txn.as_ref().map(|x| x.seq).unwrap_or(0),
+            ),
+            v if v == (OpCode::TxnAbort as i32) => {
+                format!("TxnAbort:{}", String::from_utf8_lossy(&self.key))
+            }
+            v if v == (OpCode::TxnForget as i32) => {
+                format!("TxnForget:{}", String::from_utf8_lossy(&self.key))
+            }
+            v if v == (OpCode::Expire as i32) => format!(
+                "Expire:{}@{}",
+                String::from_utf8_lossy(&self.key),
+                self.expire_at_ms,
+            ),
+            v if v == (OpCode::Persist as i32) => {
+                format!("Persist:{}", String::from_utf8_lossy(&self.key))
+            }
+            v if v == (OpCode::Ttl as i32) => format!("Ttl:{}", String::from_utf8_lossy(&self.key)),
+            v if v == (OpCode::DeleteExpired as i32) => {
+                format!("DeleteExpired:{}", String::from_utf8_lossy(&self.key))
+            }
+            v if v == (OpCode::ReadRange as i32) => format!(
+                "ReadRange:[{},{})#{}{}",
+                String::from_utf8_lossy(&self.key),
+                String::from_utf8_lossy(&self.field),
+                self.stop,
+                if self.value == REVERSE {
+                    " reverse"
+                } else {
+                    ""
+                },
+            ),
+            v if v == (OpCode::DeleteRange as i32) => format!(
+                "DeleteRange:[{},{})",
+                String::from_utf8_lossy(&self.key),
+                String::from_utf8_lossy(&self.field),
+            ),
+            v => match OpCode::from_i32(v) {
+                Some(op) => collection_cmd_str(op, self),
+                None => format!("UnknownCmd"),
+            },
         }
     }
 }
 
+/// collection_cmd_str formats a command on a collection, e.g. `HSet:k/f=v` or `LRange:k[0,-1]`.
+fn collection_cmd_str(op: OpCode, cmd: &Command) -> String {
+    let mut s = format!("{:?}:{}", op, String::from_utf8_lossy(&cmd.key));
+    if cmd.field.len() > 0 {
+        s += &format!("/{}", String::from_utf8_lossy(&cmd.field));
+    }
+    if cmd.value.len() > 0 {
+        s += &format!("={}", String::from_utf8_lossy(&cmd.value));
+    }
+    if op == OpCode::LRange || op == OpCode::ZRange {
+        s += &format!("[{},{}]", cmd.start, cmd.stop);
+    }
+    s
+}
+
 macro_rules! impl_tostr_ext {
     ($typ:path) => {
         impl ToStringExt for $typ {
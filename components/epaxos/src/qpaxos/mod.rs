@@ -1,6 +1,7 @@
 pub use std::cmp::Ordering;
 use std::ops::Index;
 use std::ops::{Deref, DerefMut};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // to let user be able to call Phase::try_into() without use this trait
 pub use std::convert::TryInto;
@@ -81,14 +82,129 @@ impl From<(&str, &str)> for InvalidRequest {
     }
 }
 
+/// ReadRange reads in reverse key order if its value is `REVERSE`.
+pub const REVERSE: &[u8] = b"reverse";
+
 impl Command {
     pub fn of(op: OpCode, key: &[u8], value: &[u8]) -> Command {
         Command {
             op: op as i32,
             key: key.to_vec(),
             value: value.to_vec(),
+            expected: None,
+            txn: None,
+            expire_at_ms: 0,
+            now_ms: 0,
+            field: vec![],
+            start: 0,
+            stop: 0,
+        }
+    }
+
+    /// compare_and_set builds a CompareAndSet command that sets `key` to `value` if the current
+    /// value is `expected`. `None` expects the key to be absent.
+    pub fn compare_and_set(key: &[u8], expected: Option<&[u8]>, value: &[u8]) -> Command {
+        Command {
+            expected: expected.map(|v| ExpectedValue { value: v.to_vec() }),
+            ..Command::of(OpCode::CompareAndSet, key, value)
+        }
+    }
+
+    /// watch builds a Watch command that aborts the instance containing it, if the value of
+    /// `key` is not `expected` when the instance is executed.
+    pub fn watch(key: &[u8], expected: Option<&[u8]>) -> Command {
+        Command {
+            expected: expected.map(|v| ExpectedValue { value: v.to_vec() }),
+            ..Command::of(OpCode::Watch, key, &[])
+        }
+    }
+
+    /// expire builds an Expire command that makes `key` expire at `expire_at_ms`.
+    pub fn expire(key: &[u8], expire_at_ms: i64) -> Command {
+        Command {
+            expire_at_ms,
+            ..Command::of(OpCode::Expire, key, &[])
+        }
+    }
+
+    /// member builds a command on `field` of the collection `key`, such as HSet or SAdd.
+    pub fn member(op: OpCode, key: &[u8], field: &[u8], value: &[u8]) -> Command {
+        Command {
+            field: field.to_vec(),
+            ..Command::of(op, key, value)
+        }
+    }
+
+    /// range builds a LRange or ZRange command that reads from `start` to `stop` inclusive.
+    pub fn range(op: OpCode, key: &[u8], start: i64, stop: i64) -> Command {
+        Command {
+            start,
+            stop,
+            ..Command::of(op, key, &[])
         }
     }
+
+    /// read_range builds a ReadRange command that reads at most `limit` string keys and values in
+    /// `[start, end)`. An empty `end` is unbounded and a `limit` of 0 is unlimited.
+    pub fn read_range(start: &[u8], end: &[u8], limit: i64, reverse: bool) -> Command {
+        let value = if reverse { REVERSE } else { &[] };
+        Command {
+            field: end.to_vec(),
+            stop: limit,
+            ..Command::of(OpCode::ReadRange, start, value)
+        }
+    }
+
+    /// delete_range builds a DeleteRange command that removes every key in `[start, end)`. An
+    /// empty `end` is unbounded.
+    pub fn delete_range(start: &[u8], end: &[u8]) -> Command {
+        Command {
+            field: end.to_vec(),
+            ..Command::of(OpCode::DeleteRange, start, &[])
+        }
+    }
+
+    /// covers returns true if the command operates on `key`. A ReadRange or DeleteRange operates
+    /// on every key in its range.
+    pub fn covers(&self, key: &[u8]) -> bool {
+        if self.op == OpCode::ReadRange as i32 || self.op == OpCode::DeleteRange as i32 {
+            return &self.key[..] <= key && (self.field.is_empty() || key < &self.field[..]);
+        }
+        &self.key[..] == key
+    }
+
+    /// is_write returns true if the command may change the value of its key.
+    pub fn is_write(&self) -> bool {
+        match OpCode::from_i32(self.op) {
+            Some(OpCode::NoOp)
+            | Some(OpCode::Get)
+            | Some(OpCode::Watch)
+            | Some(OpCode::Ttl)
+            | Some(OpCode::Type)
+            | Some(OpCode::HGet)
+            | Some(OpCode::HGetAll)
+            | Some(OpCode::HLen)
+            | Some(OpCode::HExists)
+            | Some(OpCode::LRange)
+            | Some(OpCode::LLen)
+            | Some(OpCode::SMembers)
+            | Some(OpCode::SIsMember)
+            | Some(OpCode::SCard)
+            | Some(OpCode::ZRange)
+            | Some(OpCode::ZScore)
+            | Some(OpCode::ZCard)
+            | Some(OpCode::ReadRange) => false,
+            _ => true,
+        }
+    }
+}
+
+/// now_ms returns the current time in milliseconds since epoch.
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
 }
 
 impl Conflict for Command {
@@ -103,8 +219,8 @@ impl Conflict for Command {
             return false;
         }
 
-        if self.op == OpCode::Set as i32 || with.op == OpCode::Set as i32 {
-            return self.key == with.key;
+        if self.is_write() || with.is_write() {
+            return self.covers(&with.key) || with.covers(&self.key);
         }
 
         false
@@ -416,4 +532,12 @@ impl MakeRequest {
         let p = PrepareRequest {};
         make_req!(to_replica_id, inst, p)
     }
+
+    pub fn try_pre_accept(to_replica_id: i64, inst: &Instance) -> ReplicateRequest {
+        let p = TryPreAcceptRequest {
+            cmds: inst.cmds.clone(),
+            deps: inst.deps.clone(),
+        };
+        make_req!(to_replica_id, inst, p)
+    }
 }
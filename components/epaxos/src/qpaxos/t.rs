@@ -287,6 +287,20 @@ fn test_request_commit_pb() {
     assert_eq!(inst.final_deps, req.final_deps);
 }
 
+#[test]
+fn test_request_try_pre_accept_pb() {
+    let inst = new_foo_inst();
+
+    let pp = MakeRequest::try_pre_accept(100, &inst);
+    test_enc_dec!(pp, ReplicateRequest);
+
+    let req: TryPreAcceptRequest = pp.phase.unwrap().try_into().unwrap();
+
+    test_request_common!(pp, inst, 100);
+    assert_eq!(inst.cmds, req.cmds);
+    assert_eq!(inst.deps, req.deps);
+}
+
 #[test]
 fn test_replicate_reply_pb() {
     let reply = ReplicateReply {
@@ -14,6 +14,13 @@ fn test_command_from() {
         op: OpCode::Set as i32,
         key: "key".as_bytes().to_vec(),
         value: "value".as_bytes().to_vec(),
+        expected: None,
+        txn: None,
+        expire_at_ms: 0,
+        now_ms: 0,
+        field: vec![],
+        start: 0,
+        stop: 0,
     };
 
     assert_eq!(c, (OpCode::Set, "key", "value").into());
@@ -51,6 +58,111 @@ fn test_command_conflit() {
     assert!(!sy.conflict(&sx));
 }
 
+#[test]
+fn test_command_conflict_writes() {
+    let gx = Command::from(("Get", "x", ""));
+    let gy = Command::from(("Get", "y", ""));
+
+    let writes = vec![
+        Command::from(("Delete", "x", "")),
+        Command::from(("Incr", "x", "1")),
+        Command::from(("SetIfAbsent", "x", "1")),
+        Command::from(("Append", "x", "1")),
+        Command::compare_and_set(b"x", None, b"1"),
+        Command::member(OpCode::HSet, b"x", b"f", b"1"),
+        Command::from(("LPop", "x", "")),
+    ];
+
+    for w in writes.iter() {
+        assert!(w.is_write());
+        assert!(w.conflict(&gx));
+        assert!(gx.conflict(w));
+        assert!(!w.conflict(&gy));
+    }
+
+    assert!(!gx.is_write());
+
+    // Watch reads the key.
+    let wx = Command::watch(b"x", None);
+    assert!(!wx.is_write());
+    assert!(!wx.conflict(&gx));
+    for w in writes.iter() {
+        assert!(wx.conflict(w));
+    }
+
+    // reading a collection does not conflict with other reads.
+    let hx = Command::member(OpCode::HGet, b"x", b"f", b"");
+    let zx = Command::range(OpCode::ZRange, b"x", 0, -1);
+    assert!(!hx.is_write());
+    assert!(!zx.is_write());
+    assert!(!hx.conflict(&zx));
+    assert!(!hx.conflict(&gx));
+}
+
+#[test]
+fn test_command_conflict_read_range() {
+    let r = Command::read_range(b"b", b"d", 10, false);
+    let open = Command::read_range(b"b", b"", 0, true);
+    assert!(!r.is_write());
+    assert_eq!(REVERSE, &open.value[..]);
+
+    for k in ["b", "c", "cz"].iter() {
+        let sk = Command::from(("Set", *k, "1"));
+        assert!(r.conflict(&sk), "{}", k);
+        assert!(sk.conflict(&r), "{}", k);
+        assert!(open.conflict(&sk), "{}", k);
+    }
+
+    for k in ["a", "d", "z"].iter() {
+        let sk = Command::from(("Set", *k, "1"));
+        assert!(!r.conflict(&sk), "{}", k);
+        assert!(!sk.conflict(&r), "{}", k);
+    }
+    assert!(open.conflict(&Command::from(("Set", "z", "1"))));
+    assert!(!open.conflict(&Command::from(("Set", "a", "1"))));
+
+    // reads do not conflict.
+    assert!(!r.conflict(&Command::from(("Get", "c", ""))));
+    assert!(!r.conflict(&open));
+}
+
+#[test]
+fn test_command_conflict_delete_range() {
+    let d = Command::delete_range(b"b", b"d");
+    let all = Command::delete_range(b"", b"");
+    assert!(d.is_write());
+    assert!(all.is_write());
+
+    for k in ["b", "c", "cz"].iter() {
+        let gk = Command::from(("Get", *k, ""));
+        assert!(d.conflict(&gk), "{}", k);
+        assert!(gk.conflict(&d), "{}", k);
+    }
+    for k in ["a", "d", "z"].iter() {
+        let gk = Command::from(("Get", *k, ""));
+        assert!(!d.conflict(&gk), "{}", k);
+        assert!(all.conflict(&gk), "{}", k);
+    }
+
+    // overlapping ranges conflict.
+    assert!(d.conflict(&Command::read_range(b"a", b"c", 0, false)));
+    assert!(Command::read_range(b"c", b"", 0, false).conflict(&d));
+    assert!(!d.conflict(&Command::read_range(b"d", b"", 0, false)));
+    assert!(all.conflict(&d));
+}
+
+#[test]
+fn test_command_compare_and_set() {
+    let c = Command::compare_and_set(b"x", Some(&b""[..]), b"1");
+    assert_eq!(OpCode::CompareAndSet as i32, c.op);
+    assert_eq!(Some(ExpectedValue { value: vec![] }), c.expected);
+
+    test_enc_dec!(c, Command);
+
+    let c = Command::compare_and_set(b"x", None, b"1");
+    assert_eq!(None, c.expected);
+}
+
 #[test]
 fn test_macro_cmds() {
     let cmds = cmds![("Set", "key", "value"), (OpCode::Get, "a", "b")];
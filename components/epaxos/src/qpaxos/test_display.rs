@@ -35,7 +35,14 @@ fn test_display_command() {
             Command {
                 op: OpCode::NoOp as i32,
                 key: k.clone(),
-                value: v.clone()
+                value: v.clone(),
+                expected: None,
+                txn: None,
+                expire_at_ms: 0,
+                now_ms: 0,
+                field: vec![],
+                start: 0,
+                stop: 0,
             }
         )
     );
@@ -46,7 +53,14 @@ fn test_display_command() {
             Command {
                 op: OpCode::Get as i32,
                 key: k.clone(),
-                value: v.clone()
+                value: v.clone(),
+                expected: None,
+                txn: None,
+                expire_at_ms: 0,
+                now_ms: 0,
+                field: vec![],
+                start: 0,
+                stop: 0,
             }
         )
     );
@@ -57,7 +71,14 @@ fn test_display_command() {
             Command {
                 op: OpCode::Set as i32,
                 key: k.clone(),
-                value: v.clone()
+                value: v.clone(),
+                expected: None,
+                txn: None,
+                expire_at_ms: 0,
+                now_ms: 0,
+                field: vec![],
+                start: 0,
+                stop: 0,
             }
         )
     );
@@ -68,12 +89,64 @@ fn test_display_command() {
             Command {
                 op: OpCode::Delete as i32,
                 key: k.clone(),
-                value: v.clone()
+                value: v.clone(),
+                expected: None,
+                txn: None,
+                expire_at_ms: 0,
+                now_ms: 0,
+                field: vec![],
+                start: 0,
+                stop: 0,
             }
         )
     );
 }
 
+#[test]
+fn test_display_rmw_command() {
+    let cases = vec![
+        ("Incr:k+=1", Command::from(("Incr", "k", "1"))),
+        ("SetIfAbsent:k=v", Command::from(("SetIfAbsent", "k", "v"))),
+        (
+            "CompareAndSet:k=v",
+            Command::compare_and_set(b"k", None, b"v"),
+        ),
+        ("Append:k+v", Command::from(("Append", "k", "v"))),
+        ("Watch:k", Command::watch(b"k", None)),
+        ("TxnPrepare:k", Command::from(("TxnPrepare", "k", ""))),
+        ("TxnCommit:k#0", Command::from(("TxnCommit", "k", ""))),
+        ("TxnAbort:k", Command::from(("TxnAbort", "k", ""))),
+        ("TxnForget:k", Command::from(("TxnForget", "k", ""))),
+        ("Expire:k@1000", Command::expire(b"k", 1000)),
+        ("Persist:k", Command::from(("Persist", "k", ""))),
+        ("Ttl:k", Command::from(("Ttl", "k", ""))),
+        ("DeleteExpired:k", Command::from(("DeleteExpired", "k", ""))),
+        ("Type:k", Command::from(("Type", "k", ""))),
+        (
+            "HSet:k/f=v",
+            Command::member(OpCode::HSet, b"k", b"f", b"v"),
+        ),
+        ("LPush:k=v", Command::from(("LPush", "k", "v"))),
+        (
+            "ZRange:k[0,-1]",
+            Command::range(OpCode::ZRange, b"k", 0, -1),
+        ),
+        (
+            "ReadRange:[a,b)#10",
+            Command::read_range(b"a", b"b", 10, false),
+        ),
+        (
+            "ReadRange:[a,)#0 reverse",
+            Command::read_range(b"a", b"", 0, true),
+        ),
+        ("DeleteRange:[a,)", Command::delete_range(b"a", b"")),
+    ];
+
+    for (want, cmd) in cases.iter() {
+        assert_eq!(*want, format!("{}", cmd));
+    }
+}
+
 #[test]
 fn test_display_instance_id_vec() {
     assert_eq!(
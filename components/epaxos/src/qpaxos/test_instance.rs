@@ -48,6 +48,7 @@ fn test_macro_inst_all_arg() {
         final_deps: Some(InstanceIdVec {
             ids: vec![(13, 14).into(), (15, 16).into()],
         }),
+        accepted_ballot: None,
         committed: true,
         executed: true,
     };
@@ -93,6 +94,7 @@ fn test_macro_inst() {
         }),
         deps: None,
         final_deps: None,
+        accepted_ballot: None,
         committed: false,
         executed: false,
     };
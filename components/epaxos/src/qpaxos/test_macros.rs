@@ -18,6 +18,7 @@ fn test_macro_init_inst() {
             ids: vec![(11, 12).into(), (13, 14).into()],
         }),
         final_deps: None,
+        accepted_ballot: None,
         committed: false,
         executed: false,
     };
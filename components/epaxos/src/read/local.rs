@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use storage::StorageError;
+use tokio::time::delay_for;
+
+use crate::conf::GroupInfo;
+use crate::conf::ReadConsistency;
+use crate::qpaxos::quorum;
+use crate::qpaxos::Command;
+use crate::qpaxos::Conflict;
+use crate::qpaxos::InstanceId;
+use crate::qpaxos::InstanceIdVec;
+use crate::qpaxos::ReplicaId;
+use crate::replica::ExecuteResult;
+use crate::replica::Replica;
+use crate::replica::StatusRecords;
+use crate::replication::bcast_read_index;
+use crate::replication::propose;
+use crate::ReplicationError;
+
+/// default max time a read waits for the local replica to execute the instances in the read
+/// index, before it is proposed as an instance instead.
+pub const DEFAULT_READ_WAIT: Duration = Duration::from_millis(1_000);
+
+/// interval to check whether the local replica has executed the instances in the read index.
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// ReadIndexCache keeps the last read index of a replica, which reads in `bounded_stale` mode
+/// reuse while it is not too old.
+#[derive(Default)]
+pub struct ReadIndexCache {
+    last: Mutex<Option<(Instant, InstanceIdVec)>>,
+}
+
+impl ReadIndexCache {
+    /// get returns the read index asked for within `max_age`, if there is one.
+    pub fn get(&self, max_age: Duration) -> Option<InstanceIdVec> {
+        let last = self.last.lock().unwrap();
+        match &*last {
+            Some((at, index)) if at.elapsed() < max_age => Some(index.clone()),
+            _ => None,
+        }
+    }
+
+    /// set stores a read index, which is asked for since `at`.
+    pub fn set(&self, at: Instant, index: InstanceIdVec) {
+        let mut last = self.last.lock().unwrap();
+        *last = Some((at, index));
+    }
+}
+
+/// read_index returns the max instance of every replica in the group, that is seen by a quorum
+/// including the local replica `r`, or `None` if not enough replicas reply.
+pub async fn read_index(g: &GroupInfo, r: &Replica) -> Result<Option<InstanceIdVec>, StorageError> {
+    let q = quorum(g.replicas.len() as i32);
+
+    let mut index = r.get_max_refs()?;
+    let mut n = 1;
+    if n >= q {
+        return Ok(Some(index));
+    }
+
+    let peers = r.peer_clients.sort_by_liveness(&r.peers);
+    let mut repls = bcast_read_index(&r.peer_clients, &peers);
+
+    while let Some((from_rid, repl)) = repls.recv().await {
+        let maxs = match (repl.err, repl.maxs) {
+            (None, Some(maxs)) => maxs,
+            (err, _) => {
+                println!("bad read-index reply from {:?}: {:?}", from_rid, err);
+                continue;
+            }
+        };
+
+        for iid in maxs.iter() {
+            if index.get(iid.replica_id) < Some(*iid) {
+                index.set(*iid);
+            }
+        }
+
+        n += 1;
+        if n >= q {
+            return Ok(Some(index));
+        }
+    }
+
+    Ok(None)
+}
+
+/// read evaluates read-only commands with the local replica `r` of the group `g`, as if they
+/// are in one instance, by the consistency chosen.
+/// In `bounded_stale` mode, a read index is reused within `max_stale`.
+///
+/// It returns the `ExecuteResult` of every command.
+pub async fn read(
+    cmds: &[Command],
+    g: &GroupInfo,
+    r: &Replica,
+    consistency: ReadConsistency,
+    max_stale: Duration,
+) -> Result<Vec<ExecuteResult>, ReplicationError> {
+    let index = match consistency {
+        ReadConsistency::Stale => return Ok(r.eval_reads(cmds)?),
+        ReadConsistency::Linearizable => read_index(g, r).await?,
+        ReadConsistency::BoundedStale => match r.read_index_cache.get(max_stale) {
+            Some(index) => Some(index),
+            None => {
+                let at = Instant::now();
+                let index = read_index(g, r).await?;
+                if let Some(ref index) = index {
+                    r.read_index_cache.set(at, index.clone());
+                }
+                index
+            }
+        },
+    };
+
+    if let Some(index) = index {
+        let deadline = Instant::now() + DEFAULT_READ_WAIT;
+        let mut next = HashMap::new();
+        while Instant::now() < deadline {
+            if r.is_read_ready(cmds, &index, &mut next)? {
+                return Ok(r.eval_reads(cmds)?);
+            }
+            delay_for(READ_POLL_INTERVAL).await;
+        }
+    }
+
+    propose(cmds, g, r).await
+}
+
+impl Replica {
+    /// is_read_ready returns true if this replica has executed every instance not greater than
+    /// `index` that conflicts with `cmds`, thus `cmds` could be evaluated locally.
+    /// An instance not yet seen, or seen without its commands, is treated as a conflicting one.
+    ///
+    /// `next` is the next instance to check of every replica, kept between calls with the same
+    /// `cmds` and `index`: an instance that does not block the read never does later, thus it is
+    /// not read again.
+    pub fn is_read_ready(
+        &self,
+        cmds: &[Command],
+        index: &InstanceIdVec,
+        next: &mut HashMap<ReplicaId, i64>,
+    ) -> Result<bool, StorageError> {
+        for max in index.iter() {
+            let rid = max.replica_id;
+
+            // instances are executed in order of every replica, thus only those after "exec"
+            // are checked.
+            let exec = self.storage.get_ref("exec", rid)?;
+            let from = exec.map(|x| x.idx + 1).unwrap_or(0);
+            let from = std::cmp::max(from, next.get(&rid).cloned().unwrap_or(0));
+
+            for idx in from..=max.idx {
+                if !self.is_read_ready_at(cmds, (rid, idx).into())? {
+                    next.insert(rid, idx);
+                    return Ok(false);
+                }
+            }
+            next.insert(rid, max.idx + 1);
+        }
+
+        Ok(true)
+    }
+
+    /// is_read_ready_at returns false if the instance `iid` has to be executed before `cmds`.
+    fn is_read_ready_at(&self, cmds: &[Command], iid: InstanceId) -> Result<bool, StorageError> {
+        let inst = match self.storage.get_instance(iid)? {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+
+        if inst.executed {
+            return Ok(true);
+        }
+
+        if inst.cmds.is_empty() && !inst.committed {
+            return Ok(false);
+        }
+
+        Ok(!inst.cmds.iter().any(|c| cmds.iter().any(|x| x.conflict(c))))
+    }
+
+    /// eval_reads evaluates read-only commands with the data this replica has executed, as if
+    /// they are in one instance: none is evaluated if a key watched has changed.
+    pub fn eval_reads(&self, cmds: &[Command]) -> Result<Vec<ExecuteResult>, StorageError> {
+        let _guard = self.exec_lock.read().unwrap();
+
+        let mut existed = HashMap::new();
+        let mut recs = StatusRecords::new();
+
+        if !self.check_watched(cmds, &mut existed, &mut recs)? {
+            return Ok(vec![ExecuteResult::Aborted; cmds.len()]);
+        }
+
+        let mut rst = Vec::with_capacity(cmds.len());
+        for cmd in cmds.iter() {
+            let (_, r) = self.eval_command(cmd, &mut existed, &mut recs)?;
+            rst.push(r);
+        }
+        Ok(rst)
+    }
+}
@@ -0,0 +1,22 @@
+//! read serves read-only commands with the local replica of a group, without replicating an
+//! instance for them.
+//!
+//! A write completes only after it is stored by a quorum, each of which then updates its "max"
+//! ref. Thus a replica that has executed every instance in the max refs of a quorum, the "read
+//! index", sees every write completed before it asks for the index. Only instances conflicting
+//! with the reads need to be executed.
+//!
+//! `ClusterInfo.read_consistency` chooses how often the read index is asked for:
+//! - `linearizable`: for every read.
+//! - `bounded_stale`: at most once in `read_stale_ms`, thus a read may miss writes completed
+//!   within the last `read_stale_ms`.
+//! - `stale`: never, a read is served at once with what the replica has executed.
+//!
+//! A read that can not be served locally in time, e.g., no quorum replies, falls back to be
+//! proposed as an instance.
+
+mod local;
+pub use local::*;
+
+#[cfg(test)]
+mod test_local;
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::delay_for;
+
+use crate::conf::ReadConsistency;
+use crate::qpaxos::*;
+use crate::read::*;
+use crate::replica::ExecuteResult;
+use crate::replication::*;
+use crate::testutil;
+use crate::testutil::TestCluster;
+use storage::MemEngine;
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+fn val(v: &str) -> ExecuteResult {
+    ExecuteResult::SuccessWithVal {
+        value: Some(v.as_bytes().to_vec()),
+    }
+}
+
+#[test]
+fn test_read_index_cache() {
+    let cache = ReadIndexCache::default();
+    assert_eq!(None, cache.get(Duration::from_secs(10)));
+
+    let index: InstanceIdVec = [(1, 2), (2, 3)].into();
+    cache.set(std::time::Instant::now(), index.clone());
+
+    assert_eq!(Some(index), cache.get(Duration::from_secs(10)));
+    assert_eq!(None, cache.get(Duration::from_secs(0)));
+}
+
+#[test]
+fn test_is_read_ready() {
+    let rp = testutil::new_replica(1, vec![1, 2], vec![], Arc::new(MemEngine::new().unwrap()));
+
+    let mut x = foo_inst!((2, 0), [("Set", "x", "1")], [(1, -1), (2, -1)]);
+    x.committed = true;
+    let mut y = foo_inst!((2, 1), [("Set", "y", "1")], [(1, -1), (2, 0)]);
+    y.committed = true;
+    // seen by a Prepare, without commands.
+    let z = Instance {
+        instance_id: Some((2, 2).into()),
+        ..Default::default()
+    };
+
+    for inst in [&x, &y, &z].iter() {
+        rp.storage.set_instance(inst).unwrap();
+    }
+
+    let get = |k: &str| vec![Command::from(("Get", k, ""))];
+    let index = |idx: i64| -> InstanceIdVec { [(1, -1), (2, idx)].into() };
+
+    let cases = vec![
+        (get("x"), index(-1), true),
+        (get("x"), index(0), false),
+        (get("y"), index(0), true),
+        (get("y"), index(1), false),
+        (get("w"), index(1), true),
+        (get("w"), index(2), false),
+        (get("w"), index(3), false),
+    ];
+    for (cmds, idx, want) in cases.iter() {
+        let got = rp.is_read_ready(cmds, idx, &mut HashMap::new()).unwrap();
+        assert_eq!(*want, got, "{:?} {}", cmds, idx);
+    }
+
+    {
+        // a later poll continues from the instance blocking the read.
+        let mut next = HashMap::new();
+        assert!(!rp.is_read_ready(&get("w"), &index(2), &mut next).unwrap());
+        assert_eq!(Some(&0), next.get(&1));
+        assert_eq!(Some(&2), next.get(&2));
+
+        // z is committed with its commands.
+        let mut z = foo_inst!((2, 2), [("Set", "z", "1")], [(1, -1), (2, 1)]);
+        z.committed = true;
+        rp.storage.set_instance(&z).unwrap();
+
+        assert!(rp.is_read_ready(&get("w"), &index(2), &mut next).unwrap());
+        assert_eq!(Some(&3), next.get(&2));
+    }
+
+    rp.execute_commands(vec![x.clone()]).unwrap();
+    assert!(rp
+        .is_read_ready(&get("x"), &index(1), &mut HashMap::new())
+        .unwrap());
+    assert!(!rp
+        .is_read_ready(&get("y"), &index(1), &mut HashMap::new())
+        .unwrap());
+
+    assert_eq!(vec![val("1")], rp.eval_reads(&get("x")).unwrap());
+    assert_eq!(
+        vec![ExecuteResult::SuccessWithVal { value: None }],
+        rp.eval_reads(&get("y")).unwrap()
+    );
+
+    // a watched key is changed.
+    let cmds = vec![Command::watch(b"x", None), Command::from(("Get", "x", ""))];
+    assert_eq!(
+        vec![ExecuteResult::Aborted; 2],
+        rp.eval_reads(&cmds).unwrap()
+    );
+}
+
+#[test]
+fn test_read_linearizable() {
+    _test_read_linearizable();
+}
+
+#[tokio::main]
+async fn _test_read_linearizable() {
+    let mut tc = TestCluster::new(3, 5630);
+    tc.start().await;
+
+    let (g, r0) = tc.server_datas[0].get_local_replica_for_key(b"x").unwrap();
+
+    let cmds = cmds![("Set", "x", "y")];
+    let mut st = replicate(&cmds, g, r0).await.unwrap();
+    commit(&mut st.instance, r0).unwrap();
+    let iid = st.instance.instance_id.unwrap();
+
+    let r1 = tc.replica(1);
+    for _ in 0..100 {
+        let inst = r1.storage.get_instance(iid).unwrap();
+        if inst.map(|x| x.committed) == Some(true) {
+            break;
+        }
+        delay_for(Duration::from_millis(10)).await;
+    }
+
+    // the write is seen by a quorum, and it must be executed before x is read.
+    let index = read_index(g, r1).await.unwrap().unwrap();
+    assert!(index >= iid);
+
+    let get = |k: &str| vec![Command::from(("Get", k, ""))];
+    assert!(!r1
+        .is_read_ready(&get("x"), &index, &mut HashMap::new())
+        .unwrap());
+    assert!(r1
+        .is_read_ready(&get("z"), &index, &mut HashMap::new())
+        .unwrap());
+
+    assert_eq!(vec![iid], r1.execute().unwrap());
+
+    let max_stale = Duration::from_secs(10);
+    for c in [
+        ReadConsistency::Linearizable,
+        ReadConsistency::BoundedStale,
+        ReadConsistency::Stale,
+    ]
+    .iter()
+    {
+        let rsts = read(&get("x"), g, r1, *c, max_stale).await.unwrap();
+        assert_eq!(vec![val("y")], rsts, "{:?}", c);
+    }
+    assert!(r1.read_index_cache.get(max_stale).is_some());
+}
@@ -0,0 +1,479 @@
+use std::collections::BTreeMap;
+use std::str::from_utf8;
+
+use prost::Message;
+use storage::DBColumnFamily;
+use storage::StorageError;
+use storage::WriteEntry;
+
+use crate::qpaxos::CollectionMeta;
+use crate::qpaxos::Command;
+use crate::qpaxos::OpCode;
+use crate::qpaxos::ValueType;
+use crate::replica::set_data_rec;
+use crate::replica::set_existed;
+use crate::replica::set_expire_at;
+use crate::replica::DataRecords;
+use crate::replica::ExecuteResult;
+use crate::replica::Replica;
+use crate::replica::StatusRecords;
+
+/// Collections are stored in the collection column family, apart from strings:
+///
+/// - The meta of the collection at `key` is at `META_PREFIX + key`, thus metas are in the same
+///   order as user keys, which lets a scan list collections along with strings.
+/// - Members are under `MEMBER_PREFIX + len(key) + key`, where the length is 4-byte big-endian,
+///   thus the members of two keys never prefix each other. A member is at this prefix followed
+///   by a tag and the member:
+///   - a field of a hash: `f + field` -> value.
+///   - a member of a set: `m + member` -> empty.
+///   - a member of a sorted set: `m + member` -> score in decimal, along with an index
+///     `s + encode_f64(score) + member` -> empty, which orders members by score.
+///   - an element of a list: `i + encode_i64(index)` -> element.
+pub const META_PREFIX: &[u8] = b"m";
+
+pub const MEMBER_PREFIX: &[u8] = b"d";
+
+/// ZRange returns the scores along with the members if its value is `WITHSCORES`.
+pub const WITHSCORES: &[u8] = b"withscores";
+
+pub const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+const FIELD: u8 = b'f';
+const MEMBER: u8 = b'm';
+const SCORE: u8 = b's';
+const INDEX: u8 = b'i';
+
+pub fn meta_key(key: &[u8]) -> Vec<u8> {
+    [META_PREFIX, key].concat()
+}
+
+/// members_prefix returns the prefix of all members of the collection at `key`.
+pub fn members_prefix(key: &[u8]) -> Vec<u8> {
+    let mut k = MEMBER_PREFIX.to_vec();
+    k.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    k.extend_from_slice(key);
+    k
+}
+
+fn member_key(key: &[u8], tag: u8, member: &[u8]) -> Vec<u8> {
+    let mut k = members_prefix(key);
+    k.push(tag);
+    k.extend_from_slice(member);
+    k
+}
+
+fn score_key(key: &[u8], score: f64, member: &[u8]) -> Vec<u8> {
+    member_key(key, SCORE, &[&encode_f64(score)[..], member].concat())
+}
+
+/// encode_i64 encodes an integer to 8 bytes, which sort in the same order as integers.
+pub fn encode_i64(v: i64) -> [u8; 8] {
+    ((v as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+/// encode_f64 encodes a float that is not NaN to 8 bytes, which sort in the same order as
+/// floats.
+pub fn encode_f64(v: f64) -> [u8; 8] {
+    // -0.0 and 0.0 are the same score.
+    let v = if v == 0.0 { 0.0 } else { v };
+    let b = v.to_bits();
+    let b = if b >> 63 == 1 { !b } else { b | (1 << 63) };
+    b.to_be_bytes()
+}
+
+pub fn decode_f64(v: &[u8]) -> f64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&v[..8]);
+    let b = u64::from_be_bytes(buf);
+    let b = if b >> 63 == 1 { b & !(1 << 63) } else { !b };
+    f64::from_bits(b)
+}
+
+/// parse_score parses a score of a sorted set in decimal, such as `1.5` or `-inf`.
+pub fn parse_score(v: &[u8]) -> Option<f64> {
+    let f = from_utf8(v).ok()?.parse::<f64>().ok()?;
+    if f.is_nan() {
+        None
+    } else {
+        Some(f)
+    }
+}
+
+fn format_score(v: f64) -> Vec<u8> {
+    v.to_string().into_bytes()
+}
+
+/// collection_type returns the type of the collection a command operates on, or `None` if it
+/// is not a command on a collection.
+pub fn collection_type(op: OpCode) -> Option<ValueType> {
+    let t = match op {
+        OpCode::HSet
+        | OpCode::HGet
+        | OpCode::HDel
+        | OpCode::HGetAll
+        | OpCode::HLen
+        | OpCode::HExists => ValueType::Hash,
+        OpCode::LPush
+        | OpCode::RPush
+        | OpCode::LPop
+        | OpCode::RPop
+        | OpCode::LRange
+        | OpCode::LLen => ValueType::List,
+        OpCode::SAdd | OpCode::SRem | OpCode::SMembers | OpCode::SIsMember | OpCode::SCard => {
+            ValueType::Set
+        }
+        OpCode::ZAdd | OpCode::ZRem | OpCode::ZRange | OpCode::ZScore | OpCode::ZCard => {
+            ValueType::ZSet
+        }
+        _ => return None,
+    };
+    Some(t)
+}
+
+/// type_name returns the name of the type of a key, as redis command TYPE does.
+pub fn type_name(cur: &Option<Vec<u8>>, meta: &Option<CollectionMeta>) -> &'static str {
+    if cur.is_some() {
+        return "string";
+    }
+
+    match meta.as_ref().and_then(|m| ValueType::from_i32(m.typ)) {
+        Some(ValueType::String) => "string",
+        Some(ValueType::Hash) => "hash",
+        Some(ValueType::List) => "list",
+        Some(ValueType::Set) => "set",
+        Some(ValueType::ZSet) => "zset",
+        None => "none",
+    }
+}
+
+/// range_of converts an inclusive range of indexes, which may be negative to count from the
+/// end, to the first index and the number of elements in a collection of `len` elements.
+pub fn range_of(start: i64, stop: i64, len: i64) -> Option<(i64, i64)> {
+    let start = if start < 0 { len + start } else { start };
+    let stop = if stop < 0 { len + stop } else { stop };
+
+    let start = start.max(0);
+    let stop = stop.min(len - 1);
+
+    if start > stop {
+        None
+    } else {
+        Some((start, stop - start + 1))
+    }
+}
+
+/// set_collection_rec updates a collection record in `existed` and returns the entry to write it.
+fn set_collection_rec(existed: &mut DataRecords, k: Vec<u8>, v: Option<Vec<u8>>) -> WriteEntry {
+    set_data_rec(existed, DBColumnFamily::Collection, k, v)
+}
+
+impl Replica {
+    /// get_collection_rec returns the latest value of a collection record, from `existed` or
+    /// storage.
+    fn get_collection_rec(
+        &self,
+        key: &[u8],
+        existed: &mut DataRecords,
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        self.get_data_rec(DBColumnFamily::Collection, key, existed)
+    }
+
+    /// get_meta returns the meta of the collection at `key`, or `None` if `key` is not a
+    /// collection.
+    pub(crate) fn get_meta(
+        &self,
+        key: &[u8],
+        existed: &mut DataRecords,
+    ) -> Result<Option<CollectionMeta>, StorageError> {
+        let v = self.get_collection_rec(&meta_key(key), existed)?;
+        Ok(v.and_then(|x| CollectionMeta::decode(&x[..]).ok()))
+    }
+
+    /// get_alive_meta is like `get_alive`, for the collection at the key of `cmd`.
+    pub(crate) fn get_alive_meta(
+        &self,
+        cmd: &Command,
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<(Option<CollectionMeta>, bool), StorageError> {
+        let meta = self.get_meta(&cmd.key, existed)?;
+        if meta.is_none() {
+            return Ok((None, false));
+        }
+
+        match self.get_expire_at(&cmd.key, recs)? {
+            Some(at) if at <= cmd.now_ms => Ok((None, true)),
+            _ => Ok((meta, false)),
+        }
+    }
+
+    /// list_members returns the records with keys starting with `prefix`, in key order, from
+    /// storage along with the changes in `existed`.
+    fn list_members(&self, prefix: &[u8], existed: &DataRecords) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut rst = BTreeMap::new();
+
+        let mut k = prefix.to_vec();
+        while let Some((mk, v)) = self.storage.next(DBColumnFamily::Collection, &k, false) {
+            if !mk.starts_with(prefix) {
+                break;
+            }
+            rst.insert(mk.clone(), v);
+            k = mk;
+        }
+
+        for ((cf, k), v) in existed.iter() {
+            if *cf != DBColumnFamily::Collection || !k.starts_with(prefix) {
+                continue;
+            }
+            match v {
+                Some(v) => rst.insert(k.clone(), v.clone()),
+                None => rst.remove(k),
+            };
+        }
+
+        rst.into_iter().collect()
+    }
+
+    /// delete_collection returns the entries to remove the collection at `key` and all its
+    /// members. It does not remove the expire-at of `key`.
+    pub(crate) fn delete_collection(
+        &self,
+        key: &[u8],
+        existed: &mut DataRecords,
+    ) -> Result<Vec<WriteEntry>, StorageError> {
+        let mk = meta_key(key);
+        if self.get_collection_rec(&mk, existed)?.is_none() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = vec![];
+        for (k, _) in self.list_members(&members_prefix(key), existed) {
+            entries.push(set_collection_rec(existed, k, None));
+        }
+        entries.push(set_collection_rec(existed, mk, None));
+
+        Ok(entries)
+    }
+
+    /// eval_collection evaluates `Type` or a command on a collection.
+    /// `cur` is the string value of the key and `meta` is the collection at it, both `None` if
+    /// the key is expired, in which case `expired` is true.
+    ///
+    /// A collection is created by the first command adding a member, and is removed with its
+    /// expire-at when the last member is removed.
+    pub(crate) fn eval_collection(
+        &self,
+        op: OpCode,
+        cmd: &Command,
+        cur: Option<Vec<u8>>,
+        meta: Option<CollectionMeta>,
+        expired: bool,
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<(Vec<WriteEntry>, ExecuteResult), StorageError> {
+        let val = |v: Option<Vec<u8>>| ExecuteResult::SuccessWithVal { value: v };
+        let vals = |values: Vec<Vec<u8>>| ExecuteResult::SuccessWithVals { values };
+        let int = |n: i64| val(Some(n.to_string().into_bytes()));
+        let flag = |b: bool| int(b as i64);
+        let err = |msg: &str| Ok((vec![], ExecuteResult::Error(msg.to_owned())));
+
+        if op == OpCode::Type {
+            let name = type_name(&cur, &meta);
+            return Ok((vec![], val(Some(name.as_bytes().to_vec()))));
+        }
+
+        let typ = match collection_type(op) {
+            Some(v) => v,
+            None => return err("ERR unexpected command"),
+        };
+
+        if cur.is_some() || meta.as_ref().map(|m| m.typ != typ as i32) == Some(true) {
+            return err(WRONGTYPE);
+        }
+
+        // check arguments before anything is changed.
+        let score = if op == OpCode::ZAdd {
+            match parse_score(&cmd.value) {
+                Some(v) => v,
+                None => return err("ERR value is not a valid float"),
+            }
+        } else {
+            0.0
+        };
+
+        let key = &cmd.key;
+        let write = cmd.is_write();
+        let mut entries = vec![];
+
+        // an expired key is removed before a new collection is created at it.
+        if expired && write {
+            entries.extend(self.delete_collection(key, existed)?);
+            entries.push(set_existed(existed, key.clone(), None));
+            entries.push(set_expire_at(recs, key, None));
+        }
+
+        let mut m = meta.unwrap_or(CollectionMeta {
+            typ: typ as i32,
+            ..Default::default()
+        });
+
+        let r = match op {
+            OpCode::HSet | OpCode::SAdd => {
+                let (tag, v) = match op {
+                    OpCode::HSet => (FIELD, cmd.value.clone()),
+                    _ => (MEMBER, vec![]),
+                };
+                let k = member_key(key, tag, &cmd.field);
+                let absent = self.get_collection_rec(&k, existed)?.is_none();
+                entries.push(set_collection_rec(existed, k, Some(v)));
+                m.len += absent as i64;
+                flag(absent)
+            }
+            OpCode::HDel | OpCode::SRem => {
+                let tag = if op == OpCode::HDel { FIELD } else { MEMBER };
+                let k = member_key(key, tag, &cmd.field);
+                let present = self.get_collection_rec(&k, existed)?.is_some();
+                if present {
+                    entries.push(set_collection_rec(existed, k, None));
+                    m.len -= 1;
+                }
+                flag(present)
+            }
+            OpCode::HGet => {
+                val(self.get_collection_rec(&member_key(key, FIELD, &cmd.field), existed)?)
+            }
+            OpCode::HExists | OpCode::SIsMember => {
+                let tag = if op == OpCode::HExists { FIELD } else { MEMBER };
+                let k = member_key(key, tag, &cmd.field);
+                flag(self.get_collection_rec(&k, existed)?.is_some())
+            }
+            OpCode::HLen | OpCode::LLen | OpCode::SCard | OpCode::ZCard => int(m.len),
+            OpCode::HGetAll | OpCode::SMembers => {
+                let with_value = op == OpCode::HGetAll;
+                let prefix = member_key(key, if with_value { FIELD } else { MEMBER }, &[]);
+
+                let mut values = vec![];
+                for (k, v) in self.list_members(&prefix, existed) {
+                    values.push(k[prefix.len()..].to_vec());
+                    if with_value {
+                        values.push(v);
+                    }
+                }
+                vals(values)
+            }
+
+            OpCode::LPush | OpCode::RPush => {
+                let idx = if op == OpCode::LPush {
+                    m.head -= 1;
+                    m.head
+                } else {
+                    m.tail += 1;
+                    m.tail - 1
+                };
+                let k = member_key(key, INDEX, &encode_i64(idx));
+                entries.push(set_collection_rec(existed, k, Some(cmd.value.clone())));
+                m.len = m.tail - m.head;
+                int(m.len)
+            }
+            OpCode::LPop | OpCode::RPop => {
+                if m.len == 0 {
+                    val(None)
+                } else {
+                    let idx = if op == OpCode::LPop {
+                        m.head += 1;
+                        m.head - 1
+                    } else {
+                        m.tail -= 1;
+                        m.tail
+                    };
+                    let k = member_key(key, INDEX, &encode_i64(idx));
+                    let v = self.get_collection_rec(&k, existed)?;
+                    entries.push(set_collection_rec(existed, k, None));
+                    m.len = m.tail - m.head;
+                    val(v)
+                }
+            }
+            OpCode::LRange => {
+                let mut values = vec![];
+                if let Some((start, n)) = range_of(cmd.start, cmd.stop, m.len) {
+                    for i in start..start + n {
+                        let k = member_key(key, INDEX, &encode_i64(m.head + i));
+                        values.push(self.get_collection_rec(&k, existed)?.unwrap_or_default());
+                    }
+                }
+                vals(values)
+            }
+
+            OpCode::ZAdd => {
+                let k = member_key(key, MEMBER, &cmd.field);
+                let old = self.get_collection_rec(&k, existed)?;
+                if let Some(s) = old.as_ref().and_then(|x| parse_score(x)) {
+                    entries.push(set_collection_rec(
+                        existed,
+                        score_key(key, s, &cmd.field),
+                        None,
+                    ));
+                }
+                entries.push(set_collection_rec(existed, k, Some(format_score(score))));
+                entries.push(set_collection_rec(
+                    existed,
+                    score_key(key, score, &cmd.field),
+                    Some(vec![]),
+                ));
+                m.len += old.is_none() as i64;
+                flag(old.is_none())
+            }
+            OpCode::ZRem => {
+                let k = member_key(key, MEMBER, &cmd.field);
+                let old = self.get_collection_rec(&k, existed)?;
+                if let Some(s) = old.as_ref().and_then(|x| parse_score(x)) {
+                    entries.push(set_collection_rec(
+                        existed,
+                        score_key(key, s, &cmd.field),
+                        None,
+                    ));
+                }
+                if old.is_some() {
+                    entries.push(set_collection_rec(existed, k, None));
+                    m.len -= 1;
+                }
+                flag(old.is_some())
+            }
+            OpCode::ZScore => {
+                val(self.get_collection_rec(&member_key(key, MEMBER, &cmd.field), existed)?)
+            }
+            OpCode::ZRange => {
+                let mut values = vec![];
+                if let Some((start, n)) = range_of(cmd.start, cmd.stop, m.len) {
+                    let prefix = member_key(key, SCORE, &[]);
+                    let members = self.list_members(&prefix, existed);
+                    for (k, _) in members.iter().skip(start as usize).take(n as usize) {
+                        let sub = &k[prefix.len()..];
+                        values.push(sub[8..].to_vec());
+                        if cmd.value == WITHSCORES {
+                            values.push(format_score(decode_f64(&sub[..8])));
+                        }
+                    }
+                }
+                vals(values)
+            }
+
+            _ => return err("ERR unexpected command"),
+        };
+
+        if write {
+            let mk = meta_key(key);
+            if m.len > 0 {
+                let mut v = vec![];
+                m.encode(&mut v).unwrap();
+                entries.push(set_collection_rec(existed, mk, Some(v)));
+            } else if self.get_collection_rec(&mk, existed)?.is_some() {
+                entries.push(set_collection_rec(existed, mk, None));
+                entries.push(set_expire_at(recs, key, None));
+            }
+        }
+
+        Ok((entries, r))
+    }
+}
@@ -2,23 +2,135 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::time::SystemTime;
 
-use crate::qpaxos::{Instance, InstanceId, InstanceIdVec, OpCode};
+use crate::qpaxos::{Command, ExecResult, ExecStatus, Instance, InstanceId, InstanceIdVec, OpCode};
+use crate::replica::is_txn_prepare;
+use crate::replica::is_txn_resolve;
+use crate::replica::set_expire_at;
 use crate::replica::Replica;
+use crate::replica::WRONGTYPE;
+use storage::DBColumnFamily;
 use storage::StorageError;
 use storage::WriteEntry;
 
+/// DataRecords caches user data read or written by commands in a batch, i.e., strings in the
+/// default column family and collections in the collection column family. A record is keyed by
+/// its column family along with its key, thus a string never collides with a collection record.
+pub type DataRecords = HashMap<(DBColumnFamily, Vec<u8>), Option<Vec<u8>>>;
+
+/// StatusRecords caches records in the status column family read or written by commands in a
+/// batch, such as transaction intents or expire-at of keys, like `existed` does for user data.
+pub type StatusRecords = HashMap<Vec<u8>, Option<Vec<u8>>>;
+
 thread_local! {
     static PROBLEM_INSTS: RefCell<Vec<(InstanceId, SystemTime)>> = RefCell::new(vec![]);
 }
 
+/// ExecuteResult is the result of executing a command.
+///
+/// A conditional command such as `SetIfAbsent` or `CompareAndSet` returns the value before
+/// executing it, thus the caller tells whether it took effect by comparing the value.
+/// `Incr` and `Append` return the value after executing.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ExecuteResult {
     Success,
-    SuccessWithVal { value: Option<Vec<u8>> },
+    SuccessWithVal {
+        value: Option<Vec<u8>>,
+    },
+
+    /// values read from a collection, such as fields and values of a hash.
+    SuccessWithVals {
+        values: Vec<Vec<u8>>,
+    },
+
+    /// the command is not applied, e.g., `Incr` a non-integer value.
+    Error(String),
+
+    /// the instance is aborted by a `Watch` command, none of its commands is applied.
+    Aborted,
+}
+
+impl From<&ExecuteResult> for ExecResult {
+    fn from(r: &ExecuteResult) -> Self {
+        let (status, values, error) = match r {
+            ExecuteResult::Success => (ExecStatus::Success, vec![], String::new()),
+            ExecuteResult::SuccessWithVal { value } => (
+                ExecStatus::SuccessWithVal,
+                value.iter().cloned().collect(),
+                String::new(),
+            ),
+            ExecuteResult::SuccessWithVals { values } => {
+                (ExecStatus::SuccessWithVals, values.clone(), String::new())
+            }
+            ExecuteResult::Error(e) => (ExecStatus::Error, vec![], e.clone()),
+            ExecuteResult::Aborted => (ExecStatus::Aborted, vec![], String::new()),
+        };
+
+        ExecResult {
+            status: status as i32,
+            values,
+            error,
+        }
+    }
+}
+
+impl From<ExecResult> for ExecuteResult {
+    fn from(r: ExecResult) -> Self {
+        match ExecStatus::from_i32(r.status) {
+            Some(ExecStatus::Success) => ExecuteResult::Success,
+            Some(ExecStatus::SuccessWithVal) => ExecuteResult::SuccessWithVal {
+                value: r.values.into_iter().next(),
+            },
+            Some(ExecStatus::SuccessWithVals) => {
+                ExecuteResult::SuccessWithVals { values: r.values }
+            }
+            Some(ExecStatus::Error) => ExecuteResult::Error(r.error),
+            Some(ExecStatus::Aborted) => ExecuteResult::Aborted,
+            None => ExecuteResult::Error(format!("ERR unknown result status {}", r.status)),
+        }
+    }
+}
+
+/// incr adds `delta` to `cur`, both in decimal.
+fn incr(cur: Option<&Vec<u8>>, delta: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let parse = |v: &[u8]| {
+        std::str::from_utf8(v)
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .ok_or("ERR value is not an integer or out of range")
+    };
+
+    let n = match cur {
+        Some(v) => parse(v)?,
+        None => 0,
+    };
+    let delta = parse(delta)?;
+
+    let n = n
+        .checked_add(delta)
+        .ok_or("ERR increment or decrement would overflow")?;
+
+    Ok(n.to_string().into_bytes())
 }
 
 impl Replica {
-    fn recover_instances(&self, _inst_ids: &InstanceIdVec) {}
+    /// recover_instances marks instances to recover. They are recovered by the caller of
+    /// `execute()` with `take_recover_insts()`, since recovery needs to talk to other replicas.
+    fn recover_instances(&self, inst_ids: &InstanceIdVec) {
+        let mut to_recover = self.to_recover.lock().unwrap();
+        for iid in inst_ids.iter() {
+            to_recover.insert(*iid);
+        }
+    }
+
+    /// take_recover_insts returns and clears instances found by `execute()` that need to be
+    /// recovered.
+    pub fn take_recover_insts(&self) -> Vec<InstanceId> {
+        let mut to_recover = self.to_recover.lock().unwrap();
+        let iids = to_recover.iter().cloned().collect();
+        to_recover.clear();
+        iids
+    }
+
     // R1          R2
     // -------------
     // |           |
@@ -46,17 +158,18 @@ impl Replica {
                 continue;
             }
 
-            let missing: InstanceId = match exec_up_to.get(dep_iid.replica_id) {
-                None => (dep_iid.replica_id, 0).into(),
-                Some(iid) => {
-                    if dep_iid.idx <= iid.idx {
-                        continue;
-                    }
-
-                    (dep_iid.replica_id, iid.idx + 1).into()
-                }
+            // a replica without any executed instance is treated as executed up to -1.
+            let exec_idx = match exec_up_to.get(dep_iid.replica_id) {
+                None => -1,
+                Some(iid) => iid.idx,
             };
 
+            if dep_iid.idx <= exec_idx {
+                continue;
+            }
+
+            let missing: InstanceId = (dep_iid.replica_id, exec_idx + 1).into();
+
             if let Some(_) = rst.get(dep_iid.replica_id) {
                 continue;
             }
@@ -70,13 +183,31 @@ impl Replica {
         None
     }
 
+    /// execute_commands executes instances in order, and writes their changes and the
+    /// executed flags in one batch.
+    ///
+    /// Keys removed by a DeleteRange are still read from storage until the batch is written,
+    /// thus instances after an instance with a DeleteRange are executed in another batch.
     pub fn execute_commands(
         &self,
         mut insts: Vec<Instance>,
     ) -> Result<Vec<InstanceId>, StorageError> {
+        let del_range = insts
+            .iter()
+            .position(|inst| inst.cmds.iter().any(|c| c.op == OpCode::DeleteRange as i32));
+        if let Some(i) = del_range {
+            if i + 1 < insts.len() {
+                let rest = insts.split_off(i + 1);
+                let mut rst = self.execute_commands(insts)?;
+                rst.extend(self.execute_commands(rest)?);
+                return Ok(rst);
+            }
+        }
+
         let mut rst = Vec::with_capacity(insts.len());
         let mut entrys: Vec<WriteEntry> = Vec::with_capacity(insts.len());
         let mut existed = HashMap::new();
+        let mut recs = StatusRecords::new();
         let mut replys = Vec::with_capacity(insts.len());
 
         for inst in insts.iter() {
@@ -84,28 +215,22 @@ impl Replica {
             rst.push(iid);
 
             let mut repl = Vec::with_capacity(inst.cmds.len());
-            for cmd in inst.cmds.iter() {
-                entrys.push(cmd.into());
-
-                if cmd.op == OpCode::Get as i32 {
-                    if !existed.contains_key(&cmd.key) {
-                        let v = self.storage.get_kv(&cmd.key)?;
-                        existed.insert(&cmd.key, v);
-                    }
-                    repl.push(ExecuteResult::SuccessWithVal {
-                        value: existed[&cmd.key].clone(),
-                    });
-                } else if cmd.op == OpCode::NoOp as i32 {
-                    repl.push(ExecuteResult::Success);
-                } else {
-                    let v = if cmd.op == OpCode::Delete as i32 {
-                        None
+            if is_txn_prepare(&inst.cmds) {
+                let (es, rs) = self.prepare_txn(&inst.cmds, &mut existed, &mut recs)?;
+                entrys.extend(es);
+                repl = rs;
+            } else if self.check_watched(&inst.cmds, &mut existed, &mut recs)? {
+                for cmd in inst.cmds.iter() {
+                    let (es, r) = if is_txn_resolve(cmd) {
+                        self.resolve_txn(cmd, &mut existed, &mut recs)?
                     } else {
-                        Some(cmd.value.clone())
+                        self.eval_command(cmd, &mut existed, &mut recs)?
                     };
-                    existed.insert(&cmd.key, v);
-                    repl.push(ExecuteResult::Success);
+                    entrys.extend(es);
+                    repl.push(r);
                 }
+            } else {
+                repl.resize(inst.cmds.len(), ExecuteResult::Aborted);
             }
 
             entrys.push(iid.into());
@@ -117,11 +242,233 @@ impl Replica {
             entrys.push(inst.into());
         }
 
-        // TODO send replys to client
-        self.storage.write_batch(&entrys)?;
+        {
+            let _guard = self.exec_lock.write().unwrap();
+            self.storage.write_batch(&entrys)?;
+        }
+
+        for (iid, repl) in rst.iter().zip(replys) {
+            self.waiters.notify(*iid, repl);
+        }
+
         Ok(rst)
     }
 
+    /// check_watched returns false if the value of any key watched by a `Watch` command in `cmds`
+    /// is not the expected one, before any command in `cmds` is evaluated.
+    pub(crate) fn check_watched(
+        &self,
+        cmds: &[Command],
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<bool, StorageError> {
+        for cmd in cmds.iter() {
+            if cmd.op != OpCode::Watch as i32 {
+                continue;
+            }
+
+            let (cur, _) = self.get_alive(cmd, existed, recs)?;
+            let expected = cmd.expected.as_ref().map(|x| &x.value);
+            if cur.as_ref() != expected {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// get_existed returns the latest value of a string, from `existed` or storage.
+    pub(crate) fn get_existed(
+        &self,
+        key: &[u8],
+        existed: &mut DataRecords,
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        self.get_data_rec(DBColumnFamily::Default, key, existed)
+    }
+
+    /// get_data_rec returns the latest value of a record in column family `cf`, from `existed`
+    /// or storage.
+    pub(crate) fn get_data_rec(
+        &self,
+        cf: DBColumnFamily,
+        key: &[u8],
+        existed: &mut DataRecords,
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        let k = (cf, key.to_vec());
+        if !existed.contains_key(&k) {
+            let v = self.storage.get(cf, &k.1)?;
+            existed.insert(k.clone(), v);
+        }
+        Ok(existed[&k].clone())
+    }
+
+    /// eval_command evaluates a command against the latest value of its key, which is in
+    /// `existed` if an earlier command in the same batch has read or written it, or in storage.
+    /// A key expired as of `cmd.now_ms` is treated as absent.
+    /// It returns the entries to write and the result to reply.
+    ///
+    /// The result depends only on the value and the command, thus every replica evaluates a
+    /// command to the same result.
+    pub(crate) fn eval_command(
+        &self,
+        cmd: &Command,
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<(Vec<WriteEntry>, ExecuteResult), StorageError> {
+        let op = OpCode::from_i32(cmd.op);
+
+        if op == Some(OpCode::NoOp) || op.is_none() {
+            return Ok((vec![], ExecuteResult::Success));
+        }
+
+        let op = op.unwrap();
+
+        if op == OpCode::ReadRange {
+            return Ok((vec![], self.eval_read_range(cmd, existed, recs)?));
+        }
+
+        if op == OpCode::DeleteRange {
+            return self.eval_delete_range(cmd, existed, recs);
+        }
+
+        let (cur, expired) = self.get_alive(cmd, existed, recs)?;
+        let (meta, meta_expired) = self.get_alive_meta(cmd, existed, recs)?;
+        let expired = expired || meta_expired;
+        let exists = cur.is_some() || meta.is_some();
+
+        // a command reading or modifying a string fails on a collection.
+        match op {
+            OpCode::Get | OpCode::Incr | OpCode::Append | OpCode::CompareAndSet
+                if meta.is_some() =>
+            {
+                return Ok((vec![], ExecuteResult::Error(WRONGTYPE.to_owned())));
+            }
+            _ => {}
+        }
+
+        let val = |v: Option<Vec<u8>>| ExecuteResult::SuccessWithVal { value: v };
+        let flag = |b: bool| val(Some(if b { b"1".to_vec() } else { b"0".to_vec() }));
+
+        // the expire-at to set if the key is set by Set, SetIfAbsent or CompareAndSet.
+        let expire_at = match cmd.expire_at_ms {
+            -1 => None,
+            0 => Some(None),
+            v => Some(Some(v)),
+        };
+
+        // `write` is `Some(v)` if the key is to be set to `v`, or `Some(None)` to be deleted.
+        // `expire` is `Some(v)` if the expire-at is to be set to `v`, or `Some(None)` to be
+        // removed.
+        let (write, r, expire) = match op {
+            OpCode::Get => (None, val(cur), None),
+            OpCode::Set => (
+                Some(Some(cmd.value.clone())),
+                ExecuteResult::Success,
+                expire_at,
+            ),
+            OpCode::Delete => (Some(None), ExecuteResult::Success, Some(None)),
+            OpCode::Incr => match incr(cur.as_ref(), &cmd.value) {
+                Ok(v) => (Some(Some(v.clone())), val(Some(v)), None),
+                Err(msg) => (None, ExecuteResult::Error(msg.to_string()), None),
+            },
+            OpCode::SetIfAbsent => {
+                if !exists {
+                    (Some(Some(cmd.value.clone())), val(cur), expire_at)
+                } else {
+                    // a collection is returned as an empty value, which tells it exists.
+                    (None, val(Some(cur.unwrap_or_default())), None)
+                }
+            }
+            OpCode::CompareAndSet => {
+                let expected = cmd.expected.as_ref().map(|x| &x.value);
+                if cur.as_ref() == expected {
+                    (Some(Some(cmd.value.clone())), val(cur), expire_at)
+                } else {
+                    (None, val(cur), None)
+                }
+            }
+            OpCode::Append => {
+                let mut v = cur.unwrap_or(vec![]);
+                v.extend_from_slice(&cmd.value);
+                (Some(Some(v.clone())), val(Some(v)), None)
+            }
+            // already checked by `check_watched`.
+            OpCode::Watch => (None, ExecuteResult::Success, None),
+
+            // evaluated by `prepare_txn` or `resolve_txn`, not allowed mixed with others.
+            OpCode::TxnPrepare | OpCode::TxnCommit | OpCode::TxnAbort | OpCode::TxnForget => (
+                None,
+                ExecuteResult::Error("ERR unexpected transaction command".to_owned()),
+                None,
+            ),
+
+            OpCode::Expire => {
+                if !exists {
+                    (None, flag(false), None)
+                } else if cmd.expire_at_ms <= cmd.now_ms {
+                    (Some(None), flag(true), Some(None))
+                } else {
+                    (None, flag(true), Some(Some(cmd.expire_at_ms)))
+                }
+            }
+            OpCode::Persist => {
+                let has = exists && self.get_expire_at(&cmd.key, recs)?.is_some();
+                (None, flag(has), if has { Some(None) } else { None })
+            }
+            OpCode::Ttl => {
+                let ttl = match (exists, self.get_expire_at(&cmd.key, recs)?) {
+                    (false, _) => -2,
+                    (true, None) => -1,
+                    (true, Some(at)) => at - cmd.now_ms,
+                };
+                (None, val(Some(ttl.to_string().into_bytes())), None)
+            }
+            OpCode::DeleteExpired => {
+                if expired {
+                    (Some(None), flag(true), Some(None))
+                } else {
+                    (None, flag(false), None)
+                }
+            }
+            OpCode::NoOp => (None, ExecuteResult::Success, None),
+
+            // Type and commands on collections.
+            _ => return self.eval_collection(op, cmd, cur, meta, expired, existed, recs),
+        };
+
+        // an expired key is replaced by a new one, which does not inherit the expire-at.
+        let expire = match expire {
+            None if expired && write.is_some() => Some(None),
+            v => v,
+        };
+
+        let mut entries = vec![];
+        if let Some(v) = write {
+            // a string replaces a collection, and removing a key removes a collection too.
+            entries.extend(self.delete_collection(&cmd.key, existed)?);
+            entries.push(set_existed(existed, cmd.key.clone(), v));
+        }
+
+        if let Some(at) = expire {
+            entries.push(set_expire_at(recs, &cmd.key, at));
+        }
+
+        Ok((entries, r))
+    }
+
+    /// get_status_rec returns a record in the status column family, from `recs` or storage.
+    pub(crate) fn get_status_rec(
+        &self,
+        k: Vec<u8>,
+        recs: &mut StatusRecords,
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        if !recs.contains_key(&k) {
+            let v = self.storage.get(DBColumnFamily::Status, &k)?;
+            recs.insert(k.clone(), v);
+        }
+        Ok(recs[&k].clone())
+    }
+
     /// Find out the set of smallest instances of every leader: S.
     /// If there are any a → b relations(a.final_deps ⊃ b.final_deps) in S,
     /// replace replace S with: S = {x | x ∈ S and (∃y: y → x)},
@@ -254,3 +601,37 @@ impl Replica {
         return self.execute_instances(instances);
     }
 }
+
+/// set_existed updates the latest value of a string in `existed` and returns the entry to write
+/// it.
+pub(crate) fn set_existed(existed: &mut DataRecords, k: Vec<u8>, v: Option<Vec<u8>>) -> WriteEntry {
+    set_data_rec(existed, DBColumnFamily::Default, k, v)
+}
+
+/// set_data_rec updates the latest value of a record in column family `cf` in `existed` and
+/// returns the entry to write it.
+pub(crate) fn set_data_rec(
+    existed: &mut DataRecords,
+    cf: DBColumnFamily,
+    k: Vec<u8>,
+    v: Option<Vec<u8>>,
+) -> WriteEntry {
+    existed.insert((cf, k.clone()), v.clone());
+    match v {
+        Some(v) => WriteEntry::Set(cf, k, v),
+        None => WriteEntry::Delete(cf, k),
+    }
+}
+
+/// set_status_rec updates a cached record and returns the entry to write it.
+pub(crate) fn set_status_rec(
+    recs: &mut StatusRecords,
+    k: Vec<u8>,
+    v: Option<Vec<u8>>,
+) -> WriteEntry {
+    recs.insert(k.clone(), v.clone());
+    match v {
+        Some(v) => WriteEntry::Set(DBColumnFamily::Status, k, v),
+        None => WriteEntry::Delete(DBColumnFamily::Status, k),
+    }
+}
@@ -0,0 +1,88 @@
+use std::str::from_utf8;
+
+use storage::DBColumnFamily;
+use storage::StorageError;
+use storage::WriteEntry;
+
+use crate::qpaxos::Command;
+use crate::replica::set_status_rec;
+use crate::replica::DataRecords;
+use crate::replica::Replica;
+use crate::replica::StatusRecords;
+use crate::Storage;
+
+/// the expire-at of a key is stored at `EXPIRE_PREFIX + key`, in the status column family, in
+/// milliseconds since epoch, in decimal.
+pub const EXPIRE_PREFIX: &[u8] = b"/expire/";
+
+pub fn expire_key(key: &[u8]) -> Vec<u8> {
+    [EXPIRE_PREFIX, key].concat()
+}
+
+impl Replica {
+    /// get_expire_at returns when a key expires, or `None` if it never does.
+    pub(crate) fn get_expire_at(
+        &self,
+        key: &[u8],
+        recs: &mut StatusRecords,
+    ) -> Result<Option<i64>, StorageError> {
+        let v = self.get_status_rec(expire_key(key), recs)?;
+        Ok(v.and_then(|x| parse_ms(&x)))
+    }
+
+    /// get_alive returns the value of the key of `cmd`, or `None` if it is absent or expired as
+    /// of `cmd.now_ms`. The second returned value is true if it is expired.
+    pub(crate) fn get_alive(
+        &self,
+        cmd: &Command,
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<(Option<Vec<u8>>, bool), StorageError> {
+        let cur = self.get_existed(&cmd.key, existed)?;
+        if cur.is_none() {
+            return Ok((None, false));
+        }
+
+        match self.get_expire_at(&cmd.key, recs)? {
+            Some(at) if at <= cmd.now_ms => Ok((None, true)),
+            _ => Ok((cur, false)),
+        }
+    }
+}
+
+/// set_expire_at updates the expire-at of a key and returns the entry to write it.
+/// `None` removes it.
+pub(crate) fn set_expire_at(recs: &mut StatusRecords, key: &[u8], at: Option<i64>) -> WriteEntry {
+    let v = at.map(|x| x.to_string().into_bytes());
+    set_status_rec(recs, expire_key(key), v)
+}
+
+/// list_expired returns at most `limit` keys that are expired as of `now_ms`, along with when
+/// they expire. Only keys for which `filter` returns true are returned and counted in `limit`.
+pub fn list_expired<F>(sto: &Storage, now_ms: i64, limit: usize, filter: F) -> Vec<(Vec<u8>, i64)>
+where
+    F: Fn(&[u8]) -> bool,
+{
+    let mut rst = vec![];
+    let mut k = EXPIRE_PREFIX.to_vec();
+
+    while let Some((ek, v)) = sto.next(DBColumnFamily::Status, &k, false) {
+        if !ek.starts_with(EXPIRE_PREFIX) || rst.len() >= limit {
+            break;
+        }
+
+        let key = &ek[EXPIRE_PREFIX.len()..];
+        if let Some(at) = parse_ms(&v) {
+            if at <= now_ms && filter(key) {
+                rst.push((key.to_vec(), at));
+            }
+        }
+        k = ek;
+    }
+
+    rst
+}
+
+pub(crate) fn parse_ms(v: &[u8]) -> Option<i64> {
+    from_utf8(v).ok().and_then(|x| x.parse().ok())
+}
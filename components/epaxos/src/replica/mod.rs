@@ -10,6 +10,21 @@ pub use errors::*;
 mod status;
 pub use status::*;
 
+mod waiters;
+pub use waiters::*;
+
+mod txn;
+pub use txn::*;
+
+mod expire;
+pub use expire::*;
+
+mod collection;
+pub use collection::*;
+
+mod range;
+pub use range::*;
+
 #[cfg(test)]
 mod test_status;
 
@@ -18,3 +33,15 @@ mod test_replica;
 
 #[cfg(test)]
 mod test_exec;
+
+#[cfg(test)]
+mod test_txn;
+
+#[cfg(test)]
+mod test_expire;
+
+#[cfg(test)]
+mod test_collection;
+
+#[cfg(test)]
+mod test_range;
@@ -0,0 +1,394 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::iter::Peekable;
+
+use storage::DBColumnFamily;
+use storage::StorageError;
+use storage::WriteEntry;
+
+use crate::conf::key_hash_slot;
+use crate::iters::Iter;
+use crate::qpaxos::Command;
+use crate::qpaxos::REVERSE;
+use crate::replica::expire_key;
+use crate::replica::members_prefix;
+use crate::replica::meta_key;
+use crate::replica::DataRecords;
+use crate::replica::ExecuteResult;
+use crate::replica::Replica;
+use crate::replica::StatusRecords;
+use crate::replica::EXPIRE_PREFIX;
+use crate::replica::META_PREFIX;
+use crate::Storage;
+
+type Records = Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)>>;
+
+/// max number of records of a column family a DeleteRange reads in slot mode, in which it
+/// removes keys one by one. A DeleteRange with more stops before the record after them.
+pub const MAX_SLOT_DELETE_KEYS: usize = 1024;
+
+impl Replica {
+    /// eval_read_range evaluates a ReadRange. It returns string keys and values in
+    /// `[cmd.key, cmd.field)` as `[k1, v1, k2, v2, ...]`, at most `cmd.stop` pairs if it is
+    /// positive.
+    /// Changes by commands executed earlier in the same batch, which are in `existed` and not yet
+    /// written, are seen. Keys expired as of `cmd.now_ms` are skipped.
+    pub(crate) fn eval_read_range(
+        &self,
+        cmd: &Command,
+        existed: &DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<ExecuteResult, StorageError> {
+        let end = if cmd.field.is_empty() {
+            None
+        } else {
+            Some(&cmd.field[..])
+        };
+        let limit = if cmd.stop > 0 {
+            cmd.stop as usize
+        } else {
+            usize::max_value()
+        };
+        let reverse = cmd.value == REVERSE;
+
+        // without an end there is no key to iterate backward from: iterate forward and keep the
+        // last `limit` pairs.
+        let backward = reverse && end.is_some();
+
+        let mut pairs = VecDeque::new();
+        for (k, v) in range_records(&self.storage, &cmd.key, end, backward, existed) {
+            if let Some(at) = self.get_expire_at(&k, recs)? {
+                if at <= cmd.now_ms {
+                    continue;
+                }
+            }
+
+            pairs.push_back((k, v));
+            if pairs.len() > limit {
+                pairs.pop_front();
+            }
+            if pairs.len() == limit && reverse == backward {
+                break;
+            }
+        }
+
+        let mut pairs: Vec<_> = pairs.into_iter().collect();
+        if reverse && !backward {
+            pairs.reverse();
+        }
+
+        let mut values = Vec::with_capacity(pairs.len() * 2);
+        for (k, v) in pairs {
+            values.push(k);
+            values.push(v);
+        }
+
+        Ok(ExecuteResult::SuccessWithVals { values })
+    }
+
+    /// eval_delete_range evaluates a DeleteRange. It removes strings, collections and expire-at
+    /// of keys in `[cmd.key, cmd.field)` with range deletes, thus it does not read every key,
+    /// but the metas of collections. It returns `Success`.
+    ///
+    /// In slot mode keys of other groups on this node are interleaved with those of this group,
+    /// thus every key in range is read and those in the slots of this group are removed one by
+    /// one, which costs O(keys in range) of all groups on this node. To bound the batch written
+    /// under the exec lock, at most `MAX_SLOT_DELETE_KEYS` records of every column family are
+    /// read. If there are more, it stops at the first record not read, and returns it as a
+    /// `SuccessWithVal`, from which the caller continues with another DeleteRange.
+    ///
+    /// Changes in range in `existed` and `recs` are marked removed, but any other key is still
+    /// read from storage, where it is not yet removed. Thus an instance with a DeleteRange is the
+    /// last one executed in a batch, and a command after it in the same instance does not see it.
+    pub(crate) fn eval_delete_range(
+        &self,
+        cmd: &Command,
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<(Vec<WriteEntry>, ExecuteResult), StorageError> {
+        let start = &cmd.key[..];
+        let end = if cmd.field.is_empty() {
+            None
+        } else {
+            Some(&cmd.field[..])
+        };
+
+        let cut = if self.slots.is_some() {
+            self.slot_delete_cut(start, end)
+        } else {
+            None
+        };
+        let end = cut.as_deref().or(end);
+
+        // a key in range that is served by this group.
+        let removed_key =
+            |k: &[u8]| k >= start && end.map(|e| k < e).unwrap_or(true) && self.owns_key(k);
+
+        let bound = |prefix: &[u8]| match end {
+            Some(e) => Some([prefix, e].concat()),
+            None => prefix_end(prefix),
+        };
+
+        // the members of every collection, then the metas.
+        let mut collections: BTreeSet<_> = self
+            .range_keys(
+                DBColumnFamily::Collection,
+                META_PREFIX,
+                start,
+                end,
+                usize::MAX,
+            )
+            .into_iter()
+            .filter(|k| self.owns_key(k))
+            .collect();
+        for ((cf, mk), v) in existed.iter() {
+            if *cf == DBColumnFamily::Collection
+                && v.is_some()
+                && mk.starts_with(META_PREFIX)
+                && removed_key(&mk[META_PREFIX.len()..])
+            {
+                collections.insert(mk[META_PREFIX.len()..].to_vec());
+            }
+        }
+
+        let mut entries = vec![];
+        for key in collections.iter() {
+            let p = members_prefix(key);
+            let e = prefix_end(&p);
+            entries.push(WriteEntry::DeleteRange(DBColumnFamily::Collection, p, e));
+        }
+
+        if self.slots.is_none() {
+            entries.push(WriteEntry::DeleteRange(
+                DBColumnFamily::Default,
+                start.to_vec(),
+                end.map(|x| x.to_vec()),
+            ));
+            entries.push(WriteEntry::DeleteRange(
+                DBColumnFamily::Collection,
+                meta_key(start),
+                bound(META_PREFIX),
+            ));
+            entries.push(WriteEntry::DeleteRange(
+                DBColumnFamily::Status,
+                expire_key(start),
+                bound(EXPIRE_PREFIX),
+            ));
+        } else {
+            for k in self.range_keys(DBColumnFamily::Default, b"", start, end, usize::MAX) {
+                if self.owns_key(&k) {
+                    entries.push(WriteEntry::Delete(DBColumnFamily::Default, k));
+                }
+            }
+            for key in collections.iter() {
+                entries.push(WriteEntry::Delete(
+                    DBColumnFamily::Collection,
+                    meta_key(key),
+                ));
+            }
+            let expires = self.range_keys(
+                DBColumnFamily::Status,
+                EXPIRE_PREFIX,
+                start,
+                end,
+                usize::MAX,
+            );
+            for k in expires {
+                if self.owns_key(&k) {
+                    entries.push(WriteEntry::Delete(DBColumnFamily::Status, expire_key(&k)));
+                }
+            }
+        }
+
+        for ((cf, k), v) in existed.iter_mut() {
+            let removed = if *cf == DBColumnFamily::Collection {
+                (k.starts_with(META_PREFIX) && removed_key(&k[META_PREFIX.len()..]))
+                    || collections
+                        .iter()
+                        .any(|c| k.starts_with(&members_prefix(c)))
+            } else {
+                removed_key(k)
+            };
+            if removed {
+                *v = None;
+            }
+        }
+
+        for (k, v) in recs.iter_mut() {
+            if k.starts_with(EXPIRE_PREFIX) && removed_key(&k[EXPIRE_PREFIX.len()..]) {
+                *v = None;
+            }
+        }
+
+        let rst = match cut {
+            Some(_) => ExecuteResult::SuccessWithVal { value: cut },
+            None => ExecuteResult::Success,
+        };
+
+        Ok((entries, rst))
+    }
+
+    /// slot_delete_cut returns the key a DeleteRange of `[start, end)` in slot mode stops at, if
+    /// some column family has more than `MAX_SLOT_DELETE_KEYS` records in range.
+    fn slot_delete_cut(&self, start: &[u8], end: Option<&[u8]>) -> Option<Vec<u8>> {
+        let cfs = [
+            (DBColumnFamily::Default, &b""[..]),
+            (DBColumnFamily::Collection, META_PREFIX),
+            (DBColumnFamily::Status, EXPIRE_PREFIX),
+        ];
+
+        let mut cut: Option<Vec<u8>> = None;
+        for (cf, prefix) in cfs.iter() {
+            let mut keys = self.range_keys(*cf, prefix, start, end, MAX_SLOT_DELETE_KEYS + 1);
+            if keys.len() > MAX_SLOT_DELETE_KEYS {
+                let k = keys.pop().unwrap();
+                if cut.as_ref().map(|c| &k < c).unwrap_or(true) {
+                    cut = Some(k);
+                }
+            }
+        }
+        cut
+    }
+
+    /// owns_key returns true if `key` is served by the group of this replica. Only in slot mode
+    /// some keys in storage are not, which are served by other groups on this node.
+    pub(crate) fn owns_key(&self, key: &[u8]) -> bool {
+        match self.slots {
+            Some((first, last)) => {
+                let slot = key_hash_slot(key);
+                first <= slot && slot <= last
+            }
+            None => true,
+        }
+    }
+
+    /// range_keys returns keys in storage of `cf` that are `prefix` followed by a key in
+    /// `[start, end)`, in key order and without the prefix, at most `limit` of them.
+    fn range_keys(
+        &self,
+        cf: DBColumnFamily,
+        prefix: &[u8],
+        start: &[u8],
+        end: Option<&[u8]>,
+        limit: usize,
+    ) -> Vec<Vec<u8>> {
+        let mut keys = vec![];
+        let mut k = [prefix, start].concat();
+        let mut include = true;
+        while keys.len() < limit {
+            let sk = match self.storage.next(cf, &k, include) {
+                Some((sk, _)) => sk,
+                None => break,
+            };
+            if !sk.starts_with(prefix) {
+                break;
+            }
+            let key = &sk[prefix.len()..];
+            if end.map(|e| key >= e).unwrap_or(false) {
+                break;
+            }
+            keys.push(key.to_vec());
+            k = sk;
+            include = false;
+        }
+        keys
+    }
+}
+
+/// range_records returns the string records in `[start, end)`, in key order, or in reverse
+/// order if `backward`, which requires a bounded `end`.
+/// Records in storage are merged with the changes in `existed`.
+fn range_records(
+    sto: &Storage,
+    start: &[u8],
+    end: Option<&[u8]>,
+    backward: bool,
+    existed: &DataRecords,
+) -> RangeIter {
+    let in_range = |k: &[u8]| k >= start && end.map(|e| k < e).unwrap_or(true);
+
+    let mut changes: Vec<_> = existed
+        .iter()
+        .filter(|((cf, k), _)| *cf == DBColumnFamily::Default && in_range(k))
+        .map(|((_, k), v)| (k.clone(), v.clone()))
+        .collect();
+    changes.sort();
+    if backward {
+        changes.reverse();
+    }
+
+    RangeIter {
+        records: bounded_records(sto, start, end, backward).peekable(),
+        changes: changes.into_iter().peekable(),
+        backward,
+    }
+}
+
+/// prefix_end returns the smallest key greater than every key starting with `prefix`, or `None`
+/// if there is no such key.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut k = prefix.to_vec();
+    while let Some(b) = k.pop() {
+        if b < 0xff {
+            k.push(b + 1);
+            return Some(k);
+        }
+    }
+    None
+}
+
+fn bounded_records(sto: &Storage, start: &[u8], end: Option<&[u8]>, backward: bool) -> Records {
+    let start = start.to_vec();
+    let end = end.map(|x| x.to_vec());
+
+    if backward {
+        let e = end.expect("iterating backward requires an end");
+        let it = sto.get_iter(e, false, true, DBColumnFamily::Default);
+        Box::new(it.take_while(move |(k, _)| k >= &start))
+    } else {
+        let it = sto.get_iter(start, true, false, DBColumnFamily::Default);
+        Box::new(it.take_while(move |(k, _)| end.as_ref().map(|e| k < e).unwrap_or(true)))
+    }
+}
+
+/// RangeIter merges records in storage with changes not yet written, which override records
+/// of the same keys. A change of `None` removes a key.
+struct RangeIter {
+    records: Peekable<Records>,
+    changes: Peekable<std::vec::IntoIter<(Vec<u8>, Option<Vec<u8>>)>>,
+    backward: bool,
+}
+
+impl Iterator for RangeIter {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ord = match (self.records.peek(), self.changes.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((a, _)), Some((b, _))) => {
+                    if self.backward {
+                        b.cmp(a)
+                    } else {
+                        a.cmp(b)
+                    }
+                }
+            };
+
+            match ord {
+                Ordering::Less => return self.records.next(),
+                Ordering::Equal => {
+                    self.records.next();
+                }
+                Ordering::Greater => {}
+            }
+
+            if let (k, Some(v)) = self.changes.next().unwrap() {
+                return Some((k, v));
+            }
+        }
+    }
+}
@@ -1,4 +1,8 @@
+use std::collections::BTreeSet;
 use std::i64;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
 
 use crate::conf::ClusterInfo;
 use crate::qpaxos::replicate_reply;
@@ -9,6 +13,7 @@ use crate::qpaxos::Command;
 use crate::qpaxos::CommitReply;
 use crate::qpaxos::CommitRequest;
 use crate::qpaxos::Conflict;
+use crate::qpaxos::ConflictStatus;
 use crate::qpaxos::FastAcceptReply;
 use crate::qpaxos::FastAcceptRequest;
 use crate::qpaxos::Instance;
@@ -17,10 +22,18 @@ use crate::qpaxos::InstanceIdVec;
 use crate::qpaxos::PrepareReply;
 use crate::qpaxos::PrepareRequest;
 use crate::qpaxos::ProtocolError;
+use crate::qpaxos::ReadIndexReply;
+use crate::qpaxos::ReadIndexRequest;
 use crate::qpaxos::ReplicaId;
 use crate::qpaxos::ReplicateReply;
 use crate::qpaxos::ReplicateRequest;
+use crate::qpaxos::TryPreAcceptReply;
+use crate::qpaxos::TryPreAcceptRequest;
+use crate::read::ReadIndexCache;
+use crate::replica::ExecWaiters;
+use crate::replica::InstanceStatus;
 use crate::replica::ReplicaError;
+use crate::replication::PeerClients;
 use crate::replication::RpcHandlerError;
 use crate::Iter;
 use crate::Storage;
@@ -47,7 +60,7 @@ macro_rules! ref_or_bug {
 }
 
 /// information of communication peer
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ReplicaPeer {
     pub replica_id: ReplicaId,
     pub addr: String, // ip: port pairs of each replica
@@ -80,14 +93,32 @@ impl From<(ReplicaId, String, bool)> for ReplicaPeer {
 pub struct Replica {
     pub replica_id: ReplicaId,
     pub group_replica_ids: Vec<ReplicaId>,
+    /// the hash slots the group serves in slot mode, where keys of every group hosted by a node
+    /// are interleaved in the storage they share.
+    pub slots: Option<(u16, u16)>,
     pub peers: Vec<ReplicaPeer>,
+    pub peer_clients: Arc<PeerClients>,
     pub storage: Storage,
     pub committed_timeout: i32,
+    pub waiters: ExecWaiters,
+    /// instances found by the executor that need to be recovered.
+    pub to_recover: Mutex<BTreeSet<InstanceId>>,
+    /// the executor holds it to write a batch, and a read served locally holds it to read, thus
+    /// it never sees a batch partially written.
+    pub exec_lock: RwLock<()>,
+    /// the read index reused by reads in `bounded_stale` mode.
+    pub read_index_cache: ReadIndexCache,
 }
 
 impl Replica {
-    /// create a new Replica
-    pub fn new(rid: ReplicaId, cinfo: &ClusterInfo, sto: Storage) -> Result<Replica, ReplicaError> {
+    /// create a new Replica, which talks to peers with `peer_clients`, usually shared by every
+    /// replica on a node.
+    pub fn new(
+        rid: ReplicaId,
+        cinfo: &ClusterInfo,
+        sto: Storage,
+        peer_clients: Arc<PeerClients>,
+    ) -> Result<Replica, ReplicaError> {
         let group = cinfo
             .get_group(rid)
             .ok_or(ReplicaError::ReplicaNotFound(rid))?;
@@ -102,7 +133,7 @@ impl Replica {
                 continue;
             }
 
-            // TODO check the replica is alive or not
+            // liveness is tracked by PeerClients
             let addr = format!("http://{}", node.replication.to_string());
             peers.push((*prid, addr, true).into());
         }
@@ -110,10 +141,16 @@ impl Replica {
         Ok(Replica {
             replica_id: rid,
             group_replica_ids: group.replicas.keys().cloned().collect(),
+            slots: group.slots,
             peers,
+            peer_clients,
             storage: sto,
             // TODO get from conf
             committed_timeout: 10000,
+            waiters: ExecWaiters::new(),
+            to_recover: Mutex::new(BTreeSet::new()),
+            exec_lock: RwLock::new(()),
+            read_index_cache: ReadIndexCache::default(),
         })
     }
 
@@ -139,10 +176,35 @@ impl Replica {
         inst.instance_id = Some(iid);
 
         self.storage.set_instance(&inst)?;
+        // the executor only looks for instances up to the "max" ref.
+        self.storage.set_ref("max", rid, iid)?;
 
         Ok(inst)
     }
 
+    /// load_refs reloads the "max" and "exec" instance refs of every replica in the group from
+    /// storage, when a server restarts.
+    /// An instance may be stored without updating the "max" ref if a server crashed in between,
+    /// thus the "max" ref is fixed by scanning instances.
+    pub fn load_refs(&self) -> Result<(InstanceIdVec, InstanceIdVec), StorageError> {
+        let maxs = self.get_max_instance_ids(&self.group_replica_ids);
+        let mut execs = InstanceIdVec::from([0; 0]);
+
+        for max in maxs.iter() {
+            let rid = max.replica_id;
+
+            let stored = self.storage.get_ref("max", rid)?;
+            if max.idx >= 0 && stored < Some(*max) {
+                self.storage.set_ref("max", rid, *max)?;
+            }
+
+            let exec = self.storage.get_ref("exec", rid)?;
+            execs.push(exec.unwrap_or((rid, -1).into()));
+        }
+
+        Ok((maxs, execs))
+    }
+
     /// get_max_instance_ids returns the max instance-id for every specified replica.
     /// If there is no instance at all by a replica, a `(rid, -1)` is filled.
     pub fn get_max_instance_ids(&self, rids: &[ReplicaId]) -> InstanceIdVec {
@@ -165,6 +227,31 @@ impl Replica {
         iids.into()
     }
 
+    /// get_max_refs returns the "max" ref of every replica in the group, i.e., the max instance
+    /// seen by this replica. A `(rid, -1)` is filled if it has seen none of a replica.
+    pub fn get_max_refs(&self) -> Result<InstanceIdVec, StorageError> {
+        let mut maxs = InstanceIdVec::from([0; 0]);
+        for rid in self.group_replica_ids.iter() {
+            let max = self.storage.get_ref("max", *rid)?;
+            maxs.push(max.unwrap_or((*rid, -1).into()));
+        }
+        Ok(maxs)
+    }
+
+    /// handle_read_index replies the instances this replica has seen, to a replica serving a
+    /// read locally.
+    /// The "max" ref is updated before replying to any replication request, thus an instance
+    /// committed is seen by every quorum.
+    pub fn handle_read_index(
+        &self,
+        _req: &ReadIndexRequest,
+    ) -> Result<ReadIndexReply, RpcHandlerError> {
+        Ok(ReadIndexReply {
+            err: None,
+            maxs: Some(self.get_max_refs()?),
+        })
+    }
+
     pub fn handle_replicate(
         &self,
         req: ReplicateRequest,
@@ -177,7 +264,8 @@ impl Replica {
             .ok_or(ProtocolError::LackOf("instance_id".into()))?;
 
         let mut inst = self.get_instance(iid)?;
-        let last_ballot = inst.ballot;
+        // an instance this replica has not yet seen has the smallest ballot.
+        let last_ballot = inst.ballot.or(Some((0, 0, 0).into()));
 
         println!("replica handle replicate for inst:{}", inst);
 
@@ -187,7 +275,7 @@ impl Replica {
             .ok_or(ProtocolError::LackOf("phase".into()))?;
 
         match phase {
-            Phase::Fast(_) | Phase::Accept(_) | Phase::Prepare(_) => {
+            Phase::Fast(_) | Phase::Accept(_) | Phase::Prepare(_) | Phase::TryPreAccept(_) => {
                 if req.ballot < inst.ballot {
                     return Ok(ReplicateReply {
                         err: None,
@@ -206,10 +294,29 @@ impl Replica {
             Phase::Accept(r) => self.handle_accept(r, &mut inst)?.into(),
             Phase::Commit(r) => self.handle_commit(r, &mut inst)?.into(),
             Phase::Prepare(r) => self.handle_prepare(r, &mut inst)?.into(),
+            Phase::TryPreAccept(r) => self.handle_try_pre_accept(r, &mut inst)?.into(),
         };
 
+        // a rejected TryPreAccept changes nothing.
+        if let replicate_reply::Phase::TryPreAccept(ref r) = reply_phase {
+            if !r.ok {
+                return Ok(ReplicateReply {
+                    err: None,
+                    last_ballot,
+                    instance_id: Some(iid),
+                    phase: Some(reply_phase),
+                });
+            }
+        }
+
         self.storage.set_instance(&inst)?;
 
+        // let the executor see instances initiated by other replicas.
+        let max = self.storage.get_ref("max", iid.replica_id)?;
+        if max < Some(iid) {
+            self.storage.set_ref("max", iid.replica_id, iid)?;
+        }
+
         Ok(ReplicateReply {
             err: None,
             last_ballot,
@@ -220,13 +327,90 @@ impl Replica {
 
     pub fn handle_prepare(
         &self,
-        req: &PrepareRequest,
+        _req: &PrepareRequest,
         inst: &mut Instance,
     ) -> Result<PrepareReply, RpcHandlerError> {
-        let _ = req;
-        let _ = inst;
+        // ballot is already updated by handle_replicate.
         Ok(PrepareReply {
+            cmds: inst.cmds.clone(),
+            deps: inst.deps.clone(),
+            final_deps: inst.final_deps.clone(),
+            accepted_ballot: inst.accepted_ballot,
+            committed: inst.committed,
+        })
+    }
+
+    /// handle_try_pre_accept accepts the deps proposed by a recovering replica, if there is no
+    /// local instance conflicting with it: an instance that interferes with it, is not in the
+    /// proposed deps, and does not depend on it either.
+    pub fn handle_try_pre_accept(
+        &self,
+        req: &TryPreAcceptRequest,
+        inst: &mut Instance,
+    ) -> Result<TryPreAcceptReply, RpcHandlerError> {
+        let iid = *ref_or_bug!(inst.instance_id);
+        let req_deps = req
+            .deps
+            .as_ref()
+            .ok_or(ProtocolError::LackOf("phase::TryPreAccept.deps".into()))?;
+
+        // deps can not be changed once an instance is accepted.
+        let status = inst.status();
+        if status >= InstanceStatus::Accepted {
+            return Ok(TryPreAcceptReply {
+                ok: false,
+                conflict_instance: Some(iid),
+                conflict_replica: iid.replica_id,
+                conflict_status: ConflictStatus::from(status) as i32,
+            });
+        }
+
+        // the local instance is changed only if the deps are accepted.
+        let proposed = Instance {
+            cmds: req.cmds.clone(),
             ..Default::default()
+        };
+
+        for rid in self.group_replica_ids.iter() {
+            let start_iid = (*rid, i64::MAX).into();
+
+            for local_inst in self.storage.get_instance_iter(start_iid, true, true) {
+                let local_iid = *ref_or_bug!(local_inst.instance_id);
+
+                if req_deps >= &local_iid {
+                    // lower instances are all in deps.
+                    break;
+                }
+
+                if local_iid == iid || !proposed.conflict(&local_inst) {
+                    continue;
+                }
+
+                let local_deps = local_inst.deps.as_ref();
+                if local_deps.map(|x| x >= &iid) == Some(true) {
+                    continue;
+                }
+
+                return Ok(TryPreAcceptReply {
+                    ok: false,
+                    conflict_instance: Some(local_iid),
+                    conflict_replica: local_iid.replica_id,
+                    conflict_status: ConflictStatus::from(local_inst.status()) as i32,
+                });
+            }
+        }
+
+        inst.cmds = proposed.cmds;
+        if inst.initial_deps.is_none() {
+            inst.initial_deps = req.deps.clone();
+        }
+        inst.deps = req.deps.clone();
+
+        Ok(TryPreAcceptReply {
+            ok: true,
+            conflict_instance: None,
+            conflict_replica: 0,
+            conflict_status: ConflictStatus::NoConflict as i32,
         })
     }
 
@@ -299,6 +483,8 @@ impl Replica {
         // TODO locking
         // TODO check instance status if committed or executed
         inst.final_deps = req.final_deps.clone();
+        // ballot is already updated by handle_replicate.
+        inst.accepted_ballot = inst.ballot;
         Ok(AcceptReply {})
     }
 
@@ -311,6 +497,11 @@ impl Replica {
         inst.final_deps = req.final_deps.clone();
         inst.committed = true;
 
+        // a replica may receive a commit without seeing the fast-accept.
+        if inst.deps.is_none() {
+            inst.deps = req.final_deps.clone();
+        }
+
         Ok(CommitReply {})
     }
 
@@ -32,6 +32,18 @@ impl Instance {
     }
 }
 
+impl From<InstanceStatus> for ConflictStatus {
+    fn from(st: InstanceStatus) -> Self {
+        match st {
+            InstanceStatus::Na => ConflictStatus::NoConflict,
+            InstanceStatus::FastAccepted => ConflictStatus::FastAccepted,
+            InstanceStatus::Accepted => ConflictStatus::Accepted,
+            InstanceStatus::Committed => ConflictStatus::Committed,
+            InstanceStatus::Executed => ConflictStatus::Executed,
+        }
+    }
+}
+
 /// Status tracks replication status during fast-accept, accept and commit phase.
 #[derive(Debug, Default)]
 pub struct Status {
@@ -65,6 +77,22 @@ pub struct Status {
     /// accept_oks tracks positive accept-replies.
     /// AcceptReply with error, delayed, or with lower ballot does not count.
     pub accept_oks: HashMap<ReplicaId, bool>,
+
+    /// prepare_replied tracks what replica has sent back PrepareReply during recovery.
+    pub prepare_replied: HashMap<ReplicaId, bool>,
+
+    /// prepare_oks collects positive prepare-replies, along with the ballot a replica had before
+    /// handling the PrepareRequest.
+    pub prepare_oks: HashMap<ReplicaId, (BallotNum, PrepareReply)>,
+
+    /// try_replied tracks what replica has sent back TryPreAcceptReply during recovery.
+    pub try_replied: HashMap<ReplicaId, bool>,
+
+    /// try_oks tracks replicas that accepted the deps in TryPreAcceptRequest.
+    pub try_oks: HashMap<ReplicaId, bool>,
+
+    /// try_conflicts collects try-pre-accept-replies that found a conflicting instance.
+    pub try_conflicts: HashMap<ReplicaId, TryPreAcceptReply>,
 }
 
 impl Status {
@@ -83,6 +111,13 @@ impl Status {
 
             accept_replied: HashMap::new(),
             accept_oks: HashMap::new(),
+
+            prepare_replied: HashMap::new(),
+            prepare_oks: HashMap::new(),
+
+            try_replied: HashMap::new(),
+            try_oks: HashMap::new(),
+            try_conflicts: HashMap::new(),
         };
 
         st.start_fast_accept();
@@ -90,6 +125,18 @@ impl Status {
         st
     }
 
+    /// new_recovery creates a Status for a replica to recover an instance it does not lead.
+    /// The instance may have not been seen by this replica, thus unlike `new`, it does not
+    /// fill in any fast-accept status.
+    pub fn new_recovery(n_replica: i32, instance: Instance) -> Self {
+        Self {
+            quorum: quorum(n_replica),
+            fast_quorum: fast_quorum(n_replica),
+            instance,
+            ..Default::default()
+        }
+    }
+
     /// start_fast_accept performs a handle-fast-accept-reply for the instance it serves.
     pub fn start_fast_accept(&mut self) -> &mut Self {
         let iid = self.instance.instance_id.unwrap();
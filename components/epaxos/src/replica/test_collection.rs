@@ -0,0 +1,442 @@
+use std::sync::Arc;
+
+use crate::qpaxos::{Command, Instance, OpCode};
+use crate::replica::*;
+use crate::testutil;
+use storage::DBColumnFamily;
+use storage::MemEngine;
+
+fn new_replica() -> Replica {
+    testutil::new_replica(
+        1,
+        vec![1, 2, 3],
+        vec![],
+        Arc::new(MemEngine::new().unwrap()),
+    )
+}
+
+/// exec executes an instance of `cmds` and returns the results.
+fn exec(rp: &Replica, idx: i64, cmds: Vec<Command>) -> Vec<ExecuteResult> {
+    let inst = Instance {
+        instance_id: Some((2, idx).into()),
+        cmds,
+        ..Default::default()
+    };
+
+    let mut rx = rp.waiters.register(inst.instance_id.unwrap());
+    rp.execute_commands(vec![inst]).unwrap();
+    rx.try_recv().unwrap()
+}
+
+fn m(op: OpCode, key: &str, field: &str, value: &str) -> Command {
+    Command::member(op, key.as_bytes(), field.as_bytes(), value.as_bytes())
+}
+
+fn val(v: &str) -> ExecuteResult {
+    ExecuteResult::SuccessWithVal {
+        value: Some(v.as_bytes().to_vec()),
+    }
+}
+
+fn nil() -> ExecuteResult {
+    ExecuteResult::SuccessWithVal { value: None }
+}
+
+fn vals(values: &[&str]) -> ExecuteResult {
+    ExecuteResult::SuccessWithVals {
+        values: values.iter().map(|x| x.as_bytes().to_vec()).collect(),
+    }
+}
+
+fn wrongtype() -> ExecuteResult {
+    ExecuteResult::Error(WRONGTYPE.to_owned())
+}
+
+/// count_records returns the number of records of the collection at `key` in storage.
+fn count_records(rp: &Replica, key: &str) -> usize {
+    let key = key.as_bytes();
+    let mut n = rp
+        .storage
+        .get(DBColumnFamily::Collection, &meta_key(key))
+        .unwrap()
+        .iter()
+        .count();
+
+    let prefix = members_prefix(key);
+    let mut k = prefix.clone();
+    while let Some((mk, _)) = rp.storage.next(DBColumnFamily::Collection, &k, true) {
+        if !mk.starts_with(&prefix) {
+            break;
+        }
+        n += 1;
+        k = [&mk[..], b"\x00"].concat();
+    }
+    n
+}
+
+#[test]
+fn test_collection_encode() {
+    let ints = [i64::min_value(), -2, -1, 0, 1, 2, i64::max_value()];
+    for w in ints.windows(2) {
+        assert!(encode_i64(w[0]) < encode_i64(w[1]));
+    }
+
+    let floats = [
+        std::f64::NEG_INFINITY,
+        -1.5,
+        -1.0,
+        -0.0,
+        0.5,
+        1.0,
+        1e20,
+        std::f64::INFINITY,
+    ];
+    for w in floats.windows(2) {
+        assert!(encode_f64(w[0]) < encode_f64(w[1]), "{:?}", w);
+    }
+    for f in floats.iter() {
+        assert_eq!(*f, decode_f64(&encode_f64(*f)));
+    }
+    assert_eq!(encode_f64(0.0), encode_f64(-0.0));
+
+    assert_eq!(Some(1.5), parse_score(b"1.5"));
+    assert_eq!(Some(std::f64::NEG_INFINITY), parse_score(b"-inf"));
+    assert_eq!(None, parse_score(b"nan"));
+    assert_eq!(None, parse_score(b"x"));
+
+    assert_eq!(Some((0, 3)), range_of(0, -1, 3));
+    assert_eq!(Some((1, 2)), range_of(-2, 10, 3));
+    assert_eq!(Some((0, 1)), range_of(-10, 0, 3));
+    assert_eq!(None, range_of(2, 1, 3));
+    assert_eq!(None, range_of(3, 5, 3));
+    assert_eq!(None, range_of(0, -1, 0));
+}
+
+#[test]
+fn test_collection_hash() {
+    let rp = new_replica();
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            m(OpCode::HSet, "h", "b", "2"),
+            m(OpCode::HSet, "h", "a", "1"),
+            m(OpCode::HSet, "h", "a", "3"),
+            m(OpCode::HGet, "h", "a", ""),
+            m(OpCode::HGet, "h", "c", ""),
+            m(OpCode::HExists, "h", "b", ""),
+            m(OpCode::HLen, "h", "", ""),
+            m(OpCode::HGetAll, "h", "", ""),
+            m(OpCode::Type, "h", "", ""),
+        ],
+    );
+    assert_eq!(
+        vec![
+            val("1"),
+            val("1"),
+            val("0"),
+            val("3"),
+            nil(),
+            val("1"),
+            val("2"),
+            vals(&["a", "3", "b", "2"]),
+            val("hash"),
+        ],
+        rsts
+    );
+
+    // read back from storage, in another batch.
+    let rsts = exec(
+        &rp,
+        2,
+        vec![
+            m(OpCode::HDel, "h", "a", ""),
+            m(OpCode::HDel, "h", "a", ""),
+            m(OpCode::HGetAll, "h", "", ""),
+        ],
+    );
+    assert_eq!(vec![val("1"), val("0"), vals(&["b", "2"])], rsts);
+    assert_eq!(2, count_records(&rp, "h"));
+
+    // removing the last field removes the hash.
+    let rsts = exec(
+        &rp,
+        3,
+        vec![m(OpCode::HDel, "h", "b", ""), m(OpCode::Type, "h", "", "")],
+    );
+    assert_eq!(vec![val("1"), val("none")], rsts);
+    assert_eq!(0, count_records(&rp, "h"));
+}
+
+#[test]
+fn test_collection_list() {
+    let rp = new_replica();
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            m(OpCode::RPush, "l", "", "b"),
+            m(OpCode::RPush, "l", "", "c"),
+            m(OpCode::LPush, "l", "", "a"),
+            Command::range(OpCode::LRange, b"l", 0, -1),
+            Command::range(OpCode::LRange, b"l", -2, 100),
+            m(OpCode::LLen, "l", "", ""),
+        ],
+    );
+    assert_eq!(
+        vec![
+            val("1"),
+            val("2"),
+            val("3"),
+            vals(&["a", "b", "c"]),
+            vals(&["b", "c"]),
+            val("3"),
+        ],
+        rsts
+    );
+
+    let rsts = exec(
+        &rp,
+        2,
+        vec![
+            m(OpCode::LPop, "l", "", ""),
+            m(OpCode::RPop, "l", "", ""),
+            Command::range(OpCode::LRange, b"l", 0, -1),
+            m(OpCode::RPop, "l", "", ""),
+            m(OpCode::RPop, "l", "", ""),
+            m(OpCode::LLen, "l", "", ""),
+        ],
+    );
+    assert_eq!(
+        vec![val("a"), val("c"), vals(&["b"]), val("b"), nil(), val("0")],
+        rsts
+    );
+    assert_eq!(0, count_records(&rp, "l"));
+}
+
+#[test]
+fn test_collection_set_zset() {
+    let rp = new_replica();
+
+    let zrange = |start: i64, stop: i64| Command {
+        value: WITHSCORES.to_vec(),
+        ..Command::range(OpCode::ZRange, b"z", start, stop)
+    };
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            m(OpCode::SAdd, "s", "y", ""),
+            m(OpCode::SAdd, "s", "x", ""),
+            m(OpCode::SAdd, "s", "x", ""),
+            m(OpCode::SRem, "s", "y", ""),
+            m(OpCode::SIsMember, "s", "x", ""),
+            m(OpCode::SCard, "s", "", ""),
+            m(OpCode::SMembers, "s", "", ""),
+        ],
+    );
+    assert_eq!(
+        vec![
+            val("1"),
+            val("1"),
+            val("0"),
+            val("1"),
+            val("1"),
+            val("1"),
+            vals(&["x"]),
+        ],
+        rsts
+    );
+
+    let rsts = exec(
+        &rp,
+        2,
+        vec![
+            m(OpCode::ZAdd, "z", "a", "3"),
+            m(OpCode::ZAdd, "z", "b", "1.5"),
+            m(OpCode::ZAdd, "z", "c", "-inf"),
+            m(OpCode::ZAdd, "z", "a", "1"),
+            m(OpCode::ZAdd, "z", "d", "x"),
+            m(OpCode::ZScore, "z", "a", ""),
+            m(OpCode::ZCard, "z", "", ""),
+            zrange(0, -1),
+        ],
+    );
+    assert_eq!(
+        vec![
+            val("1"),
+            val("1"),
+            val("1"),
+            val("0"),
+            ExecuteResult::Error("ERR value is not a valid float".to_owned()),
+            val("1"),
+            val("3"),
+            vals(&["c", "-inf", "a", "1", "b", "1.5"]),
+        ],
+        rsts
+    );
+
+    let rsts = exec(
+        &rp,
+        3,
+        vec![
+            m(OpCode::ZRem, "z", "a", ""),
+            m(OpCode::ZRem, "z", "a", ""),
+            zrange(1, 1),
+            Command::range(OpCode::ZRange, b"z", -2, -1),
+        ],
+    );
+    assert_eq!(
+        vec![val("1"), val("0"), vals(&["b", "1.5"]), vals(&["c", "b"])],
+        rsts
+    );
+
+    // meta, 2 members and 2 score indexes.
+    assert_eq!(5, count_records(&rp, "z"));
+}
+
+#[test]
+fn test_collection_with_string() {
+    let rp = new_replica();
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            Command::from(("Set", "x", "1")),
+            m(OpCode::HSet, "x", "f", "1"),
+            m(OpCode::SAdd, "h", "f", ""),
+            m(OpCode::HSet, "h", "f", "1"),
+            Command::from(("Get", "h", "")),
+            Command::from(("Incr", "h", "1")),
+            Command::from(("SetIfAbsent", "h", "1")),
+            m(OpCode::Type, "x", "", ""),
+            m(OpCode::Type, "h", "", ""),
+        ],
+    );
+    assert_eq!(
+        vec![
+            ExecuteResult::Success,
+            wrongtype(),
+            val("1"),
+            wrongtype(),
+            wrongtype(),
+            wrongtype(),
+            val(""),
+            val("string"),
+            val("set"),
+        ],
+        rsts
+    );
+
+    // Set replaces a collection and Delete removes it.
+    let rsts = exec(
+        &rp,
+        2,
+        vec![
+            m(OpCode::SAdd, "s", "m", ""),
+            Command::from(("Set", "h", "2")),
+            Command::from(("Get", "h", "")),
+            Command::from(("Delete", "s", "")),
+            m(OpCode::Type, "s", "", ""),
+        ],
+    );
+    assert_eq!(
+        vec![
+            val("1"),
+            ExecuteResult::Success,
+            val("2"),
+            ExecuteResult::Success,
+            val("none"),
+        ],
+        rsts
+    );
+    assert_eq!(0, count_records(&rp, "h"));
+    assert_eq!(0, count_records(&rp, "s"));
+}
+
+#[test]
+fn test_collection_expire() {
+    let rp = new_replica();
+
+    let at = |cmd: Command, now: i64| Command { now_ms: now, ..cmd };
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            at(m(OpCode::HSet, "h", "a", "1"), 10),
+            at(Command::expire(b"h", 100), 10),
+            at(Command::from(("Ttl", "h", "")), 10),
+            at(m(OpCode::HLen, "h", "", ""), 99),
+            at(m(OpCode::HLen, "h", "", ""), 100),
+        ],
+    );
+    assert_eq!(
+        vec![val("1"), val("1"), val("90"), val("1"), val("0")],
+        rsts
+    );
+
+    // the expired hash is removed before a new one is created.
+    let rsts = exec(
+        &rp,
+        2,
+        vec![
+            at(m(OpCode::HSet, "h", "b", "2"), 100),
+            at(m(OpCode::HGetAll, "h", "", ""), 100),
+            at(Command::from(("Ttl", "h", "")), 100),
+        ],
+    );
+    assert_eq!(vec![val("1"), vals(&["b", "2"]), val("-1")], rsts);
+
+    // DeleteExpired removes an expired collection.
+    exec(&rp, 3, vec![at(Command::expire(b"h", 200), 100)]);
+    let rsts = exec(
+        &rp,
+        4,
+        vec![at(Command::of(OpCode::DeleteExpired, b"h", &[]), 200)],
+    );
+    assert_eq!(vec![val("1")], rsts);
+    assert_eq!(0, count_records(&rp, "h"));
+}
+
+#[test]
+fn test_collection_apart_from_strings() {
+    let rp = new_replica();
+
+    // a string at what a collection record would be keyed with, were they in one column family.
+    let mk = String::from_utf8(meta_key(b"h")).unwrap();
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            Command::from(("Set", &mk[..], "s")),
+            m(OpCode::HSet, "h", "f", "1"),
+            Command::read_range(b"", b"", 0, false),
+        ],
+    );
+    assert_eq!(
+        vals(&[&mk[..], "s"]),
+        rsts[2],
+        "collections are not read as strings"
+    );
+
+    let rsts = exec(
+        &rp,
+        2,
+        vec![
+            Command::from(("Get", &mk[..], "")),
+            m(OpCode::HGet, "h", "f", ""),
+        ],
+    );
+    assert_eq!(vec![val("s"), val("1")], rsts);
+
+    // removing the string does not touch the hash.
+    exec(&rp, 3, vec![Command::from(("Delete", &mk[..], ""))]);
+    let rsts = exec(&rp, 4, vec![m(OpCode::HGetAll, "h", "", "")]);
+    assert_eq!(vec![vals(&["f", "1"])], rsts);
+    assert_eq!(2, count_records(&rp, "h"));
+}
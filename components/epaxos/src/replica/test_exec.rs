@@ -177,6 +177,187 @@ fn test_execute_commands() {
     }
 }
 
+#[test]
+fn test_execute_commands_notify_waiters() {
+    let rp = new_replica();
+    rp.storage
+        .set_kv(&"x".as_bytes().to_vec(), &vec![11])
+        .unwrap();
+
+    let inst = test_inst!(
+        (2, 2),
+        [("Get", "x", ""), ("Set", "x", "foo"), ("Get", "x", "")]
+    );
+    let iid = inst.instance_id.unwrap();
+
+    let mut rx = rp.waiters.register(iid);
+    let mut rx_other = rp.waiters.register((2, 3).into());
+
+    rp.execute_commands(vec![inst]).unwrap();
+
+    assert_eq!(
+        vec![
+            ExecuteResult::SuccessWithVal {
+                value: Some(vec![11])
+            },
+            ExecuteResult::Success,
+            ExecuteResult::SuccessWithVal {
+                value: Some("foo".as_bytes().to_vec())
+            },
+        ],
+        rx.try_recv().unwrap()
+    );
+
+    // waiters of other instances are not notified.
+    assert!(rx_other.try_recv().is_err());
+    assert_eq!(false, rp.waiters.notify(iid, vec![]));
+}
+
+#[test]
+fn test_execute_read_modify_write_commands() {
+    let rp = new_replica();
+    rp.storage
+        .set_kv(&"x".as_bytes().to_vec(), &"10".as_bytes().to_vec())
+        .unwrap();
+
+    let val = |v: Option<&str>| ExecuteResult::SuccessWithVal {
+        value: v.map(|x| x.as_bytes().to_vec()),
+    };
+
+    let cases: Vec<(Vec<Command>, Vec<ExecuteResult>, Option<&str>)> = vec![
+        (
+            cmds![("Incr", "x", "5"), ("Incr", "x", "-20")],
+            vec![val(Some("15")), val(Some("-5"))],
+            Some("-5"),
+        ),
+        (
+            cmds![("Incr", "y", "1"), ("Incr", "x", "a")],
+            vec![
+                val(Some("1")),
+                ExecuteResult::Error("ERR value is not an integer or out of range".into()),
+            ],
+            Some("-5"),
+        ),
+        (
+            cmds![("Set", "x", "9223372036854775807"), ("Incr", "x", "1")],
+            vec![
+                ExecuteResult::Success,
+                ExecuteResult::Error("ERR increment or decrement would overflow".into()),
+            ],
+            Some("9223372036854775807"),
+        ),
+        (
+            cmds![
+                ("SetIfAbsent", "x", "a"),
+                ("Delete", "x", ""),
+                ("SetIfAbsent", "x", "b")
+            ],
+            vec![
+                val(Some("9223372036854775807")),
+                ExecuteResult::Success,
+                val(None),
+            ],
+            Some("b"),
+        ),
+        (
+            vec![
+                Command::compare_and_set(b"x", Some(&b"c"[..]), b"d"),
+                Command::compare_and_set(b"x", Some(&b"b"[..]), b"e"),
+                Command::compare_and_set(b"x", None, b"f"),
+            ],
+            vec![val(Some("b")), val(Some("b")), val(Some("e"))],
+            Some("e"),
+        ),
+        (
+            vec![
+                Command::compare_and_set(b"z", Some(&b""[..]), b"1"),
+                Command::compare_and_set(b"z", None, b"2"),
+            ],
+            vec![val(None), val(None)],
+            None,
+        ),
+        (
+            cmds![("Append", "x", "12"), ("Append", "x", "3")],
+            vec![val(Some("e12")), val(Some("e123"))],
+            Some("e123"),
+        ),
+    ];
+
+    for (i, (cmds, want, xval)) in cases.iter().enumerate() {
+        let mut inst = test_inst!((2, i as i64), []);
+        inst.cmds = cmds.clone();
+        let iid = inst.instance_id.unwrap();
+
+        let mut rx = rp.waiters.register(iid);
+        rp.execute_commands(vec![inst]).unwrap();
+
+        assert_eq!(want, &rx.try_recv().unwrap(), "case {}", i);
+        assert_eq!(
+            xval.map(|x| x.as_bytes().to_vec()),
+            rp.storage.get_kv(&"x".as_bytes().to_vec()).unwrap(),
+            "case {}",
+            i
+        );
+    }
+
+    // an empty value is not an absent one.
+    assert_eq!(
+        Some("2".as_bytes().to_vec()),
+        rp.storage.get_kv(&"z".as_bytes().to_vec()).unwrap()
+    );
+}
+
+#[test]
+fn test_execute_watch() {
+    let rp = new_replica();
+    rp.storage
+        .set_kv(&"x".as_bytes().to_vec(), &"1".as_bytes().to_vec())
+        .unwrap();
+
+    // watched value matches: applied.
+    let mut inst1 = test_inst!((2, 1), []);
+    inst1.cmds = vec![
+        Command::watch(b"x", Some(&b"1"[..])),
+        Command::watch(b"y", None),
+        Command::from(("Set", "x", "2")),
+        Command::from(("Get", "x", "")),
+    ];
+
+    // watched value changed by inst1: aborted.
+    let mut inst2 = test_inst!((2, 2), []);
+    inst2.cmds = vec![
+        Command::from(("Set", "y", "3")),
+        Command::watch(b"x", Some(&b"1"[..])),
+    ];
+
+    let mut rx1 = rp.waiters.register(inst1.instance_id.unwrap());
+    let mut rx2 = rp.waiters.register(inst2.instance_id.unwrap());
+
+    rp.execute_commands(vec![inst1, inst2]).unwrap();
+
+    assert_eq!(
+        vec![
+            ExecuteResult::Success,
+            ExecuteResult::Success,
+            ExecuteResult::Success,
+            ExecuteResult::SuccessWithVal {
+                value: Some("2".as_bytes().to_vec())
+            },
+        ],
+        rx1.try_recv().unwrap()
+    );
+    assert_eq!(
+        vec![ExecuteResult::Aborted, ExecuteResult::Aborted],
+        rx2.try_recv().unwrap()
+    );
+
+    assert_eq!(
+        Some("2".as_bytes().to_vec()),
+        rp.storage.get_kv(&"x".as_bytes().to_vec()).unwrap()
+    );
+    assert_eq!(None, rp.storage.get_kv(&"y".as_bytes().to_vec()).unwrap());
+}
+
 #[test]
 fn test_execute_instances() {
     let rp = new_replica();
@@ -316,3 +497,37 @@ fn test_replica_execute() {
         }
     }
 }
+
+#[test]
+fn test_execute_result_to_proto() {
+    let cases = vec![
+        ExecuteResult::Success,
+        ExecuteResult::SuccessWithVal { value: None },
+        ExecuteResult::SuccessWithVal {
+            value: Some(b"".to_vec()),
+        },
+        ExecuteResult::SuccessWithVal {
+            value: Some(b"x".to_vec()),
+        },
+        ExecuteResult::SuccessWithVals { values: vec![] },
+        ExecuteResult::SuccessWithVals {
+            values: vec![b"a".to_vec(), b"".to_vec()],
+        },
+        ExecuteResult::Error("ERR foo".to_owned()),
+        ExecuteResult::Aborted,
+    ];
+
+    for r in cases.iter() {
+        let pb: crate::qpaxos::ExecResult = r.into();
+        assert_eq!(r, &ExecuteResult::from(pb), "{:?}", r);
+    }
+
+    let pb = crate::qpaxos::ExecResult {
+        status: 100,
+        ..Default::default()
+    };
+    assert_eq!(
+        ExecuteResult::Error("ERR unknown result status 100".to_owned()),
+        ExecuteResult::from(pb)
+    );
+}
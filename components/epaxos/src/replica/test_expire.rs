@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use crate::qpaxos::{Command, Instance, OpCode};
+use crate::replica::*;
+use crate::testutil;
+use storage::DBColumnFamily;
+use storage::MemEngine;
+
+fn new_replica() -> Replica {
+    testutil::new_replica(
+        1,
+        vec![1, 2, 3],
+        vec![],
+        Arc::new(MemEngine::new().unwrap()),
+    )
+}
+
+/// at returns `cmd` evaluated as of `now`.
+fn at(cmd: Command, now: i64) -> Command {
+    Command { now_ms: now, ..cmd }
+}
+
+fn set_ex(key: &str, val: &str, expire_at: i64) -> Command {
+    Command {
+        expire_at_ms: expire_at,
+        ..Command::from(("Set", key, val))
+    }
+}
+
+/// exec executes an instance of `cmds` and returns the results.
+fn exec(rp: &Replica, idx: i64, cmds: Vec<Command>) -> Vec<ExecuteResult> {
+    let inst = Instance {
+        instance_id: Some((2, idx).into()),
+        cmds,
+        ..Default::default()
+    };
+
+    let mut rx = rp.waiters.register(inst.instance_id.unwrap());
+    rp.execute_commands(vec![inst]).unwrap();
+    rx.try_recv().unwrap()
+}
+
+fn val(v: &str) -> ExecuteResult {
+    ExecuteResult::SuccessWithVal {
+        value: Some(v.as_bytes().to_vec()),
+    }
+}
+
+fn nil() -> ExecuteResult {
+    ExecuteResult::SuccessWithVal { value: None }
+}
+
+fn expire_rec(rp: &Replica, key: &str) -> Option<Vec<u8>> {
+    rp.storage
+        .get(DBColumnFamily::Status, &expire_key(key.as_bytes()))
+        .unwrap()
+}
+
+#[test]
+fn test_expire_set_get() {
+    let rp = new_replica();
+
+    let rsts = exec(&rp, 1, vec![at(set_ex("x", "1", 100), 10)]);
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+    assert_eq!(Some(b"100".to_vec()), expire_rec(&rp, "x"));
+
+    let get = Command::from(("Get", "x", ""));
+    let ttl = Command::from(("Ttl", "x", ""));
+    let rsts = exec(&rp, 2, vec![at(get.clone(), 99), at(ttl.clone(), 99)]);
+    assert_eq!(vec![val("1"), val("1")], rsts);
+
+    // expired as of 100, but not yet removed.
+    let rsts = exec(&rp, 3, vec![at(get.clone(), 100), at(ttl.clone(), 100)]);
+    assert_eq!(vec![nil(), val("-2")], rsts);
+    assert_eq!(
+        Some(b"1".to_vec()),
+        rp.storage
+            .get(DBColumnFamily::Default, &b"x".to_vec())
+            .unwrap()
+    );
+
+    // a plain Set removes the expire-at.
+    let rsts = exec(
+        &rp,
+        4,
+        vec![
+            at(Command::from(("Set", "x", "2")), 100),
+            at(ttl.clone(), 100),
+        ],
+    );
+    assert_eq!(vec![ExecuteResult::Success, val("-1")], rsts);
+    assert_eq!(None, expire_rec(&rp, "x"));
+
+    // KEEPTTL and commands that modify a value keep the expire-at.
+    let rsts = exec(
+        &rp,
+        5,
+        vec![
+            at(set_ex("x", "3", 200), 100),
+            at(set_ex("x", "4", -1), 100),
+            at(Command::from(("Incr", "x", "1")), 100),
+            at(Command::from(("Append", "x", "0")), 100),
+            at(ttl.clone(), 150),
+        ],
+    );
+    assert_eq!(
+        vec![
+            ExecuteResult::Success,
+            ExecuteResult::Success,
+            val("5"),
+            val("50"),
+            val("50"),
+        ],
+        rsts
+    );
+
+    // an expired key is absent for SetIfAbsent and Incr, and the new one never expires.
+    let rsts = exec(
+        &rp,
+        6,
+        vec![
+            at(Command::from(("SetIfAbsent", "x", "7")), 200),
+            at(ttl.clone(), 200),
+        ],
+    );
+    assert_eq!(vec![nil(), val("-1")], rsts);
+}
+
+#[test]
+fn test_expire_persist() {
+    let rp = new_replica();
+
+    let ttl = Command::from(("Ttl", "x", ""));
+    let persist = Command::from(("Persist", "x", ""));
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            at(Command::expire(b"x", 100), 10),
+            at(Command::from(("Set", "x", "1")), 10),
+            at(persist.clone(), 10),
+            at(Command::expire(b"x", 100), 10),
+            at(ttl.clone(), 10),
+            at(persist.clone(), 10),
+            at(persist.clone(), 10),
+            at(ttl.clone(), 10),
+        ],
+    );
+    assert_eq!(
+        vec![
+            val("0"),
+            ExecuteResult::Success,
+            val("0"),
+            val("1"),
+            val("90"),
+            val("1"),
+            val("0"),
+            val("-1"),
+        ],
+        rsts
+    );
+    assert_eq!(None, expire_rec(&rp, "x"));
+
+    // an expire-at in the past deletes the key.
+    let rsts = exec(
+        &rp,
+        2,
+        vec![at(Command::expire(b"x", 5), 10), at(ttl.clone(), 10)],
+    );
+    assert_eq!(vec![val("1"), val("-2")], rsts);
+    assert_eq!(
+        None,
+        rp.storage
+            .get(DBColumnFamily::Default, &b"x".to_vec())
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_expire_delete_expired() {
+    let rp = new_replica();
+
+    exec(
+        &rp,
+        1,
+        vec![
+            at(set_ex("x", "1", 100), 10),
+            at(set_ex("y", "1", 200), 10),
+            at(Command::from(("Set", "z", "1")), 10),
+        ],
+    );
+
+    let all = |_: &[u8]| true;
+    let expired = list_expired(&rp.storage, 150, 10, all);
+    assert_eq!(vec![(b"x".to_vec(), 100)], expired);
+
+    // keys filtered out are not counted in the limit.
+    let expired = list_expired(&rp.storage, 250, 1, |k: &[u8]| k != b"x");
+    assert_eq!(vec![(b"y".to_vec(), 200)], expired);
+
+    let del = |k: &str| at(Command::of(OpCode::DeleteExpired, k.as_bytes(), &[]), 150);
+    let rsts = exec(&rp, 2, vec![del("x"), del("y"), del("z")]);
+    assert_eq!(vec![val("1"), val("0"), val("0")], rsts);
+
+    assert_eq!(None, expire_rec(&rp, "x"));
+    assert_eq!(
+        None,
+        rp.storage
+            .get(DBColumnFamily::Default, &b"x".to_vec())
+            .unwrap()
+    );
+    assert_eq!(Some(b"200".to_vec()), expire_rec(&rp, "y"));
+    assert_eq!(0, list_expired(&rp.storage, 150, 10, all).len());
+    assert_eq!(1, list_expired(&rp.storage, 200, 10, all).len());
+}
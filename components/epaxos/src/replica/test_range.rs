@@ -0,0 +1,291 @@
+use std::sync::Arc;
+
+use crate::qpaxos::{Command, Instance, OpCode};
+use crate::replica::*;
+use crate::testutil;
+use storage::DBColumnFamily;
+use storage::MemEngine;
+
+fn new_replica() -> Replica {
+    testutil::new_replica(
+        1,
+        vec![1, 2, 3],
+        vec![],
+        Arc::new(MemEngine::new().unwrap()),
+    )
+}
+
+/// exec executes an instance of `cmds` and returns the results.
+fn exec(rp: &Replica, idx: i64, cmds: Vec<Command>) -> Vec<ExecuteResult> {
+    let inst = Instance {
+        instance_id: Some((2, idx).into()),
+        cmds,
+        ..Default::default()
+    };
+
+    let mut rx = rp.waiters.register(inst.instance_id.unwrap());
+    rp.execute_commands(vec![inst]).unwrap();
+    rx.try_recv().unwrap()
+}
+
+fn at(cmd: Command) -> Command {
+    Command { now_ms: 10, ..cmd }
+}
+
+fn set(k: &str) -> Command {
+    at(Command::from(("Set", k, &format!("v{}", k)[..])))
+}
+
+fn read(start: &str, end: &str, limit: i64, reverse: bool) -> Command {
+    at(Command::read_range(
+        start.as_bytes(),
+        end.as_bytes(),
+        limit,
+        reverse,
+    ))
+}
+
+/// pairs builds the result of ReadRange from keys, of which the value is `v<key>`.
+fn pairs(keys: &[&str]) -> ExecuteResult {
+    let mut values = vec![];
+    for k in keys.iter() {
+        values.push(k.as_bytes().to_vec());
+        values.push(format!("v{}", k).into_bytes());
+    }
+    ExecuteResult::SuccessWithVals { values }
+}
+
+#[test]
+fn test_read_range() {
+    let rp = new_replica();
+
+    exec(
+        &rp,
+        1,
+        vec![
+            set(""),
+            set("a"),
+            set("b"),
+            set("c"),
+            set("d"),
+            set("e"),
+            at(Command::member(OpCode::HSet, b"bb", b"f", b"1")),
+            at(Command::expire(b"e", 5)),
+        ],
+    );
+
+    let cases = vec![
+        (read("a", "d", 0, false), pairs(&["a", "b", "c"])),
+        (read("a", "d", 2, false), pairs(&["a", "b"])),
+        (read("a", "d", 0, true), pairs(&["c", "b", "a"])),
+        (read("a", "d", 2, true), pairs(&["c", "b"])),
+        (read("b", "", 0, false), pairs(&["b", "c", "d"])),
+        (read("b", "", 2, true), pairs(&["d", "c"])),
+        (read("", "", 0, false), pairs(&["", "a", "b", "c", "d"])),
+        (read("", "b", 0, true), pairs(&["a", ""])),
+        (read("x", "", 0, false), pairs(&[])),
+        (read("c", "c", 0, false), pairs(&[])),
+    ];
+
+    for (i, (cmd, want)) in cases.into_iter().enumerate() {
+        let rsts = exec(&rp, 2 + i as i64, vec![cmd.clone()]);
+        assert_eq!(vec![want], rsts, "{}", cmd);
+    }
+}
+
+#[test]
+fn test_read_range_sees_writes_in_batch() {
+    let rp = new_replica();
+
+    exec(&rp, 1, vec![set("a"), set("b"), set("c")]);
+
+    let rsts = exec(
+        &rp,
+        2,
+        vec![
+            read("a", "", 0, false),
+            at(Command::from(("Delete", "b", ""))),
+            set("bb"),
+            set("d"),
+            read("a", "", 0, false),
+            read("a", "", 0, true),
+            read("a", "c", 1, true),
+        ],
+    );
+
+    assert_eq!(pairs(&["a", "b", "c"]), rsts[0]);
+    assert_eq!(pairs(&["a", "bb", "c", "d"]), rsts[4]);
+    assert_eq!(pairs(&["d", "c", "bb", "a"]), rsts[5]);
+    assert_eq!(pairs(&["bb"]), rsts[6]);
+}
+
+/// keys returns all keys in a column family, in key order.
+fn keys(rp: &Replica, cf: DBColumnFamily) -> Vec<Vec<u8>> {
+    let mut rst = vec![];
+    let mut k = vec![];
+    let mut include = true;
+    while let Some((key, _)) = rp.storage.next(cf, &k, include) {
+        rst.push(key.clone());
+        k = key;
+        include = false;
+    }
+    rst
+}
+
+#[test]
+fn test_delete_range() {
+    let rp = new_replica();
+    let hget = |k: &str| at(Command::member(OpCode::HGet, k.as_bytes(), b"f", b""));
+    let one = ExecuteResult::SuccessWithVal {
+        value: Some(b"1".to_vec()),
+    };
+
+    exec(
+        &rp,
+        1,
+        vec![
+            set(""),
+            set("a"),
+            set("b"),
+            set("c"),
+            set("d"),
+            at(Command::member(OpCode::HSet, b"bb", b"f", b"1")),
+            at(Command::member(OpCode::HSet, b"y", b"f", b"1")),
+            at(Command::expire(b"c", 100)),
+            at(Command::expire(b"y", 100)),
+        ],
+    );
+
+    let rsts = exec(&rp, 2, vec![at(Command::delete_range(b"b", b"d"))]);
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+
+    let rsts = exec(&rp, 3, vec![read("", "", 0, false), hget("bb"), hget("y")]);
+    assert_eq!(pairs(&["", "a", "d"]), rsts[0]);
+    assert_eq!(ExecuteResult::SuccessWithVal { value: None }, rsts[1]);
+    assert_eq!(one, rsts[2]);
+
+    let status = keys(&rp, DBColumnFamily::Status);
+    assert!(!status.contains(&expire_key(b"c")));
+    assert!(status.contains(&expire_key(b"y")));
+
+    // everything is removed, but nothing else.
+    exec(&rp, 4, vec![at(Command::delete_range(b"", b""))]);
+    assert_eq!(Vec::<Vec<u8>>::new(), keys(&rp, DBColumnFamily::Default));
+    assert_eq!(Vec::<Vec<u8>>::new(), keys(&rp, DBColumnFamily::Collection));
+
+    let status = keys(&rp, DBColumnFamily::Status);
+    assert!(!status.iter().any(|k| k.starts_with(EXPIRE_PREFIX)));
+    assert!(status.len() > 0);
+}
+
+#[test]
+fn test_delete_range_in_batch() {
+    let rp = new_replica();
+
+    exec(
+        &rp,
+        1,
+        vec![
+            set("a"),
+            set("b"),
+            at(Command::member(OpCode::HSet, b"bz", b"f", b"1")),
+        ],
+    );
+
+    // keys written earlier in the batch are removed too, and instances after a DeleteRange see
+    // it.
+    let insts: Vec<_> = vec![
+        vec![
+            set("ba"),
+            at(Command::member(OpCode::SAdd, b"by", b"m", b"")),
+        ],
+        vec![at(Command::delete_range(b"b", b"c"))],
+        vec![set("bc"), read("", "", 0, false)],
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, cmds)| Instance {
+        instance_id: Some((2, 2 + i as i64).into()),
+        cmds,
+        ..Default::default()
+    })
+    .collect();
+
+    let mut rxs: Vec<_> = insts
+        .iter()
+        .map(|inst| rp.waiters.register(inst.instance_id.unwrap()))
+        .collect();
+
+    let iids = rp.execute_commands(insts).unwrap();
+    assert_eq!(3, iids.len());
+
+    let rsts = rxs[2].try_recv().unwrap();
+    assert_eq!(pairs(&["a", "bc"]), rsts[1]);
+
+    let ks = keys(&rp, DBColumnFamily::Default);
+    assert_eq!(vec![b"a".to_vec(), b"bc".to_vec()], ks);
+    assert_eq!(Vec::<Vec<u8>>::new(), keys(&rp, DBColumnFamily::Collection));
+}
+
+#[test]
+fn test_delete_range_slots() {
+    // in slot mode only keys in the slots of the group are removed: "bar" is in slot 5061 and
+    // "foo" is in slot 12182, written by another group on the same node.
+    let mut rp = new_replica();
+    rp.slots = Some((0, 8191));
+
+    exec(
+        &rp,
+        1,
+        vec![
+            set("bar"),
+            set("foo"),
+            at(Command::member(OpCode::HSet, b"{bar}h", b"f", b"1")),
+            at(Command::member(OpCode::HSet, b"{foo}h", b"f", b"1")),
+            at(Command::expire(b"bar", 100)),
+            at(Command::expire(b"foo", 100)),
+        ],
+    );
+
+    let rsts = exec(&rp, 2, vec![at(Command::delete_range(b"", b""))]);
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+
+    assert_eq!(vec![b"foo".to_vec()], keys(&rp, DBColumnFamily::Default));
+
+    let colls = keys(&rp, DBColumnFamily::Collection);
+    assert!(colls.contains(&meta_key(b"{foo}h")));
+    assert!(!colls.contains(&meta_key(b"{bar}h")));
+    assert!(!colls
+        .iter()
+        .any(|k| k.starts_with(&members_prefix(b"{bar}h"))));
+
+    let status = keys(&rp, DBColumnFamily::Status);
+    assert!(status.contains(&expire_key(b"foo")));
+    assert!(!status.contains(&expire_key(b"bar")));
+}
+
+#[test]
+fn test_delete_range_slots_cut() {
+    // in slot mode a DeleteRange reads a limited number of keys, and replies where to continue.
+    let mut rp = new_replica();
+    rp.slots = Some((0, 16383));
+
+    let n = MAX_SLOT_DELETE_KEYS + 10;
+    let names: Vec<String> = (0..n).map(|i| format!("k{:05}", i)).collect();
+    exec(&rp, 1, names.iter().map(|k| set(k)).collect());
+
+    let rsts = exec(&rp, 2, vec![at(Command::delete_range(b"", b""))]);
+    let cut = names[MAX_SLOT_DELETE_KEYS].as_bytes().to_vec();
+    assert_eq!(
+        vec![ExecuteResult::SuccessWithVal {
+            value: Some(cut.clone())
+        }],
+        rsts
+    );
+    assert_eq!(10, keys(&rp, DBColumnFamily::Default).len());
+    assert_eq!(cut, keys(&rp, DBColumnFamily::Default)[0]);
+
+    let rsts = exec(&rp, 3, vec![at(Command::delete_range(&cut, b""))]);
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+    assert_eq!(Vec::<Vec<u8>>::new(), keys(&rp, DBColumnFamily::Default));
+}
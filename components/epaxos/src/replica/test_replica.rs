@@ -3,6 +3,7 @@ use std::sync::Arc;
 use crate::conf::ClusterInfo;
 use crate::qpaxos::*;
 use crate::replica::*;
+use crate::replication::PeerClients;
 use crate::testutil;
 use crate::Storage;
 use storage::DBColumnFamily;
@@ -106,6 +107,36 @@ fn test_get_max_instance_ids() {
     assert_eq!(maxs, InstanceIdVec::from(instids![(1, 3), (3, 4), (5, -1)]));
 }
 
+#[test]
+fn test_load_refs() {
+    let inst1 = foo_inst!((1, 3), [(0, 0)]);
+    let inst2 = foo_inst!((2, 5), [(0, 0)]);
+
+    let replica = new_foo_replica(1, new_mem_sto(), &[((1, 3), &inst1), ((2, 5), &inst2)]);
+
+    // stale or absent refs.
+    replica.storage.set_ref("max", 1, (1, 2).into()).unwrap();
+    replica.storage.set_ref("exec", 1, (1, 1).into()).unwrap();
+
+    let (maxs, execs) = replica.load_refs().unwrap();
+    assert_eq!(InstanceIdVec::from([(0, -1), (1, 3), (2, 5)]), maxs);
+    assert_eq!(InstanceIdVec::from([(0, -1), (1, 1), (2, -1)]), execs);
+
+    assert_eq!(None, replica.storage.get_ref("max", 0).unwrap());
+    assert_eq!(
+        Some((1, 3).into()),
+        replica.storage.get_ref("max", 1).unwrap()
+    );
+    assert_eq!(
+        Some((2, 5).into()),
+        replica.storage.get_ref("max", 2).unwrap()
+    );
+    assert_eq!(
+        Some((1, 1).into()),
+        replica.storage.get_ref("exec", 1).unwrap()
+    );
+}
+
 #[test]
 fn test_handle_replicate_request_invalid() {
     let replica_id = 2;
@@ -401,6 +432,133 @@ fn test_handle_commit_request() {
     _test_updated_inst(&inst, cmds.clone(), fdeps.clone(), true, false);
 }
 
+#[test]
+fn test_handle_try_pre_accept_request() {
+    let replica_id = 1;
+
+    // the instance being recovered, initiated by replica 0.
+    let inst = inst!(
+        (0, 5),
+        (0, 1, _),
+        [("Set", "x", "1")],
+        [(0, 4), (1, 2), (2, 2)],
+        "withdeps"
+    );
+    let iid = inst.instance_id.unwrap();
+
+    // a conflicting instance on replica 1 that does not depend on (0, 5).
+    let confl = inst!(
+        (1, 3),
+        (0, 0, _),
+        [("Set", "x", "2")],
+        [(0, 4), (1, 2), (2, 0)],
+        "withdeps"
+    );
+    // a conflicting instance that depends on (0, 5).
+    let mut after = confl.clone();
+    after.deps = Some(instids![(0, 5), (1, 2), (2, 0)].into());
+    // a non-conflicting instance not in deps.
+    let other = inst!(
+        (2, 3),
+        (0, 0, _),
+        [("Set", "y", "1")],
+        [(0, 4), (1, 2), (2, 2)],
+        "withdeps"
+    );
+    let mut committed = confl.clone();
+    committed.committed = true;
+    committed.final_deps = committed.deps.clone();
+
+    let conflict = |st: ConflictStatus| TryPreAcceptReply {
+        ok: false,
+        conflict_instance: Some((1, 3).into()),
+        conflict_replica: 1,
+        conflict_status: st as i32,
+    };
+    let ok = TryPreAcceptReply {
+        ok: true,
+        conflict_instance: None,
+        conflict_replica: 0,
+        conflict_status: ConflictStatus::NoConflict as i32,
+    };
+
+    let cases: Vec<(&Instance, Vec<(i64, i64)>, TryPreAcceptReply)> = vec![
+        // the conflicting instance is in deps.
+        (&confl, vec![(0, 4), (1, 3), (2, 2)], ok.clone()),
+        (
+            &confl,
+            vec![(0, 4), (1, 2), (2, 2)],
+            conflict(ConflictStatus::FastAccepted),
+        ),
+        (
+            &committed,
+            vec![(0, 4), (1, 2), (2, 2)],
+            conflict(ConflictStatus::Committed),
+        ),
+        (&after, vec![(0, 4), (1, 2), (2, 2)], ok.clone()),
+        (&other, vec![(0, 4), (1, 2), (2, 2)], ok.clone()),
+    ];
+
+    for (local, deps, want) in cases.iter() {
+        let liid = local.instance_id.unwrap();
+        let replica = new_foo_replica(
+            replica_id,
+            new_mem_sto(),
+            &[((liid.replica_id, liid.idx), local)],
+        );
+
+        let mut req_inst = inst.clone();
+        req_inst.deps = Some(deps.as_slice().into());
+
+        let req = MakeRequest::try_pre_accept(replica_id, &req_inst);
+        let req: TryPreAcceptRequest = req.phase.unwrap().try_into().unwrap();
+
+        let mut local_inst = replica.get_instance(iid).unwrap();
+        let repl = replica
+            .handle_try_pre_accept(&req, &mut local_inst)
+            .unwrap();
+        assert_eq!(*want, repl, "local:{} deps:{:?}", local, deps);
+
+        if repl.ok {
+            assert_eq!(req_inst.deps, local_inst.deps);
+            assert_eq!(inst.cmds, local_inst.cmds);
+        } else {
+            assert_eq!(None, local_inst.deps);
+            assert_eq!(Vec::<Command>::new(), local_inst.cmds);
+
+            // nothing is stored for a rejected TryPreAccept.
+            let req = MakeRequest::try_pre_accept(replica_id, &req_inst);
+            replica.handle_replicate(req).unwrap();
+            assert_eq!(None, replica.storage.get_instance(iid).unwrap());
+        }
+    }
+
+    {
+        // the instance is already accepted by this replica.
+        let mut accepted = inst.clone();
+        accepted.final_deps = accepted.deps.clone();
+
+        let replica = new_foo_replica(replica_id, new_mem_sto(), &[((0, 5), &accepted)]);
+
+        let req = MakeRequest::try_pre_accept(replica_id, &inst);
+        let req: TryPreAcceptRequest = req.phase.unwrap().try_into().unwrap();
+
+        let mut local_inst = replica.get_instance(iid).unwrap();
+        let repl = replica
+            .handle_try_pre_accept(&req, &mut local_inst)
+            .unwrap();
+        assert_eq!(
+            TryPreAcceptReply {
+                ok: false,
+                conflict_instance: Some(iid),
+                conflict_replica: 0,
+                conflict_status: ConflictStatus::Accepted as i32,
+            },
+            repl
+        );
+    }
+}
+
 fn _test_updated_inst(
     got: &Instance,
     cmds: Vec<Command>,
@@ -436,7 +594,7 @@ groups:
 
     let ci = ClusterInfo::from_str(cont).unwrap();
 
-    let mut rp = Replica::new(1, &ci, new_mem_sto()).unwrap();
+    let mut rp = Replica::new(1, &ci, new_mem_sto(), Arc::new(PeerClients::default())).unwrap();
     assert_eq!(1, rp.replica_id);
 
     rp.group_replica_ids.sort();
@@ -461,6 +619,6 @@ groups:
         rp.peers[1]
     );
 
-    let rp = Replica::new(4, &ci, new_mem_sto());
+    let rp = Replica::new(4, &ci, new_mem_sto(), Arc::new(PeerClients::default()));
     assert!(rp.is_err());
 }
@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use prost::Message;
+
+use crate::qpaxos::{Command, Instance, OpCode, TxnInfo, TxnIntent};
+use crate::replica::*;
+use crate::testutil;
+use crate::txn::txn_cmd;
+use storage::DBColumnFamily;
+use storage::MemEngine;
+
+fn new_replica() -> Replica {
+    testutil::new_replica(
+        1,
+        vec![1, 2, 3],
+        vec![],
+        Arc::new(MemEngine::new().unwrap()),
+    )
+}
+
+fn new_txn(id: &str, primary: &str) -> TxnInfo {
+    TxnInfo {
+        id: id.as_bytes().to_vec(),
+        primary: primary.as_bytes().to_vec(),
+        ctime_ms: 1,
+        seq: 0,
+    }
+}
+
+fn prepare_cmd(txn: &TxnInfo, key: &str, cmds: Vec<Command>) -> Command {
+    let intent = TxnIntent {
+        txn: Some(txn.clone()),
+        cmds,
+        keys: vec![],
+    };
+    let mut v = vec![];
+    intent.encode(&mut v).unwrap();
+
+    txn_cmd(OpCode::TxnPrepare, key.as_bytes(), &v, txn, 0)
+}
+
+/// exec executes an instance of `cmds` and returns the results.
+fn exec(rp: &Replica, idx: i64, cmds: Vec<Command>) -> Vec<ExecuteResult> {
+    let inst = Instance {
+        instance_id: Some((2, idx).into()),
+        cmds,
+        ..Default::default()
+    };
+
+    let mut rx = rp.waiters.register(inst.instance_id.unwrap());
+    rp.execute_commands(vec![inst]).unwrap();
+    rx.try_recv().unwrap()
+}
+
+fn decision(rp: &Replica, id: &str) -> Option<Vec<u8>> {
+    rp.storage
+        .get(DBColumnFamily::Status, &decision_key(id.as_bytes()))
+        .unwrap()
+}
+
+#[test]
+fn test_txn_prepare_commit() {
+    let rp = new_replica();
+    let t1 = new_txn("t1", "x");
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![
+            prepare_cmd(&t1, "x", vec![Command::from(("Set", "x", "1"))]),
+            prepare_cmd(
+                &t1,
+                "y",
+                vec![
+                    Command::from(("Set", "y", "2")),
+                    Command::from(("Incr", "y", "3")),
+                ],
+            ),
+        ],
+    );
+    assert_eq!(vec![ExecuteResult::Success; 2], rsts);
+
+    // locked but not yet applied.
+    let mut recs = StatusRecords::new();
+    assert!(rp.get_intent(b"y", &mut recs).unwrap().is_some());
+    assert_eq!(None, rp.storage.get_kv(&b"y".to_vec()).unwrap());
+
+    // another transaction can not lock y.
+    let t2 = new_txn("t2", "y");
+    let rsts = exec(
+        &rp,
+        2,
+        vec![prepare_cmd(
+            &t2,
+            "y",
+            vec![Command::from(("Set", "y", "9"))],
+        )],
+    );
+    assert_eq!(vec![ExecuteResult::Aborted], rsts);
+
+    let rsts = exec(
+        &rp,
+        3,
+        vec![
+            txn_cmd(OpCode::TxnCommit, b"x", &[], &t1, 0),
+            txn_cmd(OpCode::TxnCommit, b"y", &[], &t1, 0),
+            txn_cmd(OpCode::TxnCommit, b"y", &[], &t1, 1),
+        ],
+    );
+    assert_eq!(
+        vec![
+            ExecuteResult::Success,
+            ExecuteResult::Success,
+            ExecuteResult::SuccessWithVal {
+                value: Some(b"5".to_vec())
+            },
+        ],
+        rsts
+    );
+
+    assert_eq!(Some(TXN_COMMIT.to_vec()), decision(&rp, "t1"));
+    assert_eq!(
+        Some(b"1".to_vec()),
+        rp.storage.get_kv(&b"x".to_vec()).unwrap()
+    );
+    assert_eq!(
+        Some(b"5".to_vec()),
+        rp.storage.get_kv(&b"y".to_vec()).unwrap()
+    );
+
+    let mut recs = StatusRecords::new();
+    assert_eq!(None, rp.get_intent(b"x", &mut recs).unwrap());
+    assert_eq!(None, rp.get_intent(b"y", &mut recs).unwrap());
+
+    // aborting a committed transaction replies the decision.
+    let rsts = exec(&rp, 4, vec![txn_cmd(OpCode::TxnAbort, b"x", &[], &t1, 0)]);
+    assert_eq!(
+        vec![ExecuteResult::SuccessWithVal {
+            value: Some(TXN_COMMIT.to_vec())
+        }],
+        rsts
+    );
+}
+
+#[test]
+fn test_txn_abort() {
+    let rp = new_replica();
+    let t1 = new_txn("t1", "x");
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![prepare_cmd(
+            &t1,
+            "y",
+            vec![Command::from(("Set", "y", "2"))],
+        )],
+    );
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+
+    // the primary key is never prepared, e.g., the coordinator crashed: aborting it records the
+    // decision.
+    let rsts = exec(&rp, 2, vec![txn_cmd(OpCode::TxnAbort, b"x", &[], &t1, 0)]);
+    assert_eq!(
+        vec![ExecuteResult::SuccessWithVal {
+            value: Some(TXN_ABORT.to_vec())
+        }],
+        rsts
+    );
+    assert_eq!(Some(TXN_ABORT.to_vec()), decision(&rp, "t1"));
+
+    // a late commit or prepare is rejected.
+    let rsts = exec(&rp, 3, vec![txn_cmd(OpCode::TxnCommit, b"x", &[], &t1, 0)]);
+    assert_eq!(vec![ExecuteResult::Aborted], rsts);
+
+    let rsts = exec(
+        &rp,
+        4,
+        vec![prepare_cmd(
+            &t1,
+            "x",
+            vec![Command::from(("Set", "x", "1"))],
+        )],
+    );
+    assert_eq!(vec![ExecuteResult::Aborted], rsts);
+
+    let rsts = exec(&rp, 5, vec![txn_cmd(OpCode::TxnAbort, b"y", &[], &t1, 0)]);
+    assert_eq!(
+        vec![ExecuteResult::SuccessWithVal {
+            value: Some(TXN_ABORT.to_vec())
+        }],
+        rsts
+    );
+
+    let mut recs = StatusRecords::new();
+    assert_eq!(None, rp.get_intent(b"y", &mut recs).unwrap());
+    assert_eq!(None, rp.storage.get_kv(&b"y".to_vec()).unwrap());
+    assert_eq!(None, rp.storage.get_kv(&b"x".to_vec()).unwrap());
+}
+
+#[test]
+fn test_txn_prepare_watch() {
+    let rp = new_replica();
+    rp.storage.set_kv(&b"x".to_vec(), &b"1".to_vec()).unwrap();
+
+    let t1 = new_txn("t1", "x");
+    let rsts = exec(
+        &rp,
+        1,
+        vec![prepare_cmd(
+            &t1,
+            "x",
+            vec![
+                Command::watch(b"x", Some(&b"2"[..])),
+                Command::from(("Set", "x", "3")),
+            ],
+        )],
+    );
+    assert_eq!(vec![ExecuteResult::Aborted], rsts);
+
+    let mut recs = StatusRecords::new();
+    assert_eq!(None, rp.get_intent(b"x", &mut recs).unwrap());
+}
+
+#[test]
+fn test_txn_forget() {
+    let rp = new_replica();
+    let t1 = new_txn("t1", "x");
+
+    let rsts = exec(
+        &rp,
+        1,
+        vec![prepare_cmd(
+            &t1,
+            "x",
+            vec![Command::from(("Set", "x", "1"))],
+        )],
+    );
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+
+    // not on the primary key.
+    let rsts = exec(&rp, 2, vec![txn_cmd(OpCode::TxnForget, b"y", &[], &t1, 0)]);
+    assert_eq!(vec![ExecuteResult::Aborted], rsts);
+
+    // the primary key is still locked.
+    let rsts = exec(&rp, 3, vec![txn_cmd(OpCode::TxnForget, b"x", &[], &t1, 0)]);
+    assert_eq!(vec![ExecuteResult::Aborted], rsts);
+
+    let rsts = exec(&rp, 4, vec![txn_cmd(OpCode::TxnCommit, b"x", &[], &t1, 0)]);
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+    assert_eq!(Some(TXN_COMMIT.to_vec()), decision(&rp, "t1"));
+
+    let rsts = exec(&rp, 5, vec![txn_cmd(OpCode::TxnForget, b"x", &[], &t1, 0)]);
+    assert_eq!(vec![ExecuteResult::Success], rsts);
+    assert_eq!(None, decision(&rp, "t1"));
+    assert_eq!(
+        Some(b"1".to_vec()),
+        rp.storage.get_kv(&b"x".to_vec()).unwrap()
+    );
+}
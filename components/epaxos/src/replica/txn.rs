@@ -0,0 +1,255 @@
+use prost::Message;
+use storage::StorageError;
+use storage::WriteEntry;
+
+use crate::qpaxos::Command;
+use crate::qpaxos::OpCode;
+use crate::qpaxos::TxnInfo;
+use crate::qpaxos::TxnIntent;
+use crate::replica::set_status_rec;
+use crate::replica::DataRecords;
+use crate::replica::ExecuteResult;
+use crate::replica::Replica;
+use crate::replica::StatusRecords;
+
+/// an intent on a key is stored at `TXN_INTENT_PREFIX + key`, in the status column family.
+pub const TXN_INTENT_PREFIX: &[u8] = b"/txn/intent/";
+
+/// the decision of a transaction is stored at `TXN_DECISION_PREFIX + txn-id`, by the group
+/// serving the primary key of the transaction.
+pub const TXN_DECISION_PREFIX: &[u8] = b"/txn/decision/";
+
+pub const TXN_COMMIT: &[u8] = b"commit";
+pub const TXN_ABORT: &[u8] = b"abort";
+
+pub fn intent_key(key: &[u8]) -> Vec<u8> {
+    [TXN_INTENT_PREFIX, key].concat()
+}
+
+pub fn decision_key(txn_id: &[u8]) -> Vec<u8> {
+    [TXN_DECISION_PREFIX, txn_id].concat()
+}
+
+/// is_txn_prepare returns true if `cmds` is the prepare phase of a transaction.
+pub fn is_txn_prepare(cmds: &[Command]) -> bool {
+    cmds.iter().any(|c| c.op == OpCode::TxnPrepare as i32)
+}
+
+/// is_txn_resolve returns true if `cmd` commits or aborts an intent, or forgets a decision.
+pub fn is_txn_resolve(cmd: &Command) -> bool {
+    cmd.op == OpCode::TxnCommit as i32
+        || cmd.op == OpCode::TxnAbort as i32
+        || cmd.op == OpCode::TxnForget as i32
+}
+
+impl Replica {
+    /// prepare_txn evaluates an instance of `TxnPrepare` commands, which is a participant's vote.
+    ///
+    /// If every key could be locked, it stores the intent of every key and replies `Success` for
+    /// every command. Otherwise nothing is written and it replies `Aborted` for every command.
+    /// A key can not be locked if:
+    /// - it is locked by another transaction;
+    /// - the transaction is already decided, e.g., aborted by recovery;
+    /// - a value watched by a `Watch` command in the intent has changed.
+    pub(crate) fn prepare_txn(
+        &self,
+        cmds: &[Command],
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<(Vec<WriteEntry>, Vec<ExecuteResult>), StorageError> {
+        if !self.can_prepare(cmds, existed, recs)? {
+            return Ok((vec![], vec![ExecuteResult::Aborted; cmds.len()]));
+        }
+
+        let mut entries = vec![];
+        for cmd in cmds.iter() {
+            entries.push(set_status_rec(
+                recs,
+                intent_key(&cmd.key),
+                Some(cmd.value.clone()),
+            ));
+        }
+
+        Ok((entries, vec![ExecuteResult::Success; cmds.len()]))
+    }
+
+    fn can_prepare(
+        &self,
+        cmds: &[Command],
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<bool, StorageError> {
+        for cmd in cmds.iter() {
+            if cmd.op != OpCode::TxnPrepare as i32 {
+                return Ok(false);
+            }
+
+            let intent = TxnIntent::decode(&cmd.value[..])?;
+            let txn = match intent.txn {
+                Some(v) => v,
+                None => return Ok(false),
+            };
+
+            if let Some(cur) = self.get_intent(&cmd.key, recs)? {
+                if cur.txn.map(|x| x.id) != Some(txn.id.clone()) {
+                    return Ok(false);
+                }
+            }
+
+            if cmd.key == txn.primary {
+                if self.get_status_rec(decision_key(&txn.id), recs)?.is_some() {
+                    return Ok(false);
+                }
+            }
+
+            if !self.check_watched(&intent.cmds, existed, recs)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// resolve_txn evaluates a `TxnCommit` or `TxnAbort` command.
+    ///
+    /// `TxnCommit` evaluates the `seq`-th command in the intent and replies its result. The intent
+    /// is removed after its last command is evaluated. `TxnAbort` removes the intent.
+    ///
+    /// On the primary key, the first of them records the decision, and the later one replies
+    /// according to the decision: `TxnCommit` replies `Aborted` if the transaction is aborted, and
+    /// `TxnAbort` replies the decision, thus recovery knows whether to commit or abort.
+    ///
+    /// `TxnForget` is evaluated by `forget_txn`.
+    pub(crate) fn resolve_txn(
+        &self,
+        cmd: &Command,
+        existed: &mut DataRecords,
+        recs: &mut StatusRecords,
+    ) -> Result<(Vec<WriteEntry>, ExecuteResult), StorageError> {
+        let txn = match cmd.txn {
+            Some(ref v) => v,
+            None => {
+                let msg = "ERR transaction command without txn".to_owned();
+                return Ok((vec![], ExecuteResult::Error(msg)));
+            }
+        };
+
+        if cmd.op == OpCode::TxnForget as i32 {
+            return self.forget_txn(&cmd.key, txn, recs);
+        }
+
+        let is_primary = cmd.key == txn.primary;
+        let commit = cmd.op == OpCode::TxnCommit as i32;
+
+        let intent = self
+            .get_intent(&cmd.key, recs)?
+            .filter(|x| x.txn.as_ref().map(|t| &t.id) == Some(&txn.id));
+
+        let mut entries = vec![];
+
+        let intent = match intent {
+            Some(v) => v,
+            None => {
+                // resolved already, or never prepared.
+                let decision = if is_primary {
+                    match self.get_status_rec(decision_key(&txn.id), recs)? {
+                        Some(v) => Some(v),
+                        None => {
+                            entries.push(set_status_rec(
+                                recs,
+                                decision_key(&txn.id),
+                                Some(TXN_ABORT.to_vec()),
+                            ));
+                            Some(TXN_ABORT.to_vec())
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                let r = if !commit {
+                    ExecuteResult::SuccessWithVal { value: decision }
+                } else if decision == Some(TXN_ABORT.to_vec()) {
+                    ExecuteResult::Aborted
+                } else {
+                    ExecuteResult::Error("ERR transaction is already resolved".to_owned())
+                };
+                return Ok((entries, r));
+            }
+        };
+
+        if is_primary && self.get_status_rec(decision_key(&txn.id), recs)?.is_none() {
+            let d = if commit { TXN_COMMIT } else { TXN_ABORT };
+            entries.push(set_status_rec(
+                recs,
+                decision_key(&txn.id),
+                Some(d.to_vec()),
+            ));
+        }
+
+        if !commit {
+            entries.push(set_status_rec(recs, intent_key(&cmd.key), None));
+            let r = ExecuteResult::SuccessWithVal {
+                value: Some(TXN_ABORT.to_vec()),
+            };
+            return Ok((entries, r));
+        }
+
+        let seq = txn.seq as usize;
+        let r = match intent.cmds.get(seq) {
+            Some(c) => {
+                let (es, r) = self.eval_command(c, existed, recs)?;
+                entries.extend(es);
+                r
+            }
+            None => ExecuteResult::Error("ERR invalid transaction seq".to_owned()),
+        };
+
+        if seq + 1 >= intent.cmds.len() {
+            entries.push(set_status_rec(recs, intent_key(&cmd.key), None));
+        }
+
+        Ok((entries, r))
+    }
+
+    /// forget_txn evaluates a `TxnForget` command, which removes the decision of a transaction
+    /// once the intent of every key is resolved.
+    ///
+    /// The decision is kept, and `Aborted` is replied, if the primary key is still locked by the
+    /// transaction, or `key` is not the primary key.
+    fn forget_txn(
+        &self,
+        key: &[u8],
+        txn: &TxnInfo,
+        recs: &mut StatusRecords,
+    ) -> Result<(Vec<WriteEntry>, ExecuteResult), StorageError> {
+        if key != &txn.primary[..] {
+            return Ok((vec![], ExecuteResult::Aborted));
+        }
+
+        let locked = self
+            .get_intent(key, recs)?
+            .and_then(|x| x.txn)
+            .map(|x| x.id == txn.id)
+            .unwrap_or(false);
+        if locked {
+            return Ok((vec![], ExecuteResult::Aborted));
+        }
+
+        let e = set_status_rec(recs, decision_key(&txn.id), None);
+        Ok((vec![e], ExecuteResult::Success))
+    }
+
+    /// get_intent returns the intent on a key, if it is locked by a transaction.
+    pub fn get_intent(
+        &self,
+        key: &[u8],
+        recs: &mut StatusRecords,
+    ) -> Result<Option<TxnIntent>, StorageError> {
+        let v = self.get_status_rec(intent_key(key), recs)?;
+        match v {
+            Some(v) => Ok(Some(TxnIntent::decode(&v[..])?)),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::oneshot;
+
+use crate::qpaxos::InstanceId;
+use crate::replica::ExecuteResult;
+
+/// ExecWaiters is a registry of clients waiting for instances to be executed.
+///
+/// A client registers an instance id before the instance is committed, and waits on the returned
+/// `Receiver`. When the executor applies the instance, it sends the `ExecuteResult` of every
+/// command in the instance to the waiting client.
+#[derive(Default)]
+pub struct ExecWaiters {
+    waiters: Mutex<HashMap<InstanceId, oneshot::Sender<Vec<ExecuteResult>>>>,
+}
+
+impl ExecWaiters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// register adds a waiter for the instance.
+    /// A former waiter of the same instance is replaced and will receive an error.
+    pub fn register(&self, iid: InstanceId) -> oneshot::Receiver<Vec<ExecuteResult>> {
+        let (tx, rx) = oneshot::channel();
+        let mut ws = self.waiters.lock().unwrap();
+        ws.insert(iid, tx);
+        rx
+    }
+
+    /// cancel removes the waiter of the instance, e.g., when the instance fails to commit.
+    pub fn cancel(&self, iid: InstanceId) {
+        let mut ws = self.waiters.lock().unwrap();
+        ws.remove(&iid);
+    }
+
+    /// notify sends execution results to the waiter of the instance, if there is one.
+    /// It returns true if a waiter is found.
+    pub fn notify(&self, iid: InstanceId, rst: Vec<ExecuteResult>) -> bool {
+        let tx = {
+            let mut ws = self.waiters.lock().unwrap();
+            ws.remove(&iid)
+        };
+
+        match tx {
+            // the waiter may have gone, e.g., the client closed connection.
+            Some(tx) => tx.send(rst).is_ok(),
+            None => false,
+        }
+    }
+}
@@ -1,39 +1,133 @@
-use tonic::Response;
+use std::sync::Arc;
+use std::sync::Mutex;
 
-use crate::qpaxos::QPaxosClient;
+use tokio::sync::mpsc;
+
+use crate::qpaxos::ReadIndexReply;
+use crate::qpaxos::ReadIndexRequest;
 use crate::qpaxos::ReplicaId;
 use crate::qpaxos::ReplicateReply;
 use crate::qpaxos::ReplicateRequest;
 use crate::replica::ReplicaPeer;
+use crate::replication::PeerClients;
 
-pub async fn bcast_msg(
+/// bcast_msg sends a request to every peer concurrently.
+/// Replies are sent to the returned receiver in the order they arrive, thus a caller is able to
+/// stop waiting as soon as enough replies are received.
+/// A peer that fails or does not reply within the deadline is just ignored, so is a peer known to
+/// be dead.
+/// The receiver is closed when every peer replied or failed.
+pub fn bcast_msg(
+    clients: &Arc<PeerClients>,
     peers: &[ReplicaPeer],
     req: ReplicateRequest,
-) -> Vec<(ReplicaId, Response<ReplicateReply>)> {
-    let mut rst = Vec::with_capacity(peers.len());
+) -> mpsc::UnboundedReceiver<(ReplicaId, ReplicateReply)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
     for p in peers.iter() {
-        let mut client = match QPaxosClient::connect(p.addr.clone()).await {
-            Ok(c) => c,
-            // TODO just ignore the err
-            Err(e) => {
-                println!("{:?} while connect to {:?}", e, &p.addr);
-                continue;
-            }
-        };
+        let clients = clients.clone();
+        let tx = tx.clone();
+        let p = p.clone();
 
         let mut r = req.clone();
         r.to_replica_id = p.replica_id;
-        let repl = match client.replicate(r).await {
-            Ok(r) => r,
-            // TODO just ignore the err
-            Err(e) => {
-                println!("{:?} while request to {:?}", e, &p.addr);
-                continue;
+
+        tokio::spawn(async move {
+            let repl = match clients.replicate(&p.addr, r).await {
+                Ok(v) => v,
+                // TODO just ignore the err
+                Err(e) => {
+                    println!("{:?} while request to {:?}", e, &p.addr);
+                    return;
+                }
+            };
+
+            // the receiver may have been dropped because enough replies are received.
+            let _ = tx.send((p.replica_id, repl));
+        });
+    }
+
+    rx
+}
+
+/// bcast_thrifty sends a request to the `first` peers concurrently, like `bcast_msg` does.
+/// When a request fails or does not reply within the deadline, it sends the request to the next
+/// peer in `rest` at once, without waiting for other requests in `first`.
+/// The receiver is closed when every peer requested replied or failed, and no peer is left.
+pub fn bcast_thrifty(
+    clients: &Arc<PeerClients>,
+    first: &[ReplicaPeer],
+    rest: &[ReplicaPeer],
+    req: ReplicateRequest,
+) -> mpsc::UnboundedReceiver<(ReplicaId, ReplicateReply)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    // peers are taken from the end.
+    let rest: Vec<_> = rest.iter().rev().cloned().collect();
+    let rest = Arc::new(Mutex::new(rest));
+
+    for p in first.iter() {
+        let clients = clients.clone();
+        let tx = tx.clone();
+        let rest = rest.clone();
+        let mut p = p.clone();
+        let req = req.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let mut r = req.clone();
+                r.to_replica_id = p.replica_id;
+
+                match clients.replicate(&p.addr, r).await {
+                    Ok(repl) => {
+                        let _ = tx.send((p.replica_id, repl));
+                        return;
+                    }
+                    Err(e) => {
+                        println!("{:?} while request to {:?}", e, &p.addr);
+                    }
+                };
+
+                p = match rest.lock().unwrap().pop() {
+                    Some(v) => v,
+                    None => return,
+                };
             }
+        });
+    }
+
+    rx
+}
+
+/// bcast_read_index asks every peer for the instances it has seen, concurrently, like
+/// `bcast_msg` does.
+pub fn bcast_read_index(
+    clients: &Arc<PeerClients>,
+    peers: &[ReplicaPeer],
+) -> mpsc::UnboundedReceiver<(ReplicaId, ReadIndexReply)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    for p in peers.iter() {
+        let clients = clients.clone();
+        let tx = tx.clone();
+        let p = p.clone();
+
+        let r = ReadIndexRequest {
+            to_replica_id: p.replica_id,
         };
 
-        rst.push((p.replica_id, repl));
+        tokio::spawn(async move {
+            let repl = match clients.read_index(&p.addr, r).await {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("{:?} while read-index to {:?}", e, &p.addr);
+                    return;
+                }
+            };
+
+            let _ = tx.send((p.replica_id, repl));
+        });
     }
 
-    return rst;
+    rx
 }
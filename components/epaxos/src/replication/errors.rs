@@ -7,8 +7,10 @@ use crate::qpaxos::ReplicaId;
 use crate::qpaxos::StorageFailure;
 use crate::replica::InstanceStatus;
 use crate::replica::ReplicaError;
+use crate::RangeLookupError;
 use parse::Response;
 use storage::StorageError;
+use tokio::time::Elapsed;
 
 quick_error! {
     /// RpcHandlerError is an error encountered when handle-xx-request or handle-xx-reply.
@@ -96,9 +98,52 @@ quick_error! {
         RpcHandler(e: RpcHandlerError) {
             from(e: RpcHandlerError) -> (e)
         }
+        Replica(e: ReplicaError) {
+            from(e: ReplicaError) -> (e)
+        }
         Storage(e: StorageError) {
             from(e: StorageError) -> (e)
         }
+        RangeLookup(e: RangeLookupError) {
+            from(e: RangeLookupError) -> (e)
+        }
+        /// A request to a replica on another node failed.
+        Peer(e: PeerError) {
+            from(e: PeerError) -> (e)
+        }
+        /// The waiter of an instance is dropped before the instance is executed.
+        ExecAborted(iid: InstanceId) {
+            display("execution of {} aborted", iid)
+        }
+        /// Recovery of an instance waits for a conflicting instance, which is not committed in
+        /// time.
+        Deferred(iid: InstanceId) {
+            display("recovery deferred until {} is committed", iid)
+        }
+        /// A cross-group transaction is aborted, e.g., some key is locked by another one.
+        TxnAborted(txn_id: Vec<u8>) {
+            display("transaction {} aborted", String::from_utf8_lossy(txn_id))
+        }
+    }
+}
+
+quick_error! {
+    /// PeerError is an error encountered when sending a request to a peer.
+    #[derive(Debug)]
+    pub enum PeerError {
+        Connect(e: tonic::transport::Error) {
+            from(e: tonic::transport::Error) -> (e)
+        }
+        Rpc(s: tonic::Status) {
+            from(s: tonic::Status) -> (s)
+        }
+        Timeout(e: Elapsed) {
+            from(e: Elapsed) -> (e)
+        }
+        /// The peer is dead and is not yet due to reconnect.
+        Backoff(addr: String) {
+            display("peer {} is in backoff", addr)
+        }
     }
 }
 
@@ -130,3 +130,87 @@ pub fn handle_accept_reply(
 
     Ok(())
 }
+
+pub fn handle_prepare_reply(
+    st: &mut Status,
+    from_rid: ReplicaId,
+    repl: ReplicateReply,
+) -> Result<(), RpcHandlerError> {
+    // A duplicated message is received. Just ignore.
+    if st.prepare_replied.contains_key(&from_rid) {
+        return Err(RpcHandlerError::DupRpc(
+            InstanceStatus::Na,
+            Direction::Reply,
+            from_rid,
+            st.instance.instance_id.unwrap(),
+        ));
+    }
+    st.prepare_replied.insert(from_rid, true);
+
+    if let Some(ref e) = repl.err {
+        return Err(RpcHandlerError::RemoteError(e.clone()));
+    }
+
+    let (last_ballot, _iid) = check_repl_common(&repl)?;
+    let inst = &st.instance;
+
+    if inst.ballot < Some(last_ballot) {
+        return Err(RpcHandlerError::StaleBallot(
+            inst.ballot.or(Some((0, 0, 0).into())).unwrap(),
+            last_ballot,
+        ));
+    }
+
+    let phase = repl.phase.ok_or(ProtocolError::LackOf("phase".into()))?;
+    let prepl: PrepareReply = phase
+        .try_into()
+        .or(Err(ProtocolError::LackOf("phase::Prepare".into())))?;
+
+    st.prepare_oks.insert(from_rid, (last_ballot, prepl));
+
+    Ok(())
+}
+
+pub fn handle_try_pre_accept_reply(
+    st: &mut Status,
+    from_rid: ReplicaId,
+    repl: ReplicateReply,
+) -> Result<(), RpcHandlerError> {
+    // A duplicated message is received. Just ignore.
+    if st.try_replied.contains_key(&from_rid) {
+        return Err(RpcHandlerError::DupRpc(
+            InstanceStatus::FastAccepted,
+            Direction::Reply,
+            from_rid,
+            st.instance.instance_id.unwrap(),
+        ));
+    }
+    st.try_replied.insert(from_rid, true);
+
+    if let Some(ref e) = repl.err {
+        return Err(RpcHandlerError::RemoteError(e.clone()));
+    }
+
+    let (last_ballot, _iid) = check_repl_common(&repl)?;
+    let inst = &st.instance;
+
+    if inst.ballot < Some(last_ballot) {
+        return Err(RpcHandlerError::StaleBallot(
+            inst.ballot.or(Some((0, 0, 0).into())).unwrap(),
+            last_ballot,
+        ));
+    }
+
+    let phase = repl.phase.ok_or(ProtocolError::LackOf("phase".into()))?;
+    let trepl: TryPreAcceptReply = phase
+        .try_into()
+        .or(Err(ProtocolError::LackOf("phase::TryPreAccept".into())))?;
+
+    if trepl.ok {
+        st.try_oks.insert(from_rid, true);
+    } else {
+        st.try_conflicts.insert(from_rid, trepl);
+    }
+
+    Ok(())
+}
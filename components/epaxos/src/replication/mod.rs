@@ -10,8 +10,23 @@ pub use replication::*;
 mod broadcast;
 pub use broadcast::*;
 
+mod peers;
+pub use peers::*;
+
+mod recovery;
+pub use recovery::*;
+
 #[cfg(test)]
 mod test_hdlreply;
 
 #[cfg(test)]
 mod test_broadcast;
+
+#[cfg(test)]
+mod test_replication;
+
+#[cfg(test)]
+mod test_recovery;
+
+#[cfg(test)]
+mod test_peers;
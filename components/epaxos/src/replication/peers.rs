@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::time::timeout;
+use tokio::time::Elapsed;
+use tonic::transport::Channel;
+
+use crate::qpaxos::ProposeReply;
+use crate::qpaxos::ProposeRequest;
+use crate::qpaxos::QPaxosClient;
+use crate::qpaxos::ReadIndexReply;
+use crate::qpaxos::ReadIndexRequest;
+use crate::qpaxos::ReplicateReply;
+use crate::qpaxos::ReplicateRequest;
+use crate::replica::ReplicaPeer;
+use crate::replication::PeerError;
+
+/// default deadline for connecting to a peer and receiving its reply.
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_millis(1_000);
+
+/// default deadline for a peer to propose commands and reply the results, which takes several
+/// round trips in the group of the peer.
+pub const DEFAULT_PROPOSE_TIMEOUT: Duration = Duration::from_millis(5_000);
+
+/// default delay before reconnecting a peer the first time it fails.
+pub const DEFAULT_BACKOFF_BASE: Duration = Duration::from_millis(100);
+
+/// default max delay before reconnecting a failed peer.
+pub const DEFAULT_BACKOFF_MAX: Duration = Duration::from_millis(5_000);
+
+/// PeerConn is the connection state of a peer.
+#[derive(Default)]
+struct PeerConn {
+    client: Option<QPaxosClient<Channel>>,
+
+    /// number of consecutive failures. A peer with `fails > 0` is considered dead.
+    fails: u32,
+
+    /// a dead peer is not reconnected until `retry_at`.
+    retry_at: Option<Instant>,
+}
+
+/// PeerClients keeps a long-lived grpc client to every peer, keyed by peer address.
+/// It is shared by all replicas on a node.
+///
+/// A peer is marked dead when a request to it fails, and it is not reconnected until a backoff
+/// delay passes, which doubles on every consecutive failure.
+/// A successful request or heartbeat marks it alive again.
+pub struct PeerClients {
+    /// deadline of every request, including connecting to a peer.
+    pub timeout: Duration,
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    conns: Mutex<HashMap<String, PeerConn>>,
+}
+
+impl Default for PeerClients {
+    fn default() -> Self {
+        Self::new(DEFAULT_RPC_TIMEOUT)
+    }
+}
+
+impl PeerClients {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            backoff_base: DEFAULT_BACKOFF_BASE,
+            backoff_max: DEFAULT_BACKOFF_MAX,
+            conns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// is_alive returns false if the last request to a peer failed.
+    /// A peer never contacted is considered alive.
+    pub fn is_alive(&self, addr: &str) -> bool {
+        let conns = self.conns.lock().unwrap();
+        conns.get(addr).map(|c| c.fails == 0).unwrap_or(true)
+    }
+
+    /// sort_by_liveness returns peers with `alive` updated. Alive peers are placed before dead
+    /// ones, so that a caller is able to send to a quorum of alive peers first.
+    pub fn sort_by_liveness(&self, peers: &[ReplicaPeer]) -> Vec<ReplicaPeer> {
+        let mut peers: Vec<_> = peers
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                p.alive = self.is_alive(&p.addr);
+                p
+            })
+            .collect();
+
+        // stable sort keeps the order among alive peers.
+        peers.sort_by_key(|p| !p.alive);
+        peers
+    }
+
+    /// backoff returns the delay before reconnecting a peer that failed `fails` times in a row.
+    pub fn backoff(&self, fails: u32) -> Duration {
+        let mut d = self.backoff_base;
+        for _ in 1..fails {
+            d *= 2;
+            if d >= self.backoff_max {
+                return self.backoff_max;
+            }
+        }
+        d
+    }
+
+    /// replicate sends a request to a peer and waits for the reply, within `self.timeout`.
+    /// It returns `PeerError::Backoff` at once if the peer is dead and is not yet due to retry.
+    pub async fn replicate(
+        &self,
+        addr: &str,
+        req: ReplicateRequest,
+    ) -> Result<ReplicateReply, PeerError> {
+        let rst = timeout(self.timeout, self._replicate(addr, req)).await;
+        self.track(addr, rst)
+    }
+
+    /// read_index asks a peer for the instances it has seen, within `self.timeout`.
+    pub async fn read_index(
+        &self,
+        addr: &str,
+        req: ReadIndexRequest,
+    ) -> Result<ReadIndexReply, PeerError> {
+        let rst = timeout(self.timeout, self._read_index(addr, req)).await;
+        self.track(addr, rst)
+    }
+
+    /// propose asks a peer to propose commands in its group, within `DEFAULT_PROPOSE_TIMEOUT`.
+    /// A failure does not mark the peer dead, since the proposal may fail on a live peer, e.g.,
+    /// without a quorum in its group.
+    pub async fn propose(
+        &self,
+        addr: &str,
+        req: ProposeRequest,
+    ) -> Result<ProposeReply, PeerError> {
+        let rst = timeout(DEFAULT_PROPOSE_TIMEOUT, self._propose(addr, req)).await;
+        match rst {
+            Ok(v) => v,
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// track updates the liveness of a peer by the result of a request to it.
+    fn track<T>(
+        &self,
+        addr: &str,
+        rst: Result<Result<T, PeerError>, Elapsed>,
+    ) -> Result<T, PeerError> {
+        let rst = match rst {
+            Ok(v) => v,
+            Err(e) => Err(e.into()),
+        };
+
+        match rst {
+            Ok(_) => self.mark_alive(addr),
+            Err(PeerError::Backoff(_)) => {}
+            Err(_) => self.mark_dead(addr),
+        }
+
+        rst
+    }
+
+    /// heartbeat reconnects every dead peer that is due to retry, and updates its liveness.
+    pub async fn heartbeat(&self) {
+        let now = Instant::now();
+        let addrs: Vec<String> = {
+            let conns = self.conns.lock().unwrap();
+            conns
+                .iter()
+                .filter(|(_, c)| c.fails > 0 && c.retry_at.map(|t| t <= now).unwrap_or(true))
+                .map(|(addr, _)| addr.clone())
+                .collect()
+        };
+
+        for addr in addrs.iter() {
+            let rst = timeout(self.timeout, QPaxosClient::connect(addr.clone())).await;
+            match rst {
+                Ok(Ok(c)) => {
+                    self.mark_alive(addr);
+                    let mut conns = self.conns.lock().unwrap();
+                    conns.entry(addr.clone()).or_default().client = Some(c);
+                }
+                Ok(Err(e)) => {
+                    println!("{:?} while heartbeat to {:?}", e, addr);
+                    self.mark_dead(addr);
+                }
+                Err(e) => {
+                    println!("{:?} while heartbeat to {:?}", e, addr);
+                    self.mark_dead(addr);
+                }
+            }
+        }
+    }
+
+    fn mark_alive(&self, addr: &str) {
+        let mut conns = self.conns.lock().unwrap();
+        let c = conns.entry(addr.to_string()).or_default();
+        c.fails = 0;
+        c.retry_at = None;
+    }
+
+    fn mark_dead(&self, addr: &str) {
+        let mut conns = self.conns.lock().unwrap();
+        let c = conns.entry(addr.to_string()).or_default();
+
+        // the channel may be broken, reconnect next time.
+        c.client = None;
+        c.fails += 1;
+        c.retry_at = Some(Instant::now() + self.backoff(c.fails));
+    }
+
+    async fn _replicate(
+        &self,
+        addr: &str,
+        req: ReplicateRequest,
+    ) -> Result<ReplicateReply, PeerError> {
+        let mut client = self.get_client(addr).await?;
+        let repl = client.replicate(req).await?;
+        Ok(repl.into_inner())
+    }
+
+    async fn _read_index(
+        &self,
+        addr: &str,
+        req: ReadIndexRequest,
+    ) -> Result<ReadIndexReply, PeerError> {
+        let mut client = self.get_client(addr).await?;
+        let repl = client.read_index(req).await?;
+        Ok(repl.into_inner())
+    }
+
+    async fn _propose(&self, addr: &str, req: ProposeRequest) -> Result<ProposeReply, PeerError> {
+        let mut client = self.get_client(addr).await?;
+        let repl = client.propose(req).await?;
+        Ok(repl.into_inner())
+    }
+
+    async fn get_client(&self, addr: &str) -> Result<QPaxosClient<Channel>, PeerError> {
+        {
+            let conns = self.conns.lock().unwrap();
+            if let Some(c) = conns.get(addr) {
+                if let Some(client) = &c.client {
+                    return Ok(client.clone());
+                }
+
+                if let Some(t) = c.retry_at {
+                    if Instant::now() < t {
+                        return Err(PeerError::Backoff(addr.to_string()));
+                    }
+                }
+            }
+        }
+
+        let c = QPaxosClient::connect(addr.to_string()).await?;
+
+        let mut conns = self.conns.lock().unwrap();
+        conns.entry(addr.to_string()).or_default().client = Some(c.clone());
+
+        Ok(c)
+    }
+}
@@ -0,0 +1,447 @@
+use std::time::Duration;
+use std::time::Instant;
+
+use storage::StorageError;
+use tokio::time::delay_for;
+
+use crate::qpaxos::BallotNum;
+use crate::qpaxos::Command;
+use crate::qpaxos::ConflictStatus;
+use crate::qpaxos::Instance;
+use crate::qpaxos::InstanceId;
+use crate::qpaxos::InstanceIdVec;
+use crate::qpaxos::MakeRequest;
+use crate::qpaxos::OpCode;
+use crate::qpaxos::PrepareReply;
+use crate::replica::InstanceStatus;
+use crate::replica::Replica;
+use crate::replica::Status;
+use crate::replication::bcast_msg;
+use crate::replication::commit;
+use crate::replication::handle_accept_reply;
+use crate::replication::handle_prepare_reply;
+use crate::replication::handle_try_pre_accept_reply;
+use crate::replication::RpcHandlerError;
+use crate::ReplicationError;
+
+/// next_ballot returns a ballot greater than `blt`, owned by replica `rid`.
+pub fn next_ballot(blt: Option<BallotNum>, rid: i64) -> BallotNum {
+    let blt = blt.unwrap_or((0, 0, 0).into());
+    (blt.epoch, blt.num + 1, rid).into()
+}
+
+/// max number of rounds `recover` runs, each with a ballot greater than any seen before.
+const MAX_RECOVER_ROUNDS: usize = 3;
+
+/// max time recovery waits for a conflicting instance to be committed, before TryPreAccept again.
+const DEFER_TIMEOUT: Duration = Duration::from_millis(1_000);
+
+/// interval to check whether a conflicting instance is committed.
+const DEFER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// TryPreAccepted is the outcome of TryPreAccept during recovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TryPreAccepted {
+    /// a fast quorum of replicas have the deps.
+    Ok,
+    /// the deps could not have been committed on fast-path.
+    Abort,
+    /// the conflicting instance has to be committed before the deps could be decided.
+    Defer(InstanceId),
+}
+
+/// recover runs explicit prepare for an instance whose leader did not finish replicating it,
+/// e.g., the leader crashed. It commits the instance with the value chosen by the prepare phase:
+///
+/// - If any replica has committed it, commit it with the same cmds and final_deps.
+/// - If any replica has accepted it, re-run Accept with the final_deps accepted with the highest
+///   ballot, then commit.
+/// - If the replicas except the leader that have fast-accepted it with the same deps, along with
+///   the leader, could form a fast quorum, it might have been committed on fast-path. Run
+///   TryPreAccept to let other replicas accept the same deps. If a fast quorum is reached, run
+///   Accept with these deps, then commit. If a replica rejects them for a conflicting instance:
+///   - the conflicting instance is not committed, and is not from the leader: it might be
+///     committed after this one on fast-path. Wait for it to be committed and TryPreAccept again.
+///   - the conflicting instance is committed, or is from the leader: this instance could not have
+///     been committed on fast-path with these deps. Fall through to the next case.
+/// - If any replica has fast-accepted it, run Accept with the union of replied deps, then commit.
+/// - Otherwise no replica in the quorum has seen it, commit a NoOp.
+///
+/// If a round fails because some replica has seen a higher ballot, e.g., another replica is
+/// recovering it too, it starts another round with a ballot greater than that one.
+///
+/// On success it returns the committed instance.
+pub async fn recover(iid: InstanceId, r: &Replica) -> Result<Instance, ReplicationError> {
+    let mut seen: Option<BallotNum> = None;
+    let mut last_err = None;
+
+    for _ in 0..MAX_RECOVER_ROUNDS {
+        let mut inst = r.get_instance(iid)?;
+        if inst.committed {
+            // some replica may still miss the commit.
+            commit(&mut inst, r)?;
+            return Ok(inst);
+        }
+
+        inst.ballot = Some(next_ballot(std::cmp::max(inst.ballot, seen), r.replica_id));
+
+        match recover_with_ballot(iid, inst, r).await {
+            Err(ReplicationError::RpcHandler(RpcHandlerError::StaleBallot(stale, last))) => {
+                seen = std::cmp::max(seen, Some(last));
+                last_err = Some(RpcHandlerError::StaleBallot(stale, last).into());
+            }
+            rst => return rst,
+        }
+    }
+
+    Err(last_err.unwrap())
+}
+
+/// recover_with_ballot runs one round of recovery, with the ballot in `inst`.
+/// If not enough replicas accept the ballot and some of them has seen a higher one, it returns a
+/// `StaleBallot` error with the highest ballot seen.
+async fn recover_with_ballot(
+    iid: InstanceId,
+    inst: Instance,
+    r: &Replica,
+) -> Result<Instance, ReplicationError> {
+    let n = r.group_replica_ids.len() as i32;
+    let mut st = Status::new_recovery(n, inst);
+    let mut higher = None;
+
+    // prepare locally first, then other replicas.
+    let req = MakeRequest::prepare(r.replica_id, &st.instance);
+    let repl = r.handle_replicate(req)?;
+    handle_prepare_reply(&mut st, r.replica_id, repl)?;
+
+    let req = MakeRequest::prepare(0, &st.instance);
+    let mut repls = bcast_msg(&r.peer_clients, &r.peers, req);
+
+    while let Some((from_rid, repl)) = repls.recv().await {
+        let rst = handle_prepare_reply(&mut st, from_rid, repl);
+        if let Err(e) = rst {
+            println!("{:?} while handle prepare reply from {:?}", e, from_rid);
+            see_ballot(e, &mut higher);
+        }
+        if st.prepare_oks.len() as i32 >= st.quorum {
+            break;
+        }
+    }
+
+    if (st.prepare_oks.len() as i32) < st.quorum {
+        return Err(stale_or(
+            higher,
+            ReplicationError::NotEnoughQuorum(
+                // prepare phase
+                InstanceStatus::Na,
+                st.quorum,
+                st.prepare_oks.len() as i32,
+            ),
+        ));
+    }
+
+    // committed by some replica: just commit it.
+    for (_, (_, prepl)) in st.prepare_oks.iter() {
+        if prepl.committed {
+            st.instance.cmds = prepl.cmds.clone();
+            st.instance.final_deps = prepl.final_deps.clone();
+            commit(&mut st.instance, r)?;
+            return Ok(st.instance);
+        }
+    }
+
+    // accepted by some replica: it might have been committed on slow-path with the value accepted
+    // with the highest ballot, which `choose_accept_value` chooses. Deps fast-accepted by other
+    // replicas must not be tried then.
+    let accepted = st
+        .prepare_oks
+        .values()
+        .any(|(_, prepl)| prepl.final_deps.is_some());
+
+    // fast-accepted by a minority with the same deps: it might have been committed on fast-path.
+    let fast = if accepted {
+        None
+    } else {
+        choose_fast_value(iid, &st)
+    };
+
+    let mut chosen = None;
+
+    if let Some((cmds, deps)) = fast {
+        st.instance.cmds = cmds.clone();
+        st.instance.deps = Some(deps.clone());
+
+        let mut deferred = 0;
+        loop {
+            match try_pre_accept(&mut st, r).await? {
+                TryPreAccepted::Ok => {
+                    chosen = Some((cmds, deps));
+                    break;
+                }
+                TryPreAccepted::Abort => break,
+                TryPreAccepted::Defer(conflict) => {
+                    deferred += 1;
+                    if deferred > MAX_RECOVER_ROUNDS || !wait_committed(r, conflict).await? {
+                        return Err(ReplicationError::Deferred(conflict));
+                    }
+                    st.try_replied.clear();
+                    st.try_oks.clear();
+                    st.try_conflicts.clear();
+                }
+            }
+        }
+    }
+
+    let (cmds, final_deps) = match chosen {
+        Some(v) => v,
+        None => {
+            let (cmds, mut final_deps) = choose_accept_value(iid, &st, r);
+
+            // a conflicting instance found by TryPreAccept might not be seen by the prepare
+            // quorum, but it has to be ordered with this one.
+            for trepl in st.try_conflicts.values() {
+                if let Some(c) = trepl.conflict_instance {
+                    if c != iid && final_deps.get(c.replica_id) < Some(c) {
+                        final_deps.set(c);
+                    }
+                }
+            }
+            (cmds, final_deps)
+        }
+    };
+
+    st.instance.cmds = cmds;
+    st.instance.final_deps = Some(final_deps);
+    st.instance.accepted_ballot = st.instance.ballot;
+    if st.instance.deps.is_none() {
+        st.instance.deps = st.instance.final_deps.clone();
+    }
+
+    // slow path
+
+    r.storage.set_instance(&st.instance)?;
+    st.accept_replied.insert(r.replica_id, true);
+    st.accept_oks.insert(r.replica_id, true);
+
+    let req = MakeRequest::accept(0, &st.instance);
+    let mut repls = bcast_msg(&r.peer_clients, &r.peers, req);
+
+    while let Some((from_rid, repl)) = repls.recv().await {
+        let rst = handle_accept_reply(&mut st, from_rid, &repl);
+        if let Err(e) = rst {
+            println!("{:?} while handle accept reply from {:?}", e, from_rid);
+            see_ballot(e, &mut higher);
+        }
+        if st.accept_oks.len() as i32 >= st.quorum {
+            break;
+        }
+    }
+
+    if (st.accept_oks.len() as i32) < st.quorum {
+        return Err(stale_or(
+            higher,
+            ReplicationError::NotEnoughQuorum(
+                InstanceStatus::Accepted,
+                st.quorum,
+                st.accept_oks.len() as i32,
+            ),
+        ));
+    }
+
+    commit(&mut st.instance, r)?;
+    Ok(st.instance)
+}
+
+/// choose_fast_value returns the cmds and deps that the most replicas has fast-accepted with, if
+/// the instance might have been committed on fast-path with them.
+/// Only replicas other than the leader that have fast-accepted it at the original ballot count,
+/// i.e., those have not accepted any value.
+fn choose_fast_value(iid: InstanceId, st: &Status) -> Option<(Vec<Command>, InstanceIdVec)> {
+    // the leader has not committed it, and would never since the ballot has changed.
+    if st.prepare_oks.contains_key(&iid.replica_id) {
+        return None;
+    }
+
+    let mut candidates: Vec<(&Vec<Command>, &InstanceIdVec, i32)> = vec![];
+    for (rid, (_, prepl)) in st.prepare_oks.iter() {
+        if *rid == iid.replica_id || prepl.final_deps.is_some() || prepl.accepted_ballot.is_some() {
+            continue;
+        }
+
+        let pdeps = match prepl.deps {
+            Some(ref v) => v,
+            None => continue,
+        };
+
+        match candidates.iter_mut().find(|x| x.1 == pdeps) {
+            Some(c) => c.2 += 1,
+            None => candidates.push((&prepl.cmds, pdeps, 1)),
+        }
+    }
+
+    let best = candidates.iter().max_by_key(|x| x.2)?;
+
+    // along with the leader
+    if best.2 + 1 < st.fast_quorum {
+        return None;
+    }
+
+    Some((best.0.clone(), best.1.clone()))
+}
+
+/// try_pre_accept sends TryPreAccept with `st.instance.deps` to replicas that have not yet
+/// fast-accepted these deps.
+/// It returns `Ok` if at least a fast quorum of replicas have these deps. Otherwise it decides by
+/// the conflicting instances replied, as `recover` describes.
+async fn try_pre_accept(st: &mut Status, r: &Replica) -> Result<TryPreAccepted, ReplicationError> {
+    let leader = st.instance.instance_id.unwrap().replica_id;
+
+    // a replica that has accepted or committed it would reject these deps.
+    for (rid, (_, prepl)) in st.prepare_oks.iter() {
+        if prepl.deps == st.instance.deps && prepl.final_deps.is_none() && !prepl.committed {
+            st.try_replied.insert(*rid, true);
+            st.try_oks.insert(*rid, true);
+        }
+    }
+
+    if !st.try_replied.contains_key(&r.replica_id) {
+        let req = MakeRequest::try_pre_accept(r.replica_id, &st.instance);
+        let repl = r.handle_replicate(req)?;
+        handle_try_pre_accept_reply(st, r.replica_id, repl)?;
+    }
+
+    let peers: Vec<_> = r
+        .peers
+        .iter()
+        .filter(|p| !st.try_replied.contains_key(&p.replica_id))
+        .cloned()
+        .collect();
+
+    let req = MakeRequest::try_pre_accept(0, &st.instance);
+    let mut repls = bcast_msg(&r.peer_clients, &peers, req);
+
+    let mut higher = None;
+    while let Some((from_rid, repl)) = repls.recv().await {
+        if let Err(e) = handle_try_pre_accept_reply(st, from_rid, repl) {
+            see_ballot(e, &mut higher);
+        }
+        if st.try_oks.len() as i32 >= st.fast_quorum {
+            return Ok(TryPreAccepted::Ok);
+        }
+    }
+
+    if st.try_oks.len() as i32 >= st.fast_quorum {
+        return Ok(TryPreAccepted::Ok);
+    }
+
+    if let Some((stale, last)) = higher {
+        return Err(RpcHandlerError::StaleBallot(stale, last).into());
+    }
+
+    let mut defer = None;
+    for trepl in st.try_conflicts.values() {
+        let committed = trepl.conflict_status >= ConflictStatus::Committed as i32;
+        match trepl.conflict_instance {
+            Some(c)
+                if !committed && c.replica_id != leader && Some(c) != st.instance.instance_id =>
+            {
+                defer = Some(c);
+            }
+            _ => return Ok(TryPreAccepted::Abort),
+        }
+    }
+
+    match defer {
+        Some(c) => Ok(TryPreAccepted::Defer(c)),
+        // not enough replies, but none rejects the deps either.
+        None => Ok(TryPreAccepted::Abort),
+    }
+}
+
+/// wait_committed waits for the instance `iid` to be committed on the local replica, and asks
+/// for it to be recovered meanwhile, in case its leader has crashed too.
+/// It returns false if it is not committed within `DEFER_TIMEOUT`.
+async fn wait_committed(r: &Replica, iid: InstanceId) -> Result<bool, StorageError> {
+    r.to_recover.lock().unwrap().insert(iid);
+
+    let deadline = Instant::now() + DEFER_TIMEOUT;
+    while Instant::now() < deadline {
+        let inst = r.storage.get_instance(iid)?;
+        if inst.map(|x| x.committed) == Some(true) {
+            return Ok(true);
+        }
+        delay_for(DEFER_POLL_INTERVAL).await;
+    }
+
+    Ok(false)
+}
+
+/// choose_accept_value chooses cmds and final_deps to run Accept with, from prepare-replies that
+/// none is committed.
+pub fn choose_accept_value(
+    iid: InstanceId,
+    st: &Status,
+    r: &Replica,
+) -> (Vec<Command>, InstanceIdVec) {
+    // accepted by some replica: choose the one accepted with the highest ballot.
+    // The ballot a replica has promised is not the one it accepted with: a replica may have
+    // promised a higher ballot to a recovery that did not reach the Accept phase.
+    let mut accepted: Option<&PrepareReply> = None;
+    for (_, (_, prepl)) in st.prepare_oks.iter() {
+        if prepl.final_deps.is_none() {
+            continue;
+        }
+
+        if accepted.is_none() || accepted.unwrap().accepted_ballot < prepl.accepted_ballot {
+            accepted = Some(prepl);
+        }
+    }
+
+    if let Some(prepl) = accepted {
+        return (prepl.cmds.clone(), prepl.final_deps.clone().unwrap());
+    }
+
+    // fast-accepted by some replica: choose the union of deps.
+    let mut cmds = None;
+    let mut deps = InstanceIdVec::from([0; 0]);
+    for (_, (_, prepl)) in st.prepare_oks.iter() {
+        let pdeps = match prepl.deps {
+            Some(ref v) => v,
+            None => continue,
+        };
+
+        cmds = Some(prepl.cmds.clone());
+        for d in pdeps.iter() {
+            if deps.get(d.replica_id) < Some(*d) {
+                deps.set(*d);
+            }
+        }
+    }
+
+    if let Some(cmds) = cmds {
+        return (cmds, deps);
+    }
+
+    // no replica has seen it: commit a NoOp, which does not need to be after itself.
+    let mut deps = r.get_max_instance_ids(&r.group_replica_ids);
+    deps.set((iid.replica_id, iid.idx - 1).into());
+
+    (vec![Command::of(OpCode::NoOp, &[], &[])], deps)
+}
+
+/// see_ballot keeps the highest ballot that a replica rejects a request with, in `higher`.
+fn see_ballot(e: RpcHandlerError, higher: &mut Option<(BallotNum, BallotNum)>) {
+    if let RpcHandlerError::StaleBallot(stale, last) = e {
+        if higher.map(|x| x.1 < last) != Some(false) {
+            *higher = Some((stale, last));
+        }
+    }
+}
+
+/// stale_or returns a `StaleBallot` error with the highest ballot seen, or `e` if none is seen.
+fn stale_or(higher: Option<(BallotNum, BallotNum)>, e: ReplicationError) -> ReplicationError {
+    match higher {
+        Some((stale, last)) => RpcHandlerError::StaleBallot(stale, last).into(),
+        None => e,
+    }
+}
@@ -1,10 +1,13 @@
 use crate::conf::GroupInfo;
 use crate::qpaxos::Command;
+use crate::qpaxos::Instance;
 use crate::qpaxos::MakeRequest;
+use crate::replica::ExecuteResult;
 use crate::replica::InstanceStatus;
 use crate::replica::Replica;
 use crate::replica::Status;
 use crate::replication::bcast_msg;
+use crate::replication::bcast_thrifty;
 use crate::replication::handle_accept_reply;
 use crate::replication::handle_fast_accept_reply;
 use crate::ReplicationError;
@@ -20,13 +23,11 @@ pub async fn replicate(
     r: &Replica,
 ) -> Result<Status, ReplicationError> {
     let grids: Vec<_> = g.replicas.keys().cloned().collect();
-    println!("grids:{:?}", grids);
 
     let inst = r.new_instance(cmds)?;
 
     let n = grids.len();
     let mut st = Status::new(n as i32, inst);
-    println!("st:{:?}", st);
 
     // a special path for n = 1
     let fast = st.get_fast_commit_deps(&grids);
@@ -48,12 +49,16 @@ pub async fn replicate(
     }
 
     let req = MakeRequest::fast_accept(0, &st.instance, &deps_committed);
-    let repls = bcast_msg(&r.peers, req).await;
 
-    println!("fast-replies:{:?}", repls);
+    // send to a fast quorum of alive peers first, and to another peer once one of them fails.
+    let peers = r.peer_clients.sort_by_liveness(&r.peers);
+    let n = std::cmp::min(peers.len(), (st.fast_quorum - 1) as usize);
+    let (first, rest) = peers.split_at(n);
 
-    for (from_rid, repl) in repls.iter() {
-        handle_fast_accept_reply(&mut st, *from_rid, repl.get_ref().clone())?;
+    let mut repls = bcast_thrifty(&r.peer_clients, first, rest, req);
+
+    while let Some((from_rid, repl)) = repls.recv().await {
+        handle_fast_accept_reply(&mut st, from_rid, repl)?;
         let fast = st.get_fast_commit_deps(&grids);
         match fast {
             Some(fdeps) => {
@@ -68,8 +73,6 @@ pub async fn replicate(
     }
 
     let adeps = st.get_accept_deps(&grids);
-    println!("st.fast_deps: {:?}", st.fast_deps);
-    println!("got accept deps:{:?}", adeps);
 
     let adeps = adeps.ok_or(ReplicationError::NotEnoughQuorum(
         InstanceStatus::FastAccepted,
@@ -80,25 +83,64 @@ pub async fn replicate(
     // slow path
 
     st.instance.final_deps = Some(adeps.into());
+    st.instance.accepted_ballot = st.instance.ballot;
     st.start_accept();
     r.storage.set_instance(&st.instance)?;
 
     let req = MakeRequest::accept(0, &st.instance);
-    let repls = bcast_msg(&r.peers, req).await;
+    let mut repls = bcast_msg(&r.peer_clients, &r.peers, req);
 
-    for (from_rid, repl) in repls.iter() {
-        handle_accept_reply(&mut st, *from_rid, repl.get_ref())?;
+    while let Some((from_rid, repl)) = repls.recv().await {
+        handle_accept_reply(&mut st, from_rid, &repl)?;
         if st.accept_oks.len() as i32 >= st.quorum {
             // instance is safe to commit.
             return Ok(st);
         }
     }
 
-    println!("{:?}", st.fast_deps);
-
     Err(ReplicationError::NotEnoughQuorum(
         InstanceStatus::Accepted,
         st.quorum,
         st.accept_oks.len() as i32,
     ))
 }
+
+/// commit marks an instance as committed in leader's local storage and broadcasts a Commit
+/// request to other replicas in the group.
+///
+/// Broadcasting is asynchronous: it returns once the local commit is done, without waiting for
+/// replies. A replica that misses the Commit recovers the instance by itself.
+pub fn commit(inst: &mut Instance, r: &Replica) -> Result<(), ReplicationError> {
+    inst.committed = true;
+    r.storage.set_instance(inst)?;
+
+    let req = MakeRequest::commit(0, inst);
+
+    // replies are not needed.
+    let _ = bcast_msg(&r.peer_clients, &r.peers, req);
+
+    Ok(())
+}
+
+/// propose replicates `cmds` in one instance with the local replica `r`, commits it and waits
+/// for it to be executed.
+/// It returns the `ExecuteResult` of every command.
+pub async fn propose(
+    cmds: &[Command],
+    g: &GroupInfo,
+    r: &Replica,
+) -> Result<Vec<ExecuteResult>, ReplicationError> {
+    let mut st = replicate(cmds, g, r).await?;
+    let inst = &mut st.instance;
+    let iid = inst.instance_id.unwrap();
+
+    // register before committing, or the executor might apply it before anyone waits.
+    let rx = r.waiters.register(iid);
+
+    if let Err(e) = commit(inst, r) {
+        r.waiters.cancel(iid);
+        return Err(e);
+    }
+
+    rx.await.or(Err(ReplicationError::ExecAborted(iid)))
+}
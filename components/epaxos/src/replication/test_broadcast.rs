@@ -1,25 +1,129 @@
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
 use crate::qpaxos::Command;
 use crate::qpaxos::Instance;
 use crate::qpaxos::InstanceId;
 use crate::qpaxos::MakeRequest;
+use crate::replica::ReplicaPeer;
 use crate::replication::bcast_msg;
+use crate::replication::bcast_thrifty;
+use crate::replication::PeerClients;
 use crate::testutil::TestCluster;
 
 #[tokio::main]
 async fn _bcast() {
-    let mut tc = TestCluster::new(3);
+    let mut tc = TestCluster::new(3, 5550);
     tc.start().await;
     let inst = foo_inst!((0, 1), "key_x", [(0, 0), (1, 0), (2, 0)]);
     let req = MakeRequest::fast_accept(0, &inst, &[true, true, true]);
 
-    let r = bcast_msg(&tc.replicas[0].peers, req).await;
+    let r0 = tc.replica(0);
+
+    let mut rx = bcast_msg(&r0.peer_clients, &r0.peers, req.clone());
+    let mut r = vec![];
+    while let Some(repl) = rx.recv().await {
+        r.push(repl);
+    }
 
     println!("receive fast accept replys: {:?}", r);
     // not contain self
     assert_eq!(2, r.len());
+
+    let mut rids: Vec<_> = r.iter().map(|x| x.0).collect();
+    rids.sort();
+    assert_eq!(vec![1, 2], rids);
+
+    // channels are reused.
+    let mut rx = bcast_msg(&r0.peer_clients, &r0.peers, req);
+    assert!(rx.recv().await.is_some());
+    assert!(rx.recv().await.is_some());
+    assert!(rx.recv().await.is_none());
 }
 
 #[test]
 fn test_bcast_replicate_request() {
     _bcast();
 }
+
+#[tokio::main]
+async fn _bcast_deadline() {
+    let mut tc = TestCluster::new(3, 5600);
+    tc.start().await;
+
+    // a peer accepts connections but never replies.
+    let hang = TcpListener::bind("127.0.0.1:5610").unwrap();
+
+    let inst = foo_inst!((0, 1), "key_x", [(0, 0), (1, 0), (2, 0)]);
+    let req = MakeRequest::fast_accept(0, &inst, &[true, true, true]);
+
+    let mut peers = tc.replica(0).peers.clone();
+    peers.push(ReplicaPeer::new(3, "http://127.0.0.1:5610".into(), true));
+
+    let clients = Arc::new(PeerClients::new(Duration::from_millis(200)));
+
+    let start = Instant::now();
+    let mut rx = bcast_msg(&clients, &peers, req);
+
+    // replies from live peers do not wait for the hanging peer.
+    assert!(rx.recv().await.is_some());
+    assert!(rx.recv().await.is_some());
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    // the hanging peer is given up after the deadline.
+    assert!(rx.recv().await.is_none());
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(start.elapsed() < Duration::from_millis(1_000));
+
+    drop(hang);
+}
+
+#[test]
+fn test_bcast_deadline() {
+    _bcast_deadline();
+}
+
+#[tokio::main]
+async fn _bcast_thrifty() {
+    let mut tc = TestCluster::new(3, 5650);
+    tc.start().await;
+
+    // a peer accepts connections but never replies.
+    let hang = TcpListener::bind("127.0.0.1:5660").unwrap();
+
+    let inst = foo_inst!((0, 1), "key_x", [(0, 0), (1, 0), (2, 0)]);
+    let req = MakeRequest::fast_accept(0, &inst, &[true, true, true]);
+
+    let peers = tc.replica(0).peers.clone();
+    let first = vec![
+        ReplicaPeer::new(3, "http://127.0.0.1:5660".into(), true),
+        peers[0].clone(),
+    ];
+    let rest = vec![peers[1].clone()];
+
+    let clients = Arc::new(PeerClients::new(Duration::from_millis(200)));
+
+    let start = Instant::now();
+    let mut rx = bcast_thrifty(&clients, &first, &rest, req);
+
+    let (rid, _) = rx.recv().await.unwrap();
+    assert_eq!(peers[0].replica_id, rid);
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    // the rest peer is sent to once the hanging peer is given up, without waiting any longer.
+    let (rid, _) = rx.recv().await.unwrap();
+    assert_eq!(peers[1].replica_id, rid);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(start.elapsed() < Duration::from_millis(1_000));
+
+    assert!(rx.recv().await.is_none());
+
+    drop(hang);
+}
+
+#[test]
+fn test_bcast_thrifty() {
+    _bcast_thrifty();
+}
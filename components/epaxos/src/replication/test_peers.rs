@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use crate::qpaxos::Command;
+use crate::qpaxos::Instance;
+use crate::qpaxos::InstanceId;
+use crate::qpaxos::MakeRequest;
+use crate::replica::ReplicaPeer;
+use crate::replication::PeerClients;
+use crate::replication::PeerError;
+use crate::testutil::TestCluster;
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn test_peer_clients_backoff() {
+    let mut pc = PeerClients::default();
+    pc.backoff_base = Duration::from_millis(100);
+    pc.backoff_max = Duration::from_millis(500);
+
+    assert_eq!(Duration::from_millis(100), pc.backoff(1));
+    assert_eq!(Duration::from_millis(200), pc.backoff(2));
+    assert_eq!(Duration::from_millis(400), pc.backoff(3));
+    assert_eq!(Duration::from_millis(500), pc.backoff(4));
+    assert_eq!(Duration::from_millis(500), pc.backoff(100));
+}
+
+#[test]
+fn test_peer_clients_liveness() {
+    _peer_clients_liveness();
+}
+
+#[tokio::main]
+async fn _peer_clients_liveness() {
+    let addr = "http://127.0.0.1:5620";
+
+    let mut pc = PeerClients::new(Duration::from_millis(500));
+    pc.backoff_base = Duration::from_millis(200);
+
+    let inst = foo_inst!((0, 1), "key_x", [(0, 0), (1, 0), (2, 0)]);
+    let req = MakeRequest::prepare(0, &inst);
+
+    assert!(pc.is_alive(addr), "never contacted");
+
+    // nothing is listening.
+    let rst = pc.replicate(addr, req.clone()).await;
+    assert!(rst.is_err());
+    assert!(!pc.is_alive(addr));
+
+    // dead peer is skipped at once.
+    let rst = pc.replicate(addr, req.clone()).await;
+    match &rst {
+        Err(PeerError::Backoff(a)) => assert_eq!(addr, a.as_str()),
+        _ => panic!("expect backoff but: {:?}", rst),
+    }
+
+    let peers: Vec<ReplicaPeer> = vec![
+        (0, addr, true).into(),
+        (1, "http://127.0.0.1:5621", true).into(),
+    ];
+    let sorted = pc.sort_by_liveness(&peers);
+    assert_eq!(
+        vec![
+            ReplicaPeer::from((1, "http://127.0.0.1:5621", true)),
+            ReplicaPeer::from((0, addr, false)),
+        ],
+        sorted
+    );
+
+    // the peer comes back, heartbeat finds it once backoff passes.
+    let mut tc = TestCluster::new(1, 5620);
+    tc.start().await;
+
+    pc.heartbeat().await;
+    assert!(pc.is_alive(addr));
+
+    let rst = pc.replicate(addr, req.clone()).await;
+    assert!(rst.is_ok());
+}
@@ -0,0 +1,365 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::delay_for;
+
+use crate::qpaxos::*;
+use crate::replica::Replica;
+use crate::replica::Status;
+use crate::replication::*;
+use crate::testutil;
+use crate::testutil::TestCluster;
+use storage::MemEngine;
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+async fn wait_committed(rp: &Replica, iid: InstanceId) -> Instance {
+    for _ in 0..100 {
+        let inst = rp.storage.get_instance(iid).unwrap();
+        if inst.as_ref().map(|x| x.committed) == Some(true) {
+            break;
+        }
+        delay_for(Duration::from_millis(10)).await;
+    }
+
+    rp.storage.get_instance(iid).unwrap().unwrap()
+}
+
+#[test]
+fn test_next_ballot() {
+    assert_eq!(BallotNum::from((0, 1, 2)), next_ballot(None, 2));
+    assert_eq!(
+        BallotNum::from((3, 5, 1)),
+        next_ballot(Some((3, 4, 2).into()), 1)
+    );
+}
+
+#[test]
+fn test_recover_fast_accepted() {
+    _recover_fast_accepted();
+}
+
+#[tokio::main]
+async fn _recover_fast_accepted() {
+    let mut tc = TestCluster::new(3, 5570);
+    tc.start().await;
+
+    let cmds = cmds![("Set", "x", "y")];
+
+    let (g, r) = tc.server_datas[0]
+        .get_local_replica_for_key("x".as_bytes())
+        .unwrap();
+
+    // the leader crashes after fast-accept, before commit.
+    let st = replicate(&cmds, g, r).await.unwrap();
+    let iid = st.instance.instance_id.unwrap();
+    tc.kill(0);
+
+    let inst = recover(iid, tc.replica(1)).await.unwrap();
+    assert!(inst.committed);
+    assert_eq!(cmds, inst.cmds);
+    assert_eq!(Some((0, 1, 1).into()), inst.ballot);
+
+    for i in 1..3 {
+        let got = wait_committed(tc.replica(i), iid).await;
+        assert!(got.committed, "replica {} committed", i);
+        assert_eq!(cmds, got.cmds);
+        assert_eq!(inst.final_deps, got.final_deps);
+    }
+}
+
+#[test]
+fn test_recover_unseen() {
+    _recover_unseen();
+}
+
+#[tokio::main]
+async fn _recover_unseen() {
+    let mut tc = TestCluster::new(3, 5580);
+    tc.start().await;
+
+    let cmds = cmds![("Set", "x", "y")];
+
+    // the leader crashes before sending anything.
+    let inst = tc.replica(0).new_instance(&cmds).unwrap();
+    let iid = inst.instance_id.unwrap();
+    tc.kill(0);
+
+    let inst = recover(iid, tc.replica(1)).await.unwrap();
+    assert!(inst.committed);
+    assert_eq!(cmds![("NoOp", "", "")], inst.cmds);
+
+    for i in 1..3 {
+        let got = wait_committed(tc.replica(i), iid).await;
+        assert!(got.committed, "replica {} committed", i);
+        assert_eq!(inst.cmds, got.cmds);
+
+        let executed = tc.replica(i).execute().unwrap();
+        assert_eq!(vec![iid], executed, "replica {} executed", i);
+    }
+}
+
+#[test]
+fn test_recover_fast_accepted_by_minority() {
+    _recover_fast_accepted_by_minority();
+}
+
+#[tokio::main]
+async fn _recover_fast_accepted_by_minority() {
+    let mut tc = TestCluster::new(3, 5590);
+    tc.start().await;
+
+    let cmds = cmds![("Set", "x", "y")];
+
+    // the leader crashes after sending fast-accept to replica 2 only.
+    let r0 = tc.replica(0);
+    let inst = r0.new_instance(&cmds).unwrap();
+    let iid = inst.instance_id.unwrap();
+
+    let peers: Vec<_> = r0
+        .peers
+        .iter()
+        .filter(|p| p.replica_id == 2)
+        .cloned()
+        .collect();
+    let req = MakeRequest::fast_accept(0, &inst, &[false, false, false]);
+    let mut repls = bcast_msg(&r0.peer_clients, &peers, req);
+    assert!(repls.recv().await.is_some());
+
+    tc.kill(0);
+
+    // replica 1 try-pre-accepts the deps replica 2 has and commits it with the same deps.
+    let got = recover(iid, tc.replica(1)).await.unwrap();
+    assert!(got.committed);
+    assert_eq!(cmds, got.cmds);
+    assert_eq!(inst.deps, got.final_deps);
+
+    for i in 1..3 {
+        let got = wait_committed(tc.replica(i), iid).await;
+        assert!(got.committed, "replica {} committed", i);
+        assert_eq!(cmds, got.cmds);
+        assert_eq!(inst.deps, got.final_deps);
+    }
+}
+
+#[test]
+fn test_recover_accepted_over_fast_accepted() {
+    _recover_accepted_over_fast_accepted();
+}
+
+#[tokio::main]
+async fn _recover_accepted_over_fast_accepted() {
+    let mut tc = TestCluster::new(3, 5660);
+    tc.start().await;
+
+    let cmds = cmds![("Set", "x", "y")];
+
+    // replica 1 and 2 fast-accept the instance with the same deps.
+    let r0 = tc.replica(0);
+    let mut inst = r0.new_instance(&cmds).unwrap();
+    let iid = inst.instance_id.unwrap();
+    for i in 1..3 {
+        let req = MakeRequest::fast_accept(i as i64, &inst, &[false, false, false]);
+        tc.replica(i).handle_replicate(req).unwrap();
+    }
+
+    // the leader commits other deps on slow-path, accepted by replica 1 only, then crashes.
+    let mut final_deps = inst.deps.clone().unwrap();
+    final_deps.set((1, 3).into());
+    inst.final_deps = Some(final_deps.clone());
+    inst.accepted_ballot = inst.ballot;
+    tc.replica(1)
+        .handle_replicate(MakeRequest::accept(1, &inst))
+        .unwrap();
+    inst.committed = true;
+    r0.storage.set_instance(&inst).unwrap();
+
+    tc.kill(0);
+
+    // replica 2 still has the fast-accepted deps, which form a fast quorum along with the leader,
+    // but the accepted value is chosen.
+    let got = recover(iid, tc.replica(2)).await.unwrap();
+    assert!(got.committed);
+    assert_eq!(cmds, got.cmds);
+    assert_eq!(Some(final_deps.clone()), got.final_deps);
+
+    for i in 1..3 {
+        let got = wait_committed(tc.replica(i), iid).await;
+        assert!(got.committed, "replica {} committed", i);
+        assert_eq!(Some(final_deps.clone()), got.final_deps);
+    }
+}
+
+/// start_minority_fast_accepted starts a cluster of 3 replicas. The leader, replica 0, sends
+/// fast-accept of an instance of `Set x` to replica 2 only and crashes.
+/// It returns the cluster and the instance.
+async fn start_minority_fast_accepted(port_base: i32) -> (TestCluster, Instance) {
+    let mut tc = TestCluster::new(3, port_base);
+    tc.start().await;
+
+    let r0 = tc.replica(0);
+    let inst = r0.new_instance(&cmds![("Set", "x", "y")]).unwrap();
+
+    let req = MakeRequest::fast_accept(2, &inst, &[false, false, false]);
+    tc.replica(2).handle_replicate(req).unwrap();
+
+    tc.kill(0);
+    (tc, inst)
+}
+
+#[test]
+fn test_recover_try_pre_accept_committed_conflict() {
+    _recover_try_pre_accept_committed_conflict();
+}
+
+#[tokio::main]
+async fn _recover_try_pre_accept_committed_conflict() {
+    let (tc, inst) = start_minority_fast_accepted(5670).await;
+    let iid = inst.instance_id.unwrap();
+
+    // replica 1 has committed a conflicting instance, which does not depend on the one being
+    // recovered: the deps could not have been committed on fast-path.
+    let r1 = tc.replica(1);
+    let mut confl = r1.new_instance(&cmds![("Set", "x", "z")]).unwrap();
+    confl.final_deps = confl.deps.clone();
+    confl.committed = true;
+    r1.storage.set_instance(&confl).unwrap();
+
+    let got = recover(iid, tc.replica(2)).await.unwrap();
+    assert!(got.committed);
+    assert_eq!(inst.cmds, got.cmds);
+    assert_eq!(confl.instance_id, got.final_deps.unwrap().get(1));
+}
+
+#[test]
+fn test_recover_try_pre_accept_leader_conflict() {
+    _recover_try_pre_accept_leader_conflict();
+}
+
+#[tokio::main]
+async fn _recover_try_pre_accept_leader_conflict() {
+    let (tc, inst) = start_minority_fast_accepted(5680).await;
+    let iid = inst.instance_id.unwrap();
+
+    // replica 1 has fast-accepted a conflicting instance of the leader, which does not depend on
+    // the one being recovered.
+    let mut confl = inst.clone();
+    confl.instance_id = Some((0, 1).into());
+    confl.cmds = cmds![("Set", "x", "z")];
+    let req = MakeRequest::fast_accept(1, &confl, &[false, false, false]);
+    tc.replica(1).handle_replicate(req).unwrap();
+
+    let got = recover(iid, tc.replica(2)).await.unwrap();
+    assert!(got.committed);
+    assert_eq!(inst.cmds, got.cmds);
+    assert_eq!(confl.instance_id, got.final_deps.unwrap().get(0));
+}
+
+#[test]
+fn test_recover_try_pre_accept_deferred() {
+    _recover_try_pre_accept_deferred();
+}
+
+#[tokio::main]
+async fn _recover_try_pre_accept_deferred() {
+    let (tc, inst) = start_minority_fast_accepted(5690).await;
+    let iid = inst.instance_id.unwrap();
+
+    // replica 1 has a conflicting instance not yet committed, which does not depend on the one
+    // being recovered. Recovery waits for it to be committed.
+    let r1 = tc.replica(1);
+    let mut confl = r1.new_instance(&cmds![("Set", "x", "z")]).unwrap();
+    confl.final_deps = confl.deps.clone();
+
+    let commit_later = async {
+        delay_for(Duration::from_millis(100)).await;
+        for i in 1..3 {
+            let req = MakeRequest::commit(i as i64, &confl);
+            tc.replica(i).handle_replicate(req).unwrap();
+        }
+    };
+
+    let (got, _) = tokio::join!(recover(iid, tc.replica(2)), commit_later);
+    let got = got.unwrap();
+    assert!(got.committed);
+    assert_eq!(inst.cmds, got.cmds);
+    assert_eq!(confl.instance_id, got.final_deps.unwrap().get(1));
+}
+
+#[test]
+fn test_choose_accept_value() {
+    let rp = testutil::new_replica(
+        1,
+        vec![1, 2, 3],
+        vec![],
+        Arc::new(MemEngine::new().unwrap()),
+    );
+
+    let iid: InstanceId = (3, 0).into();
+    let inst = Instance {
+        instance_id: Some(iid),
+        ballot: Some((0, 4, 1).into()),
+        ..Default::default()
+    };
+    let mut st = Status::new_recovery(3, inst);
+
+    let accepted = |cmd: &str, fdeps: InstanceIdVec, blt: (i32, i32, i64)| PrepareReply {
+        cmds: cmds![("Set", cmd, "y")],
+        deps: Some(fdeps.clone()),
+        final_deps: Some(fdeps),
+        accepted_ballot: Some(blt.into()),
+        committed: false,
+    };
+
+    // replica 1 accepted an older value, but it has promised a higher ballot to a recovery
+    // that did not reach Accept, after replica 2 accepted a newer value.
+    st.prepare_oks.insert(
+        1,
+        ((0, 3, 2).into(), accepted("x", [(3, 0)].into(), (0, 1, 3))),
+    );
+    st.prepare_oks.insert(
+        2,
+        ((0, 2, 2).into(), accepted("z", [(3, 1)].into(), (0, 2, 2))),
+    );
+
+    let (cmds, fdeps) = choose_accept_value(iid, &st, &rp);
+    assert_eq!(cmds![("Set", "z", "y")], cmds);
+    assert_eq!(InstanceIdVec::from([(3, 1)]), fdeps);
+}
+
+#[test]
+fn test_recover_higher_ballot() {
+    _recover_higher_ballot();
+}
+
+#[tokio::main]
+async fn _recover_higher_ballot() {
+    let mut tc = TestCluster::new(3, 5640);
+    tc.start().await;
+
+    let cmds = cmds![("Set", "x", "y")];
+
+    let (g, r) = tc.server_datas[0]
+        .get_local_replica_for_key("x".as_bytes())
+        .unwrap();
+
+    let st = replicate(&cmds, g, r).await.unwrap();
+    let iid = st.instance.instance_id.unwrap();
+    tc.kill(0);
+
+    // replica 2 has promised a ballot higher than any replica 1 knows of.
+    let r2 = tc.replica(2);
+    let mut inst = r2.get_instance(iid).unwrap();
+    inst.ballot = Some((0, 5, 2).into());
+    r2.handle_replicate(MakeRequest::prepare(2, &inst)).unwrap();
+
+    let inst = recover(iid, tc.replica(1)).await.unwrap();
+    assert!(inst.committed);
+    assert_eq!(cmds, inst.cmds);
+    assert_eq!(Some((0, 6, 1).into()), inst.ballot);
+
+    let got = wait_committed(r2, iid).await;
+    assert!(got.committed);
+    assert_eq!(inst.final_deps, got.final_deps);
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use tokio::time::delay_for;
+
+use crate::qpaxos::*;
+use crate::replication::*;
+use crate::testutil::TestCluster;
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn test_replicate_and_commit() {
+    _replicate_and_commit();
+}
+
+#[tokio::main]
+async fn _replicate_and_commit() {
+    let mut tc = TestCluster::new(3, 5560);
+    tc.start().await;
+
+    let cmds = cmds![("Set", "x", "y")];
+
+    let (g, r) = tc.server_datas[0]
+        .get_local_replica_for_key("x".as_bytes())
+        .unwrap();
+
+    let mut st = replicate(&cmds, g, r).await.unwrap();
+    commit(&mut st.instance, r).unwrap();
+
+    let iid = st.instance.instance_id.unwrap();
+
+    // every replica receives the Commit and executes the write.
+    for i in 0..3 {
+        let rp = tc.replica(i);
+
+        for _ in 0..100 {
+            let inst = rp.storage.get_instance(iid).unwrap();
+            if inst.map(|x| x.committed) == Some(true) {
+                break;
+            }
+            delay_for(Duration::from_millis(10)).await;
+        }
+
+        let inst = rp.storage.get_instance(iid).unwrap().unwrap();
+        assert!(inst.committed, "replica {} committed", i);
+        assert_eq!(cmds, inst.cmds);
+        assert_eq!(st.instance.final_deps, inst.final_deps);
+
+        let executed = rp.execute().unwrap();
+        assert_eq!(vec![iid], executed, "replica {} executed", i);
+
+        let v = rp.storage.get_kv(&"x".as_bytes().to_vec()).unwrap();
+        assert_eq!(Some("y".as_bytes().to_vec()), v);
+    }
+}
@@ -1,12 +1,16 @@
 use parse::Response;
+use storage::StorageError;
+
+use crate::conf::NodeId;
+use crate::replica::ReplicaError;
 
 quick_error! {
     /// RangeLookupError defines all error occurs at server level.
     /// It also wraps lower level errors.
     #[derive(Debug, PartialEq)]
     pub enum RangeLookupError {
-        NoGroupForKey(k: String) {}
-        NoLocalReplicaForKey(k: String) {}
+        NoGroupForKey(k: Vec<u8>) {}
+        NoLocalReplicaForKey(k: Vec<u8>) {}
     }
 }
 
@@ -14,11 +18,27 @@ impl From<RangeLookupError> for Response {
     fn from(e: RangeLookupError) -> Response {
         match e {
             RangeLookupError::NoGroupForKey(k) => {
-                Response::Error(format!("No gruop serves: {}", k))
+                Response::Error(format!("No gruop serves: {}", String::from_utf8_lossy(&k)))
             }
             RangeLookupError::NoLocalReplicaForKey(k) => {
-                Response::Error(format!("No replica serve: {}", k))
+                Response::Error(format!("No replica serve: {}", String::from_utf8_lossy(&k)))
             }
         }
     }
 }
+
+quick_error! {
+    /// ServerDataError is an error encountered when loading the replicas a node hosts.
+    #[derive(Debug, PartialEq)]
+    pub enum ServerDataError {
+        NoSuchNode(nid: NodeId) {
+            display("node {} not found in cluster", nid)
+        }
+        Replica(e: ReplicaError) {
+            from(e: ReplicaError) -> (e)
+        }
+        Storage(e: StorageError) {
+            from(e: StorageError) -> (e)
+        }
+    }
+}
@@ -4,7 +4,9 @@ use crate::conf::Node;
 use crate::conf::NodeId;
 use crate::qpaxos::ReplicaId;
 use crate::replica::Replica;
+use crate::replication::PeerClients;
 use crate::RangeLookupError;
+use crate::ServerDataError;
 use crate::Storage;
 use std::collections::BTreeMap;
 use std::sync::Arc;
@@ -19,6 +21,8 @@ pub struct ServerData {
     pub node: Node,
     pub local_replicas: BTreeMap<ReplicaId, Replica>,
     pub storage: Storage,
+    /// connections to other nodes, shared by all local replicas.
+    pub peer_clients: Arc<PeerClients>,
 }
 
 impl Default for ServerData {
@@ -40,49 +44,111 @@ groups:
         let sto = MemEngine::new().unwrap();
         let sto = Arc::new(sto);
         let node_id = "127.0.0.1:4441";
-        ServerData::new(sto, ci, node_id.into())
+        ServerData::new(sto, ci, node_id.into()).unwrap()
     }
 }
 
 impl ServerData {
-    pub fn new(sto: Storage, cluster: ClusterInfo, node_id: NodeId) -> ServerData {
-        let n = cluster.get(&node_id).unwrap().clone();
+    /// new loads the replicas hosted by node `node_id` from `sto`.
+    /// It returns an error if the node is not in `cluster`, or the storage fails.
+    pub fn new(
+        sto: Storage,
+        cluster: ClusterInfo,
+        node_id: NodeId,
+    ) -> Result<ServerData, ServerDataError> {
+        let n = match cluster.get(&node_id) {
+            Some(v) => v.clone(),
+            None => return Err(ServerDataError::NoSuchNode(node_id)),
+        };
+
+        let peer_clients = Arc::new(PeerClients::default());
 
         let mut rs = BTreeMap::new();
         for (rid, rinfo) in cluster.replicas.iter() {
             if rinfo.node_id == node_id {
-                let rp = Replica::new(*rid, &cluster, sto.clone()).unwrap();
+                let rp = Replica::new(*rid, &cluster, sto.clone(), peer_clients.clone())?;
+
+                // continue with the data in storage, if the server restarts.
+                let (maxs, execs) = rp.load_refs()?;
+                println!("replica {} loaded max:{} exec:{}", rid, maxs, execs);
+
                 rs.insert(*rid, rp);
             }
         }
 
-        ServerData {
+        Ok(ServerData {
             cluster,
             node_id,
             node: n,
             local_replicas: rs,
             storage: sto,
-        }
+            peer_clients,
+        })
+    }
+
+    /// get_group_for_key returns the group serving `key`.
+    pub fn get_group_for_key(&self, key: &[u8]) -> Result<&GroupInfo, RangeLookupError> {
+        self.cluster
+            .get_group_for_key(key)
+            .ok_or_else(|| RangeLookupError::NoGroupForKey(key.to_vec()))
     }
 
     pub fn get_local_replica_for_key(
         &self,
         key: &[u8],
     ) -> Result<(&GroupInfo, &Replica), RangeLookupError> {
-        let k = String::from_utf8(key.to_vec()).unwrap();
+        let g = self.get_group_for_key(key)?;
 
-        let g = self
-            .cluster
-            .get_group_for_key(&k)
-            .ok_or(RangeLookupError::NoGroupForKey(k.clone()))?;
+        match self.get_local_replica_for_group(g) {
+            Some(r) => Ok((g, r)),
+            None => Err(RangeLookupError::NoLocalReplicaForKey(key.to_vec())),
+        }
+    }
 
+    /// get_remote_node_for_key returns a node hosting a replica of the group serving `key`, if
+    /// this node hosts none. A node whose replication address is alive is preferred.
+    /// It returns `None` if there is a local replica for `key`.
+    pub fn get_remote_node_for_key(&self, key: &[u8]) -> Result<Option<&Node>, RangeLookupError> {
+        let g = self.get_group_for_key(key)?;
+
+        if self.get_local_replica_for_group(g).is_some() {
+            return Ok(None);
+        }
+
+        match self.get_remote_node_for_group(g) {
+            Some(n) => Ok(Some(n)),
+            None => Err(RangeLookupError::NoLocalReplicaForKey(key.to_vec())),
+        }
+    }
+
+    /// get_local_replica_for_group returns the replica of `g` on this node, if there is one.
+    pub fn get_local_replica_for_group(&self, g: &GroupInfo) -> Option<&Replica> {
+        g.replicas
+            .keys()
+            .find_map(|rid| self.local_replicas.get(rid))
+    }
+
+    /// get_remote_node_for_group returns a node hosting a replica of `g`, preferring one whose
+    /// replication address is alive, or `None` if no replica of `g` is on a known node.
+    pub fn get_remote_node_for_group(&self, g: &GroupInfo) -> Option<&Node> {
+        self.get_remote_replica_for_group(g).map(|(_, n)| n)
+    }
+
+    /// get_remote_replica_for_group is `get_remote_node_for_group` along with the id of the
+    /// replica of `g` on the node.
+    pub fn get_remote_replica_for_group(&self, g: &GroupInfo) -> Option<(ReplicaId, &Node)> {
+        let mut replicas = vec![];
         for (rid, _) in g.replicas.iter() {
-            let replica = self.local_replicas.get(rid);
-            if let Some(v) = replica {
-                return Ok((g, v));
+            if let Some(n) = self.cluster.get_replica_node(*rid) {
+                replicas.push((*rid, n));
             }
         }
 
-        Err(RangeLookupError::NoLocalReplicaForKey(k.clone()))
+        let alive = replicas.iter().find(|(_, n)| {
+            let addr = format!("http://{}", n.replication);
+            self.peer_clients.is_alive(&addr)
+        });
+
+        alive.or(replicas.first()).copied()
     }
 }
@@ -1,6 +1,7 @@
 use crate::conf::ClusterInfo;
 use crate::RangeLookupError;
 use crate::ServerData;
+use crate::ServerDataError;
 use std::sync::Arc;
 use storage::MemEngine;
 
@@ -28,7 +29,7 @@ groups:
     {
         // test lookup group
         let node_id = "192.168.0.1:4442";
-        let sd = ServerData::new(sto.clone(), ci.clone(), node_id.into());
+        let sd = ServerData::new(sto.clone(), ci.clone(), node_id.into()).unwrap();
 
         let (g, r) = sd.get_local_replica_for_key("b".as_bytes()).unwrap();
         assert_eq!(g, &ci.groups[0]);
@@ -38,15 +39,54 @@ groups:
             RangeLookupError::NoGroupForKey("z".into()),
             rst.err().unwrap()
         );
+
+        // binary keys
+        let rst = sd.get_local_replica_for_key(&[0xff, 0xfe]);
+        assert_eq!(
+            RangeLookupError::NoGroupForKey(vec![0xff, 0xfe]),
+            rst.err().unwrap()
+        );
+        assert!(sd.get_local_replica_for_key(&[b'b', 0xff]).is_ok());
     }
     {
         // test no replica locally
         let node_id = "127.0.0.1:4441";
-        let sd = ServerData::new(sto.clone(), ci.clone(), node_id.into());
+        let sd = ServerData::new(sto.clone(), ci.clone(), node_id.into()).unwrap();
 
         assert_eq!(
             RangeLookupError::NoLocalReplicaForKey("b".into()),
             sd.get_local_replica_for_key("b".as_bytes()).err().unwrap()
         );
+
+        // forward to the node hosting the replica
+        let n = sd.get_remote_node_for_key("b".as_bytes()).unwrap().unwrap();
+        assert_eq!("192.168.0.1:3332", n.api_addr.to_string());
+
+        let g = sd.get_group_for_key(b"b").unwrap();
+        assert_eq!(g, &ci.groups[0]);
+        assert!(sd.get_local_replica_for_group(g).is_none());
+        assert_eq!(Some(n), sd.get_remote_node_for_group(g));
+        assert_eq!(Some((1, n)), sd.get_remote_replica_for_group(g));
+        assert_eq!(
+            RangeLookupError::NoGroupForKey("z".into()),
+            sd.get_remote_node_for_key("z".as_bytes()).err().unwrap()
+        );
+    }
+    {
+        let node_id = "192.168.0.1:4442";
+        let sd = ServerData::new(sto.clone(), ci.clone(), node_id.into()).unwrap();
+
+        assert_eq!(None, sd.get_remote_node_for_key("b".as_bytes()).unwrap());
+
+        let g = ci.get_group_for_key(b"b").unwrap();
+        assert!(sd.get_local_replica_for_group(g).is_some());
+    }
+    {
+        // unknown node
+        let rst = ServerData::new(sto.clone(), ci.clone(), "127.0.0.1:9999".into());
+        assert_eq!(
+            ServerDataError::NoSuchNode("127.0.0.1:9999".into()),
+            rst.err().unwrap()
+        );
     }
 }
@@ -1,7 +1,14 @@
+use crate::qpaxos::ExecResult;
+use crate::qpaxos::ProposeReply;
+use crate::qpaxos::ProposeRequest;
 use crate::qpaxos::ProtocolError;
 use crate::qpaxos::QPaxos;
+use crate::qpaxos::ReadIndexReply;
+use crate::qpaxos::ReadIndexRequest;
 use crate::qpaxos::ReplicateReply;
 use crate::qpaxos::ReplicateRequest;
+use crate::replication::propose;
+use crate::replication::ReplicationError;
 use crate::replication::RpcHandlerError;
 use crate::ServerData;
 use std::sync::Arc;
@@ -40,6 +47,42 @@ impl QPaxos for MyQPaxos {
         };
         Ok(Response::new(reply))
     }
+
+    async fn read_index(
+        &self,
+        request: Request<ReadIndexRequest>,
+    ) -> Result<Response<ReadIndexReply>, Status> {
+        let req = request.into_inner();
+
+        let reply = handle_read_index_request(self, req);
+        let reply = match reply {
+            Ok(v) => v,
+            Err(e) => ReadIndexReply {
+                err: Some(e.into()),
+                ..Default::default()
+            },
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn propose(
+        &self,
+        request: Request<ProposeRequest>,
+    ) -> Result<Response<ProposeReply>, Status> {
+        let req = request.into_inner();
+
+        let reply = handle_propose_request(self, req).await;
+        let reply = match reply {
+            Ok(v) => v,
+            Err(ReplicationError::RpcHandler(e)) => ProposeReply {
+                err: Some(e.into()),
+                ..Default::default()
+            },
+            // the commands may or may not be executed.
+            Err(e) => return Err(Status::unavailable(format!("{:?}", e))),
+        };
+        Ok(Response::new(reply))
+    }
 }
 
 pub fn handle_replicate_request(
@@ -53,3 +96,33 @@ pub fn handle_replicate_request(
 
     r.handle_replicate(req)
 }
+
+pub fn handle_read_index_request(
+    sv: &MyQPaxos,
+    req: ReadIndexRequest,
+) -> Result<ReadIndexReply, RpcHandlerError> {
+    let rid = req.to_replica_id;
+    let r = sv.server_data.local_replicas.get(&rid);
+    let r = r.ok_or(ProtocolError::NoSuchReplica(rid, 0))?;
+
+    r.handle_read_index(&req)
+}
+
+pub async fn handle_propose_request(
+    sv: &MyQPaxos,
+    req: ProposeRequest,
+) -> Result<ProposeReply, ReplicationError> {
+    let sd = &sv.server_data;
+    let rid = req.to_replica_id;
+    let no_such = || RpcHandlerError::from(ProtocolError::NoSuchReplica(rid, 0));
+
+    let r = sd.local_replicas.get(&rid).ok_or_else(no_such)?;
+    let g = sd.cluster.get_group(rid).ok_or_else(no_such)?;
+
+    let rsts = propose(&req.cmds, g, r).await?;
+
+    Ok(ProposeReply {
+        results: rsts.iter().map(ExecResult::from).collect(),
+        ..Default::default()
+    })
+}
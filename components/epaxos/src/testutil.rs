@@ -1,10 +1,16 @@
-use std::collections::HashMap;
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
 use std::time::Duration;
 
+use crate::conf::ClusterInfo;
 use crate::qpaxos::*;
-use crate::replica::{Replica, ReplicaPeer};
+use crate::read::ReadIndexCache;
+use crate::replica::{ExecWaiters, Replica, ReplicaPeer};
+use crate::replication::PeerClients;
 use crate::MyQPaxos;
+use crate::ServerData;
 use crate::Storage;
 use storage::MemEngine;
 
@@ -100,64 +106,99 @@ pub fn new_replica(
     Replica {
         replica_id: rid,
         group_replica_ids: group,
+        slots: None,
         peers,
+        peer_clients: Arc::new(PeerClients::default()),
         storage: sto,
         committed_timeout: 1000,
+        waiters: ExecWaiters::new(),
+        to_recover: Mutex::new(BTreeSet::new()),
+        exec_lock: RwLock::new(()),
+        read_index_cache: ReadIndexCache::default(),
     }
 }
 
+/// TestCluster setup a cluster of `replica_cnt` nodes in one process.
+/// Every node has one replica of the only group, its own storage and its own replication server
+/// listening on `127.0.0.1:<port_base + i>`.
 pub struct TestCluster {
-    pub replicas: Vec<Replica>,
-    pub txs: Vec<oneshot::Sender<()>>,
+    pub server_datas: Vec<Arc<ServerData>>,
+    pub txs: Vec<Option<oneshot::Sender<()>>>,
     pub addrs: Vec<String>,
 }
 
 impl TestCluster {
-    pub fn new(replica_cnt: i32) -> Self {
-        let sto = Arc::new(MemEngine::new().unwrap());
+    pub fn new(replica_cnt: i32, port_base: i32) -> Self {
+        let mut nodes = String::new();
+        let mut replicas = String::new();
+        let mut addrs = vec![];
 
-        let mut addrs = HashMap::new();
         for i in 0..replica_cnt {
-            let url = format!("http://127.0.0.1:555{}", i);
-            addrs.insert(i as ReplicaId, url);
+            let addr = format!("127.0.0.1:{}", port_base + i);
+            nodes.push_str(&format!(
+                "
+    {}:
+        api_addr: 127.0.0.1:{}
+        replication: {}",
+                addr,
+                port_base + 100 + i,
+                addr
+            ));
+            replicas.push_str(&format!(
+                "
+        {}: {}",
+                i, addr
+            ));
+            addrs.push(addr);
         }
 
-        let group: Vec<ReplicaId> = addrs.iter().map(|(k, _)| k.clone()).collect();
-
-        let mut replicas = vec![];
-        for (k, _) in addrs.iter() {
-            let mut ps = vec![];
-            for (k1, v1) in addrs.iter() {
-                if k1 == k {
-                    continue;
-                }
-                ps.push((*k1, v1.clone(), true).into());
-            }
-
-            let rp = new_replica(*k, group.clone(), ps, sto.clone());
-            replicas.push(rp);
+        let yaml = format!(
+            "
+nodes:{}
+groups:
+-   range:
+    -   a
+    -   z
+    replicas:{}
+",
+            nodes, replicas
+        );
+
+        let ci = ClusterInfo::from_str(&yaml).unwrap();
+
+        let mut server_datas = vec![];
+        for addr in addrs.iter() {
+            let sto = Arc::new(MemEngine::new().unwrap());
+            let sd = ServerData::new(sto, ci.clone(), addr.clone()).unwrap();
+            server_datas.push(Arc::new(sd));
         }
 
         Self {
-            replicas,
+            server_datas,
             txs: vec![],
-            addrs: addrs.iter().map(|(_, v)| v.clone()).collect(),
+            addrs,
         }
     }
 
+    /// replica returns the replica on the i-th node.
+    pub fn replica(&self, i: usize) -> &Replica {
+        self.server_datas[i]
+            .local_replicas
+            .get(&(i as ReplicaId))
+            .unwrap()
+    }
+
     pub async fn start(&mut self) {
-        for addr in self.addrs.iter() {
+        for (i, addr) in self.addrs.iter().enumerate() {
             let (tx, rx) = oneshot::channel::<()>();
 
-            let qp = MyQPaxos::default();
+            let qp = MyQPaxos::new(self.server_datas[i].clone());
             let s = Server::builder().add_service(QPaxosServer::new(qp));
 
-            // remove scheme
-            // TODO standard parse the addr
-            let addr = addr[7..].parse().unwrap();
+            let addr = addr.parse().unwrap();
             tokio::spawn(async move {
                 s.serve_with_shutdown(addr, async {
-                    rx.await.unwrap();
+                    rx.await.ok();
                 })
                 .await
                 .unwrap();
@@ -165,16 +206,23 @@ impl TestCluster {
 
             println!("serving addr: {:?}", addr);
 
-            self.txs.push(tx);
+            self.txs.push(Some(tx));
         }
 
         // TODO check the server available with connect
         delay_for(Duration::from_millis(1_000)).await;
     }
 
+    /// kill shuts down the replication server on the i-th node, to simulate a crashed node.
+    pub fn kill(&mut self, i: usize) {
+        if let Some(tx) = self.txs[i].take() {
+            let _ = tx.send(());
+        }
+    }
+
     pub fn stop(&mut self) {
-        while let Some(tx) = self.txs.pop() {
-            tx.send(()).unwrap();
+        for i in 0..self.txs.len() {
+            self.kill(i);
         }
     }
 }
@@ -0,0 +1,344 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use futures::future::join_all;
+use prost::Message;
+
+use crate::conf::GroupInfo;
+use crate::qpaxos::now_ms;
+use crate::qpaxos::Command;
+use crate::qpaxos::OpCode;
+use crate::qpaxos::ProposeRequest;
+use crate::qpaxos::ProtocolError;
+use crate::qpaxos::ReplicaId;
+use crate::qpaxos::TxnInfo;
+use crate::qpaxos::TxnIntent;
+use crate::replica::ExecuteResult;
+use crate::replica::Replica;
+use crate::replication::propose;
+use crate::replication::RpcHandlerError;
+use crate::RangeLookupError;
+use crate::ReplicationError;
+use crate::ServerData;
+
+static TXN_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Proposer is the replica to propose the commands of a participant with.
+enum Proposer<'a> {
+    /// the replica of the group on this node.
+    Local(&'a Replica),
+
+    /// a replica of the group on another node, which proposes on behalf of this node with the
+    /// `propose` RPC.
+    Remote { replica_id: ReplicaId, addr: String },
+}
+
+/// Participant is a group involved in a transaction, with the replica to propose with.
+struct Participant<'a> {
+    sd: &'a ServerData,
+    g: &'a GroupInfo,
+    proposer: Proposer<'a>,
+
+    /// keys in the order they first appear in the transaction.
+    keys: Vec<Vec<u8>>,
+
+    /// the commands on every key.
+    intents: Vec<Vec<Command>>,
+}
+
+impl<'a> Participant<'a> {
+    /// new creates a participant of `g`, the group serving `key`, which proposes with the local
+    /// replica of `g` if there is one, or with a replica of `g` on another node.
+    fn new(sd: &'a ServerData, g: &'a GroupInfo, key: &[u8]) -> Result<Self, RangeLookupError> {
+        let proposer = match sd.get_local_replica_for_group(g) {
+            Some(r) => Proposer::Local(r),
+            None => {
+                let (replica_id, n) = sd
+                    .get_remote_replica_for_group(g)
+                    .ok_or_else(|| RangeLookupError::NoLocalReplicaForKey(key.to_vec()))?;
+                Proposer::Remote {
+                    replica_id,
+                    addr: format!("http://{}", n.replication),
+                }
+            }
+        };
+
+        Ok(Participant {
+            sd,
+            g,
+            proposer,
+            keys: vec![],
+            intents: vec![],
+        })
+    }
+
+    fn replica_id(&self) -> ReplicaId {
+        match self.proposer {
+            Proposer::Local(r) => r.replica_id,
+            Proposer::Remote { replica_id, .. } => replica_id,
+        }
+    }
+
+    /// propose runs `cmds` in one instance of the group and returns the result of every command.
+    async fn propose(&self, cmds: &[Command]) -> Result<Vec<ExecuteResult>, ReplicationError> {
+        let (replica_id, addr) = match self.proposer {
+            Proposer::Local(r) => return propose(cmds, self.g, r).await,
+            Proposer::Remote {
+                replica_id,
+                ref addr,
+            } => (replica_id, addr),
+        };
+
+        let req = ProposeRequest {
+            to_replica_id: replica_id,
+            cmds: cmds.to_vec(),
+        };
+        let repl = self.sd.peer_clients.propose(addr, req).await?;
+
+        if let Some(e) = repl.err {
+            return Err(RpcHandlerError::RemoteError(e).into());
+        }
+
+        if repl.results.len() != cmds.len() {
+            let e = ProtocolError::Incomplete(
+                "results".into(),
+                cmds.len() as i32,
+                repl.results.len() as i32,
+            );
+            return Err(RpcHandlerError::from(e).into());
+        }
+
+        Ok(repl.results.into_iter().map(ExecuteResult::from).collect())
+    }
+
+    /// prepare_cmds returns a `TxnPrepare` for every key, of which the intent records `all_keys`
+    /// of the transaction.
+    fn prepare_cmds(&self, txn: &TxnInfo, all_keys: &[Vec<u8>]) -> Vec<Command> {
+        let mut cmds = vec![];
+        for (key, kcmds) in self.keys.iter().zip(self.intents.iter()) {
+            let intent = TxnIntent {
+                txn: Some(txn.clone()),
+                cmds: kcmds.clone(),
+                keys: all_keys.to_vec(),
+            };
+
+            let mut v = vec![];
+            intent.encode(&mut v).unwrap();
+
+            cmds.push(txn_cmd(OpCode::TxnPrepare, key, &v, txn, 0));
+        }
+        cmds
+    }
+
+    /// commit_cmds returns a `TxnCommit` for every command in the intents, in the same order as
+    /// `intents`.
+    fn commit_cmds(&self, txn: &TxnInfo) -> Vec<Command> {
+        let mut cmds = vec![];
+        for (key, kcmds) in self.keys.iter().zip(self.intents.iter()) {
+            for seq in 0..kcmds.len() {
+                cmds.push(txn_cmd(OpCode::TxnCommit, key, &[], txn, seq as i64));
+            }
+        }
+        cmds
+    }
+
+    fn abort_cmds(&self, txn: &TxnInfo) -> Vec<Command> {
+        self.keys
+            .iter()
+            .map(|key| txn_cmd(OpCode::TxnAbort, key, &[], txn, 0))
+            .collect()
+    }
+
+    /// prepare returns true if the group votes yes.
+    async fn prepare(&self, txn: &TxnInfo, all_keys: &[Vec<u8>]) -> bool {
+        let cmds = self.prepare_cmds(txn, all_keys);
+        match self.propose(&cmds).await {
+            Ok(rsts) => rsts.iter().all(|x| x == &ExecuteResult::Success),
+            Err(e) => {
+                println!(
+                    "{:?} while prepare txn in group of {}",
+                    e,
+                    self.replica_id()
+                );
+                false
+            }
+        }
+    }
+
+    async fn commit(&self, txn: &TxnInfo) -> Result<Vec<ExecuteResult>, ReplicationError> {
+        self.propose(&self.commit_cmds(txn)).await
+    }
+
+    /// abort returns true if the intents in the group are aborted.
+    async fn abort(&self, txn: &TxnInfo) -> bool {
+        match self.propose(&self.abort_cmds(txn)).await {
+            Ok(_) => true,
+            Err(e) => {
+                // the intents are aborted by recovery later.
+                println!("{:?} while abort txn in group of {}", e, self.replica_id());
+                false
+            }
+        }
+    }
+
+    /// forget removes the decision from the primary group, after the intent of every key is
+    /// resolved. A decision failed to remove is left in storage.
+    async fn forget(&self, txn: &TxnInfo) {
+        let cmd = txn_cmd(OpCode::TxnForget, &txn.primary, &[], txn, 0);
+        if let Err(e) = self.propose(&[cmd]).await {
+            println!("{:?} while forget txn in group of {}", e, self.replica_id());
+        }
+    }
+}
+
+/// propose_for_key runs `cmds` in one instance of the group serving `key`, with the local replica
+/// of the group, or with a replica of it on another node.
+pub(crate) async fn propose_for_key(
+    sd: &ServerData,
+    key: &[u8],
+    cmds: &[Command],
+) -> Result<Vec<ExecuteResult>, ReplicationError> {
+    let g = sd.get_group_for_key(key)?;
+    Participant::new(sd, g, key)?.propose(cmds).await
+}
+
+pub fn txn_cmd(op: OpCode, key: &[u8], value: &[u8], txn: &TxnInfo, seq: i64) -> Command {
+    Command {
+        txn: Some(TxnInfo { seq, ..txn.clone() }),
+        ..Command::of(op, key, value)
+    }
+}
+
+/// propose_txn runs `cmds` atomically as a transaction, whose keys may be served by different
+/// groups. The commands of a group without a replica on this node are proposed by a replica of
+/// it on another node.
+///
+/// It returns the `ExecuteResult` of every command, as if they are executed in one instance.
+/// It returns `ReplicationError::TxnAborted` if the transaction is aborted, e.g., some key is
+/// locked by another transaction, or a watched key has changed.
+///
+/// Commands on different keys in a transaction are independent of each other, thus they are
+/// evaluated in the order of keys, other than the order in `cmds`.
+pub async fn propose_txn(
+    sd: &ServerData,
+    cmds: &[Command],
+) -> Result<Vec<ExecuteResult>, ReplicationError> {
+    let seq = TXN_SEQ.fetch_add(1, Ordering::SeqCst);
+    let ctime_ms = now_ms();
+
+    let txn = TxnInfo {
+        id: format!("{}/{}/{}", sd.node_id, ctime_ms, seq).into_bytes(),
+        primary: cmds[0].key.clone(),
+        ctime_ms,
+        seq: 0,
+    };
+
+    // where the result of every command is: (group, key index, seq in the intent).
+    let mut pos = vec![];
+    let mut parts: BTreeMap<ReplicaId, Participant> = BTreeMap::new();
+    let mut all_keys: Vec<Vec<u8>> = vec![];
+
+    for cmd in cmds.iter() {
+        let g = sd.get_group_for_key(&cmd.key)?;
+        if !all_keys.contains(&cmd.key) {
+            all_keys.push(cmd.key.clone());
+        }
+
+        // a group is identified by its first replica, since a replica belongs to only one group.
+        let gid = g.replicas.keys().next().copied().unwrap_or_default();
+
+        let p = match parts.entry(gid) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(Participant::new(sd, g, &cmd.key)?),
+        };
+
+        let ki = match p.keys.iter().position(|k| k == &cmd.key) {
+            Some(i) => i,
+            None => {
+                p.keys.push(cmd.key.clone());
+                p.intents.push(vec![]);
+                p.keys.len() - 1
+            }
+        };
+
+        pos.push((gid, ki, p.intents[ki].len()));
+        p.intents[ki].push(cmd.clone());
+    }
+
+    let primary_gid = pos[0].0;
+
+    // phase 1
+
+    let votes = join_all(parts.values().map(|p| p.prepare(&txn, &all_keys))).await;
+
+    let primary = &parts[&primary_gid];
+
+    if votes.iter().any(|x| !x) {
+        abort_all(&parts, primary, &txn).await;
+        return Err(ReplicationError::TxnAborted(txn.id));
+    }
+
+    // phase 2: commit in the primary group decides the transaction.
+
+    let rsts = primary.commit(&txn).await?;
+    if rsts.iter().any(|x| x == &ExecuteResult::Aborted) {
+        // aborted by recovery
+        abort_all(&parts, primary, &txn).await;
+        return Err(ReplicationError::TxnAborted(txn.id));
+    }
+
+    let mut results = BTreeMap::new();
+    results.insert(primary_gid, rsts);
+
+    let others: Vec<_> = parts
+        .iter()
+        .filter(|(gid, _)| **gid != primary_gid)
+        .collect();
+    let rsts = join_all(others.iter().map(|(_, p)| p.commit(&txn))).await;
+
+    for ((gid, p), rst) in others.iter().zip(rsts) {
+        match rst {
+            Ok(v) => {
+                results.insert(**gid, v);
+            }
+            Err(e) => {
+                // committed, but the results are unknown. The intents are committed by
+                // recovery later.
+                println!("{:?} while commit txn in group of {}", e, p.replica_id());
+            }
+        }
+    }
+
+    if results.len() == parts.len() {
+        primary.forget(&txn).await;
+    }
+
+    let mut rsts = vec![];
+    for (gid, ki, seq) in pos.iter() {
+        let p = &parts[gid];
+        let offset: usize = p.intents[..*ki].iter().map(|x| x.len()).sum();
+
+        let r = match results.get(gid) {
+            Some(v) => v[offset + seq].clone(),
+            None => ExecuteResult::Error("ERR transaction is committed but pending".to_owned()),
+        };
+        rsts.push(r);
+    }
+
+    Ok(rsts)
+}
+
+/// abort_all aborts the intents in every group, and then removes the decision if all of them
+/// are aborted.
+async fn abort_all(
+    parts: &BTreeMap<ReplicaId, Participant<'_>>,
+    primary: &Participant<'_>,
+    txn: &TxnInfo,
+) {
+    let oks = join_all(parts.values().map(|p| p.abort(txn))).await;
+    if oks.iter().all(|x| *x) {
+        primary.forget(txn).await;
+    }
+}
@@ -0,0 +1,32 @@
+//! txn implements transactions spanning several groups with two-phase commit.
+//!
+//! A transaction is coordinated by the node receiving it, over QPaxos instances of every
+//! participant group, i.e., every group serving some of its keys:
+//!
+//! 1. Prepare: an instance of `TxnPrepare` commands is proposed in every group. When executed,
+//!    it stores an intent on every key, which locks the key, or votes no if it could not.
+//! 2. Commit or abort: if every group votes yes, an instance of `TxnCommit` commands is proposed
+//!    in the primary group, i.e., the group serving the first key, then in other groups.
+//!    Otherwise `TxnAbort` is proposed.
+//!
+//! The decision is recorded by the primary group when the first `TxnCommit` or `TxnAbort` on
+//! the primary key is executed, thus it is durable once the primary group executed it.
+//!
+//! The commands of a group without a replica on the coordinator node are sent to a replica of the
+//! group on another node with the `propose` RPC, which proposes them on behalf of the coordinator.
+//!
+//! The decision is removed by a `TxnForget` in the primary group once the intent of every key is
+//! resolved.
+//!
+//! If the coordinator crashes, the intents left are found by `recover_txns`, which aborts the
+//! transaction in the primary group unless it has been committed, and then applies the decision
+//! to the intent of every key of the transaction, which is recorded in every intent.
+
+mod coordinator;
+pub use coordinator::*;
+
+mod recovery;
+pub use recovery::*;
+
+#[cfg(test)]
+mod test_recovery;
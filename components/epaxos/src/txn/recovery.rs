@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use storage::DBColumnFamily;
+use storage::StorageError;
+
+use crate::qpaxos::OpCode;
+use crate::qpaxos::TxnInfo;
+use crate::qpaxos::TxnIntent;
+use crate::replica::ExecuteResult;
+use crate::replica::StatusRecords;
+use crate::replica::TXN_COMMIT;
+use crate::replica::TXN_INTENT_PREFIX;
+use crate::replication::propose;
+use crate::txn::propose_for_key;
+use crate::txn::txn_cmd;
+use crate::ReplicationError;
+use crate::ServerData;
+
+/// default age of an intent, after which its coordinator is considered crashed.
+pub const DEFAULT_TXN_TIMEOUT: Duration = Duration::from_millis(5_000);
+
+/// IntentAges records when this node first sees every intent, by the key and the transaction id.
+/// The age of an intent is measured with the local clock since then, other than with `ctime_ms`,
+/// which is on the clock of the coordinator.
+#[derive(Debug, Default)]
+pub struct IntentAges {
+    seen: Mutex<HashMap<(Vec<u8>, Vec<u8>), Instant>>,
+}
+
+impl IntentAges {
+    /// older_than returns the intents in `intents` that have been seen for more than `timeout`.
+    /// An intent not seen before is recorded as seen now, and an intent not in `intents` any
+    /// more is forgotten.
+    pub fn older_than(
+        &self,
+        intents: Vec<(Vec<u8>, TxnIntent)>,
+        timeout: Duration,
+    ) -> Vec<(Vec<u8>, TxnIntent)> {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        let mut next = HashMap::new();
+
+        let mut rst = vec![];
+        for (key, intent) in intents {
+            let txn_id = intent
+                .txn
+                .as_ref()
+                .map(|x| x.id.clone())
+                .unwrap_or_default();
+            let k = (key.clone(), txn_id);
+
+            let t = seen.get(&k).copied().unwrap_or(now);
+            next.insert(k, t);
+
+            if now.duration_since(t) >= timeout {
+                rst.push((key, intent));
+            }
+        }
+
+        *seen = next;
+        rst
+    }
+}
+
+/// list_intents returns the intents stored on this node, along with the keys they lock.
+/// Only intents on keys recovered by this node are returned, i.e., keys of a group of which this
+/// node hosts the replica with the smallest id, thus an intent is recovered by only one node.
+pub fn list_intents(sd: &ServerData) -> Result<Vec<(Vec<u8>, TxnIntent)>, StorageError> {
+    let mut rst = vec![];
+    let mut k = TXN_INTENT_PREFIX.to_vec();
+    while let Some((ik, v)) = sd.storage.next(DBColumnFamily::Status, &k, false) {
+        if !ik.starts_with(TXN_INTENT_PREFIX) {
+            break;
+        }
+
+        let key = ik[TXN_INTENT_PREFIX.len()..].to_vec();
+        k = ik;
+
+        if !is_recoverer(sd, &key) {
+            continue;
+        }
+
+        let intent: TxnIntent = prost::Message::decode(&v[..])?;
+        rst.push((key, intent));
+    }
+
+    Ok(rst)
+}
+
+/// is_recoverer returns true if the local replica of the group serving `key` has the smallest
+/// replica id in the group.
+fn is_recoverer(sd: &ServerData, key: &[u8]) -> bool {
+    match sd.get_local_replica_for_key(key) {
+        Ok((g, r)) => g.replicas.keys().next() == Some(&r.replica_id),
+        Err(_) => false,
+    }
+}
+
+/// recover_txns resolves the intents left by coordinators that are considered crashed, i.e.,
+/// intents seen by this node for more than `timeout`, as recorded in `ages`.
+///
+/// For every such intent it aborts the transaction in the primary group, which is a no-op if
+/// the transaction has been committed, then commits or aborts the intent of every key of the
+/// transaction according to the decision the primary group replies. At last the decision is
+/// removed if every intent is resolved.
+///
+/// The primary group is asked through a replica on another node if this node hosts none of it.
+/// The intent of a key served by a group not hosted by this node is left to a node hosting it,
+/// which finds the intent and asks the primary group for the decision too, thus the decision is
+/// left in this case.
+///
+/// It returns the errors encountered. An intent failed to resolve is retried in the next call.
+pub async fn recover_txns(
+    sd: &ServerData,
+    ages: &IntentAges,
+    timeout: Duration,
+) -> Vec<ReplicationError> {
+    let intents = match list_intents(sd) {
+        Ok(v) => v,
+        Err(e) => return vec![e.into()],
+    };
+
+    let mut errs = vec![];
+    let mut done = HashSet::new();
+
+    for (key, intent) in ages.older_than(intents, timeout) {
+        // all intents of a transaction are resolved along with the first one found.
+        let txn_id = intent.txn.as_ref().map(|x| x.id.clone());
+        if !done.insert(txn_id) {
+            continue;
+        }
+
+        if let Err(e) = recover_intent(sd, &key, &intent).await {
+            errs.push(e);
+        }
+    }
+
+    errs
+}
+
+async fn recover_intent(
+    sd: &ServerData,
+    key: &[u8],
+    intent: &TxnIntent,
+) -> Result<(), ReplicationError> {
+    let txn = match intent.txn {
+        Some(ref v) => v,
+        None => return Ok(()),
+    };
+
+    let cmd = txn_cmd(OpCode::TxnAbort, &txn.primary, &[], txn, 0);
+    let rsts = propose_for_key(sd, &txn.primary, &[cmd]).await?;
+
+    let committed = match rsts.get(0) {
+        Some(ExecuteResult::SuccessWithVal { value }) => value.as_deref() == Some(TXN_COMMIT),
+        _ => false,
+    };
+
+    // an intent written without `keys` only knows its own key, and the decision is kept.
+    let keys = if intent.keys.is_empty() {
+        vec![key.to_vec()]
+    } else {
+        intent.keys.clone()
+    };
+
+    let mut all = !intent.keys.is_empty();
+    let mut rst = Ok(());
+    for k in keys.iter() {
+        // the intent on the primary key is removed in the same instance the decision is recorded.
+        if k == &txn.primary {
+            continue;
+        }
+
+        if sd.get_remote_node_for_key(k)?.is_some() {
+            all = false;
+            continue;
+        }
+
+        // other keys are still resolved, and the error is returned at last.
+        if let Err(e) = resolve_intent(sd, k, txn, committed).await {
+            all = false;
+            rst = Err(e);
+        }
+    }
+
+    if all {
+        let cmd = txn_cmd(OpCode::TxnForget, &txn.primary, &[], txn, 0);
+        propose_for_key(sd, &txn.primary, &[cmd]).await?;
+    }
+
+    rst
+}
+
+/// resolve_intent commits or aborts the intent of `txn` on `key`, if it is not yet resolved.
+async fn resolve_intent(
+    sd: &ServerData,
+    key: &[u8],
+    txn: &TxnInfo,
+    committed: bool,
+) -> Result<(), ReplicationError> {
+    let (g, r) = sd.get_local_replica_for_key(key)?;
+
+    let mut recs = StatusRecords::new();
+    let intent = match r.get_intent(key, &mut recs)? {
+        Some(v) if v.txn.as_ref().map(|x| &x.id) == Some(&txn.id) => v,
+        _ => return Ok(()),
+    };
+
+    let cmds: Vec<_> = if committed {
+        (0..intent.cmds.len())
+            .map(|seq| txn_cmd(OpCode::TxnCommit, key, &[], txn, seq as i64))
+            .collect()
+    } else {
+        vec![txn_cmd(OpCode::TxnAbort, key, &[], txn, 0)]
+    };
+
+    propose(&cmds, g, r).await?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::qpaxos::TxnInfo;
+use crate::qpaxos::TxnIntent;
+use crate::txn::IntentAges;
+
+fn intent(key: &str, txn_id: &str) -> (Vec<u8>, TxnIntent) {
+    let intent = TxnIntent {
+        txn: Some(TxnInfo {
+            id: txn_id.as_bytes().to_vec(),
+            // the clock of the coordinator is not used.
+            ctime_ms: 1,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    (key.as_bytes().to_vec(), intent)
+}
+
+#[test]
+fn test_intent_ages() {
+    let ages = IntentAges::default();
+    let timeout = Duration::from_millis(100);
+
+    let keys =
+        |v: Vec<(Vec<u8>, TxnIntent)>| -> Vec<Vec<u8>> { v.into_iter().map(|x| x.0).collect() };
+
+    // just seen.
+    let rst = ages.older_than(vec![intent("a", "t1"), intent("b", "t1")], timeout);
+    assert!(rst.is_empty());
+
+    sleep(Duration::from_millis(150));
+
+    // c is seen the first time.
+    let rst = ages.older_than(
+        vec![intent("a", "t1"), intent("b", "t1"), intent("c", "t2")],
+        timeout,
+    );
+    assert_eq!(vec![b"a".to_vec(), b"b".to_vec()], keys(rst));
+
+    // b is resolved and then locked by another transaction.
+    let rst = ages.older_than(vec![intent("b", "t3"), intent("c", "t2")], timeout);
+    assert!(rst.is_empty());
+
+    sleep(Duration::from_millis(150));
+
+    let rst = ages.older_than(vec![intent("b", "t3"), intent("c", "t2")], timeout);
+    assert_eq!(vec![b"b".to_vec(), b"c".to_vec()], keys(rst));
+
+    // a zero timeout returns every intent at once.
+    let rst = ages.older_than(vec![intent("d", "t4")], Duration::from_millis(0));
+    assert_eq!(vec![b"d".to_vec()], keys(rst));
+}
@@ -60,6 +60,15 @@ async fn _repl_server() {
 
     println!("RESPONSE={:?}", response);
 
+    // propose to a replica not on the server.
+    let request = qp::ProposeRequest {
+        to_replica_id: 100,
+        cmds: vec![qp::Command::from(("Set", "x", "1"))],
+    };
+    let response = client.propose(request).await.unwrap().into_inner();
+    assert!(response.err.is_some());
+    assert!(response.results.is_empty());
+
     // shut up or shut down?:)
     let _ = tx.send(());
 }
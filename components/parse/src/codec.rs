@@ -0,0 +1,181 @@
+use std::str::from_utf8;
+
+use bytes::Buf;
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+
+use crate::ParseError;
+use crate::ProtocolVersion;
+use crate::Response;
+
+/// max length of an inline command, same as redis.
+pub const MAX_INLINE_LEN: usize = 64 * 1024;
+
+/// max length of a bulk string, same as redis.
+pub const MAX_BULK_LEN: i64 = 512 * 1024 * 1024;
+
+/// max number of elements in a multi-bulk request, same as redis.
+pub const MAX_MULTIBULK_LEN: i64 = 1024 * 1024;
+
+/// RespCodec decodes redis requests from a byte stream and encodes `Response` into it.
+///
+/// A request is either a RESP array of bulk strings, e.g. `*2\r\n$3\r\nGET\r\n$1\r\nx\r\n`,
+/// or an inline command, e.g. `GET x\r\n`. It is decoded into a vector of tokens.
+///
+/// A partial frame is left in the buffer until more data arrives, and several pipelined requests
+/// in one buffer are decoded one by one.
+///
+/// A `Response` is encoded with `proto`, which is changed when a client negotiates another
+/// protocol version.
+#[derive(Debug, Default)]
+pub struct RespCodec {
+    pub proto: ProtocolVersion,
+}
+
+impl RespCodec {
+    pub fn new() -> Self {
+        RespCodec {
+            proto: ProtocolVersion::Resp2,
+        }
+    }
+}
+
+impl Decoder for RespCodec {
+    type Item = Vec<Vec<u8>>;
+    type Error = ParseError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let (tokens, n) = match parse_request(&src[..])? {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+
+            src.advance(n);
+
+            // an empty request such as a blank line is ignored.
+            if tokens.len() > 0 {
+                return Ok(Some(tokens));
+            }
+        }
+    }
+}
+
+impl Encoder for RespCodec {
+    type Item = Response;
+    type Error = ParseError;
+
+    fn encode(&mut self, item: Response, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&item.encode(self.proto));
+        Ok(())
+    }
+}
+
+/// parse_request parses one request at the start of `buf`.
+/// It returns the tokens and the number of bytes consumed, or None if `buf` does not yet contain
+/// a complete request.
+pub fn parse_request(buf: &[u8]) -> Result<Option<(Vec<Vec<u8>>, usize)>, ParseError> {
+    if buf.len() == 0 {
+        return Ok(None);
+    }
+
+    if buf[0] == b'*' {
+        parse_multibulk(buf)
+    } else {
+        parse_inline(buf)
+    }
+}
+
+fn parse_inline(buf: &[u8]) -> Result<Option<(Vec<Vec<u8>>, usize)>, ParseError> {
+    let (line, n) = match read_line(buf, 0, MAX_INLINE_LEN, "too big inline request")? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let tokens = line
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|x| x.len() > 0)
+        .map(|x| x.to_vec())
+        .collect();
+
+    Ok(Some((tokens, n)))
+}
+
+fn parse_multibulk(buf: &[u8]) -> Result<Option<(Vec<Vec<u8>>, usize)>, ParseError> {
+    let (line, mut pos) = match read_line(buf, 0, MAX_INLINE_LEN, "too big mbulk count string")? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let n = parse_int(&line[1..], "invalid multibulk length")?;
+    if n > MAX_MULTIBULK_LEN {
+        return Err("invalid multibulk length".into());
+    }
+
+    let mut tokens = vec![];
+
+    for _ in 0..n {
+        let (line, p) = match read_line(buf, pos, MAX_INLINE_LEN, "too big bulk count string")? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        if line.len() == 0 || line[0] != b'$' {
+            let got = String::from_utf8_lossy(&line[..line.len().min(1)]);
+            return Err(ParseError::Protocol(format!("expected '$', got '{}'", got)));
+        }
+
+        let l = parse_int(&line[1..], "invalid bulk length")?;
+        if l < 0 || l > MAX_BULK_LEN {
+            return Err("invalid bulk length".into());
+        }
+
+        let l = l as usize;
+        if buf.len() < p + l + 2 {
+            return Ok(None);
+        }
+
+        if &buf[p + l..p + l + 2] != b"\r\n" {
+            return Err("bulk string is not terminated by CRLF".into());
+        }
+
+        tokens.push(buf[p..p + l].to_vec());
+        pos = p + l + 2;
+    }
+
+    Ok(Some((tokens, pos)))
+}
+
+/// read_line returns the line starting at `start` without the trailing CRLF, and the position
+/// after the CRLF.
+fn read_line<'a>(
+    buf: &'a [u8],
+    start: usize,
+    max: usize,
+    too_big: &str,
+) -> Result<Option<(&'a [u8], usize)>, ParseError> {
+    let b = &buf[start..];
+
+    match b.iter().position(|x| *x == b'\n') {
+        Some(i) => {
+            let line = if i > 0 && b[i - 1] == b'\r' {
+                &b[..i - 1]
+            } else {
+                &b[..i]
+            };
+            Ok(Some((line, start + i + 1)))
+        }
+        None => {
+            if b.len() > max {
+                return Err(too_big.into());
+            }
+            Ok(None)
+        }
+    }
+}
+
+fn parse_int(s: &[u8], errmsg: &str) -> Result<i64, ParseError> {
+    let s = from_utf8(s).or(Err(ParseError::from(errmsg)))?;
+    s.parse::<i64>().or(Err(ParseError::from(errmsg)))
+}
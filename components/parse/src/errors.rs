@@ -0,0 +1,21 @@
+use std::io;
+
+quick_error! {
+    /// ParseError is an error encountered when decoding redis protocol.
+    #[derive(Debug)]
+    pub enum ParseError {
+        /// Malformed input that is not a valid redis protocol frame.
+        Protocol(msg: String) {
+            display("Protocol error: {}", msg)
+        }
+        IO(e: io::Error) {
+            from(e: io::Error) -> (e)
+        }
+    }
+}
+
+impl From<&str> for ParseError {
+    fn from(msg: &str) -> Self {
+        ParseError::Protocol(msg.to_string())
+    }
+}
@@ -1,10 +1,42 @@
+#[macro_use]
+extern crate quick_error;
+
 use std::fmt::Debug;
 
+mod errors;
+pub use errors::*;
+
+mod codec;
+pub use codec::*;
+
+#[cfg(test)]
+mod test_codec;
+
+#[cfg(test)]
+mod test_response;
+
+/// ProtocolVersion is the version of redis protocol a connection speaks.
+/// A connection starts with RESP2 and switches to RESP3 with `HELLO 3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    Resp2 = 2,
+    Resp3 = 3,
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        ProtocolVersion::Resp2
+    }
+}
+
 /// A command response to send to a client
 #[derive(PartialEq, Debug)]
 pub enum Response {
     /// No data
     Nil,
+    /// A null array, e.g., the reply of an EXEC aborted by WATCH. It is the same as `Nil` in
+    /// RESP3.
+    NilArray,
     /// A number
     Integer(i64),
     /// Binary data
@@ -15,20 +47,49 @@ pub enum Response {
     Status(String),
     /// An array of responses that may mix different types
     Array(Vec<Response>),
+    /// RESP3: an ordered list of key-value pairs. It is a flat array of keys and values in RESP2.
+    Map(Vec<(Response, Response)>),
+    /// RESP3: an unordered collection of elements. It is an array in RESP2.
+    Set(Vec<Response>),
+    /// RESP3: a floating point number. It is a bulk string in RESP2.
+    Double(f64),
+    /// RESP3: true or false. It is integer 1 or 0 in RESP2.
+    Boolean(bool),
+    /// RESP3: an integer out of the range of i64, in decimal. It is a bulk string in RESP2.
+    BigNumber(String),
+    /// RESP3: a string with a 3-byte format, e.g. "txt" or "mkd". It is a bulk string in RESP2.
+    Verbatim(String, Vec<u8>),
+    /// RESP3: out of band data pushed to a client. It is an array in RESP2.
+    Push(Vec<Response>),
 }
 
 impl Response {
     /// Serializes the response into an array of bytes using Redis protocol.
     pub fn as_bytes(&self) -> Vec<u8> {
+        self.encode(ProtocolVersion::Resp2)
+    }
+
+    /// Serializes the response with the specified protocol version.
+    /// A RESP3 type is converted to the closest RESP2 type if `ver` is RESP2.
+    pub fn encode(&self, ver: ProtocolVersion) -> Vec<u8> {
+        let resp3 = ver == ProtocolVersion::Resp3;
+
         return match *self {
-            Response::Nil => b"$-1\r\n".to_vec(),
-            Response::Data(ref d) => [
-                &b"$"[..],
-                &format!("{}\r\n", d.len()).into_bytes()[..],
-                &d[..],
-                &"\r\n".to_owned().into_bytes()[..],
-            ]
-            .concat(),
+            Response::Nil => {
+                if resp3 {
+                    b"_\r\n".to_vec()
+                } else {
+                    b"$-1\r\n".to_vec()
+                }
+            }
+            Response::NilArray => {
+                if resp3 {
+                    b"_\r\n".to_vec()
+                } else {
+                    b"*-1\r\n".to_vec()
+                }
+            }
+            Response::Data(ref d) => bulk(b'$', d),
             Response::Integer(ref i) => {
                 [&b":"[..], &format!("{}\r\n", i).into_bytes()[..]].concat()
             }
@@ -44,12 +105,57 @@ impl Response {
                 &"\r\n".to_owned().into_bytes()[..],
             ]
             .concat(),
-            Response::Array(ref a) => [
-                &b"*"[..],
-                &format!("{}\r\n", a.len()).into_bytes()[..],
-                &(a.iter().map(|el| el.as_bytes()).collect::<Vec<_>>()[..].concat())[..],
-            ]
-            .concat(),
+            Response::Array(ref a) => aggregate(b'*', a.iter(), a.len(), ver),
+            Response::Map(ref m) => {
+                let elts = m.iter().flat_map(|(k, v)| vec![k, v]);
+                if resp3 {
+                    aggregate(b'%', elts, m.len(), ver)
+                } else {
+                    aggregate(b'*', elts, m.len() * 2, ver)
+                }
+            }
+            Response::Set(ref a) => {
+                aggregate(if resp3 { b'~' } else { b'*' }, a.iter(), a.len(), ver)
+            }
+            Response::Push(ref a) => {
+                aggregate(if resp3 { b'>' } else { b'*' }, a.iter(), a.len(), ver)
+            }
+            Response::Double(ref f) => {
+                let s = if f.is_nan() {
+                    "nan".to_owned()
+                } else if f.is_infinite() {
+                    if *f > 0.0 { "inf" } else { "-inf" }.to_owned()
+                } else {
+                    format!("{}", f)
+                };
+
+                if resp3 {
+                    [&b","[..], s.as_bytes(), &b"\r\n"[..]].concat()
+                } else {
+                    bulk(b'$', s.as_bytes())
+                }
+            }
+            Response::Boolean(ref b) => {
+                if resp3 {
+                    if *b { b"#t\r\n" } else { b"#f\r\n" }.to_vec()
+                } else {
+                    Response::Integer(*b as i64).encode(ver)
+                }
+            }
+            Response::BigNumber(ref n) => {
+                if resp3 {
+                    [&b"("[..], n.as_bytes(), &b"\r\n"[..]].concat()
+                } else {
+                    bulk(b'$', n.as_bytes())
+                }
+            }
+            Response::Verbatim(ref format, ref text) => {
+                if resp3 {
+                    bulk(b'=', &[format.as_bytes(), &b":"[..], &text[..]].concat())
+                } else {
+                    bulk(b'$', text)
+                }
+            }
         };
     }
 
@@ -71,3 +177,30 @@ impl Response {
         }
     }
 }
+
+/// bulk encodes a length-prefixed string, such as a bulk string or a verbatim string.
+fn bulk(prefix: u8, d: &[u8]) -> Vec<u8> {
+    [
+        &[prefix][..],
+        &format!("{}\r\n", d.len()).into_bytes()[..],
+        d,
+        &b"\r\n"[..],
+    ]
+    .concat()
+}
+
+/// aggregate encodes an aggregate type with `n` as the number of elements, such as an array or a
+/// map.
+fn aggregate<'a>(
+    prefix: u8,
+    elts: impl Iterator<Item = &'a Response>,
+    n: usize,
+    ver: ProtocolVersion,
+) -> Vec<u8> {
+    let mut buf = vec![prefix];
+    buf.extend_from_slice(&format!("{}\r\n", n).into_bytes());
+    for e in elts {
+        buf.extend_from_slice(&e.encode(ver));
+    }
+    buf
+}
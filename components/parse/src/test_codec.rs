@@ -0,0 +1,130 @@
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+
+use crate::*;
+
+fn toks(v: &[&str]) -> Vec<Vec<u8>> {
+    v.iter().map(|x| x.as_bytes().to_vec()).collect()
+}
+
+#[test]
+fn test_decode_multibulk() {
+    let mut c = RespCodec::new();
+    let mut buf = BytesMut::from(&b"*3\r\n$3\r\nSET\r\n$1\r\nx\r\n$0\r\n\r\n"[..]);
+
+    let got = c.decode(&mut buf).unwrap();
+    assert_eq!(Some(toks(&["SET", "x", ""])), got);
+    assert_eq!(0, buf.len());
+
+    let got = c.decode(&mut buf).unwrap();
+    assert_eq!(None, got);
+}
+
+#[test]
+fn test_decode_binary_value() {
+    let mut c = RespCodec::new();
+    let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n$4\r\na\r\nb\r\n"[..]);
+
+    let got = c.decode(&mut buf).unwrap();
+    assert_eq!(Some(vec![b"GET".to_vec(), b"a\r\nb".to_vec()]), got);
+}
+
+#[test]
+fn test_decode_inline() {
+    let mut c = RespCodec::new();
+    let mut buf = BytesMut::from(&b"\r\nGET  x\r\nPING\n"[..]);
+
+    assert_eq!(Some(toks(&["GET", "x"])), c.decode(&mut buf).unwrap());
+    assert_eq!(Some(toks(&["PING"])), c.decode(&mut buf).unwrap());
+    assert_eq!(None, c.decode(&mut buf).unwrap());
+}
+
+#[test]
+fn test_decode_partial() {
+    let req = b"*2\r\n$3\r\nGET\r\n$3\r\nfoo\r\n";
+
+    // feed one byte a time: no frame is returned until the last byte arrives.
+    let mut c = RespCodec::new();
+    let mut buf = BytesMut::new();
+    for (i, b) in req.iter().enumerate() {
+        buf.extend_from_slice(&[*b]);
+        let got = c.decode(&mut buf).unwrap();
+        if i < req.len() - 1 {
+            assert_eq!(None, got, "i={}", i);
+        } else {
+            assert_eq!(Some(toks(&["GET", "foo"])), got);
+        }
+    }
+    assert_eq!(0, buf.len());
+}
+
+#[test]
+fn test_decode_pipelined() {
+    let mut c = RespCodec::new();
+    let mut buf =
+        BytesMut::from(&b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGET\r\n$1\r\nx\r\n*2\r\n$3"[..]);
+
+    assert_eq!(Some(toks(&["PING"])), c.decode(&mut buf).unwrap());
+    assert_eq!(Some(toks(&["GET", "x"])), c.decode(&mut buf).unwrap());
+    assert_eq!(None, c.decode(&mut buf).unwrap());
+
+    // the partial frame is kept.
+    assert_eq!(&b"*2\r\n$3"[..], &buf[..]);
+}
+
+#[test]
+fn test_decode_error() {
+    let cases: Vec<(&[u8], &str)> = vec![
+        (b"*x\r\n", "Protocol error: invalid multibulk length"),
+        (b"*1\r\n$x\r\n", "Protocol error: invalid bulk length"),
+        (b"*1\r\n$-2\r\n", "Protocol error: invalid bulk length"),
+        (b"*1\r\n+OK\r\n", "Protocol error: expected '$', got '+'"),
+        (
+            b"*1\r\n$1\r\nabc\r\n",
+            "Protocol error: bulk string is not terminated by CRLF",
+        ),
+    ];
+
+    for (input, want) in cases.iter() {
+        let mut c = RespCodec::new();
+        let mut buf = BytesMut::from(&input[..]);
+        let got = c.decode(&mut buf);
+        match got {
+            Err(e) => assert_eq!(*want, format!("{}", e)),
+            Ok(v) => panic!("expect error but: {:?}", v),
+        }
+    }
+}
+
+#[test]
+fn test_decode_too_big_inline() {
+    let mut c = RespCodec::new();
+    let mut buf = BytesMut::from(&vec![b'a'; MAX_INLINE_LEN + 1][..]);
+
+    let got = c.decode(&mut buf);
+    assert!(got.is_err());
+}
+
+#[test]
+fn test_encode() {
+    let mut c = RespCodec::new();
+    let mut buf = BytesMut::new();
+
+    c.encode(Response::Status("OK".into()), &mut buf).unwrap();
+    c.encode(Response::Nil, &mut buf).unwrap();
+
+    assert_eq!(&b"+OK\r\n$-1\r\n"[..], &buf[..]);
+}
+
+#[test]
+fn test_encode_resp3() {
+    let mut c = RespCodec::new();
+    c.proto = ProtocolVersion::Resp3;
+    let mut buf = BytesMut::new();
+
+    c.encode(Response::Nil, &mut buf).unwrap();
+    c.encode(Response::Boolean(true), &mut buf).unwrap();
+
+    assert_eq!(&b"_\r\n#t\r\n"[..], &buf[..]);
+}
@@ -0,0 +1,90 @@
+use crate::ProtocolVersion::*;
+use crate::*;
+
+#[test]
+fn test_encode_resp2() {
+    let cases: Vec<(Response, &[u8])> = vec![
+        (Response::Nil, b"$-1\r\n"),
+        (Response::NilArray, b"*-1\r\n"),
+        (Response::Integer(-3), b":-3\r\n"),
+        (Response::Data(b"ab".to_vec()), b"$2\r\nab\r\n"),
+        (Response::Error("ERR x".into()), b"-ERR x\r\n"),
+        (Response::Status("OK".into()), b"+OK\r\n"),
+        (
+            Response::Array(vec![Response::Integer(1), Response::Nil]),
+            b"*2\r\n:1\r\n$-1\r\n",
+        ),
+        (
+            Response::Map(vec![(Response::Status("a".into()), Response::Integer(1))]),
+            b"*2\r\n+a\r\n:1\r\n",
+        ),
+        (Response::Set(vec![Response::Integer(1)]), b"*1\r\n:1\r\n"),
+        (Response::Double(1.5), b"$3\r\n1.5\r\n"),
+        (Response::Double(std::f64::INFINITY), b"$3\r\ninf\r\n"),
+        (Response::Boolean(true), b":1\r\n"),
+        (Response::Boolean(false), b":0\r\n"),
+        (
+            Response::BigNumber("12345678901234567890".into()),
+            b"$20\r\n12345678901234567890\r\n",
+        ),
+        (
+            Response::Verbatim("txt".into(), b"hi".to_vec()),
+            b"$2\r\nhi\r\n",
+        ),
+        (Response::Push(vec![Response::Integer(1)]), b"*1\r\n:1\r\n"),
+    ];
+
+    for (resp, want) in cases.iter() {
+        assert_eq!(want.to_vec(), resp.encode(Resp2), "{:?}", resp);
+        assert_eq!(want.to_vec(), resp.as_bytes(), "{:?}", resp);
+    }
+}
+
+#[test]
+fn test_encode_resp3() {
+    let cases: Vec<(Response, &[u8])> = vec![
+        (Response::Nil, b"_\r\n"),
+        (Response::NilArray, b"_\r\n"),
+        (Response::Integer(-3), b":-3\r\n"),
+        (Response::Data(b"ab".to_vec()), b"$2\r\nab\r\n"),
+        (Response::Error("ERR x".into()), b"-ERR x\r\n"),
+        (Response::Status("OK".into()), b"+OK\r\n"),
+        (
+            Response::Array(vec![Response::Integer(1), Response::Nil]),
+            b"*2\r\n:1\r\n_\r\n",
+        ),
+        (
+            Response::Map(vec![
+                (Response::Status("a".into()), Response::Integer(1)),
+                (Response::Status("b".into()), Response::Boolean(true)),
+            ]),
+            b"%2\r\n+a\r\n:1\r\n+b\r\n#t\r\n",
+        ),
+        (
+            Response::Set(vec![Response::Integer(1), Response::Integer(2)]),
+            b"~2\r\n:1\r\n:2\r\n",
+        ),
+        (Response::Double(1.5), b",1.5\r\n"),
+        (Response::Double(std::f64::INFINITY), b",inf\r\n"),
+        (Response::Double(std::f64::NEG_INFINITY), b",-inf\r\n"),
+        (Response::Double(std::f64::NAN), b",nan\r\n"),
+        (Response::Boolean(true), b"#t\r\n"),
+        (Response::Boolean(false), b"#f\r\n"),
+        (
+            Response::BigNumber("12345678901234567890".into()),
+            b"(12345678901234567890\r\n",
+        ),
+        (
+            Response::Verbatim("txt".into(), b"hi".to_vec()),
+            b"=6\r\ntxt:hi\r\n",
+        ),
+        (
+            Response::Push(vec![Response::Data(b"msg".to_vec())]),
+            b">1\r\n$3\r\nmsg\r\n",
+        ),
+    ];
+
+    for (resp, want) in cases.iter() {
+        assert_eq!(want.to_vec(), resp.encode(Resp3), "{:?}", resp);
+    }
+}
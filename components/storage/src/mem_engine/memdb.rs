@@ -68,16 +68,29 @@ impl Base for MemEngine {
         None
     }
 
-    // TODO now just execute these commands in order
+    /// write_batch applies all entries under one lock, thus a reader sees either none or all
+    /// of them.
     fn write_batch(&self, entrys: &Vec<WriteEntry>) -> Result<(), StorageError> {
+        let mut db = self._db.lock().unwrap();
+
         for en in entrys {
             match en {
                 WriteEntry::Nil => {}
                 WriteEntry::Set(cf, k, v) => {
-                    self.set(*cf, k, v).unwrap();
+                    let bt = db.entry((*cf).into()).or_insert(BTreeMap::new());
+                    bt.insert(k.clone(), v.clone());
                 }
                 WriteEntry::Delete(cf, k) => {
-                    self.delete(*cf, k).unwrap();
+                    let bt = db.entry((*cf).into()).or_insert(BTreeMap::new());
+                    bt.remove(k);
+                }
+                WriteEntry::DeleteRange(cf, start, end) => {
+                    let bt = db.entry((*cf).into()).or_insert(BTreeMap::new());
+                    let mut removed = bt.split_off(start);
+                    if let Some(e) = end {
+                        let mut after = removed.split_off(e);
+                        bt.append(&mut after);
+                    }
                 }
             }
         }
@@ -90,6 +103,7 @@ impl Base for MemEngine {
 mod tests {
     use crate::test_engine::*;
     use crate::*;
+    use std::sync::Arc;
 
     #[test]
     fn test_engine() {
@@ -112,5 +126,15 @@ mod tests {
             let eng = MemEngine::new().unwrap();
             test_instance_trait(&eng);
         }
+
+        {
+            let eng = MemEngine::new().unwrap();
+            test_write_batch_atomic(Arc::new(eng));
+        }
+
+        {
+            let eng = MemEngine::new().unwrap();
+            test_delete_range(&eng);
+        }
     }
 }
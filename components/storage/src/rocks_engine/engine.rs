@@ -4,6 +4,11 @@ use crate::WriteEntry;
 use crate::{Base, RocksDBEngine, StorageError};
 use rocksdb::{CFHandle, SeekKey, Writable, WriteBatch};
 
+/// UNBOUNDED_END is the end of a DeleteRange without one, since rocksdb requires a bounded
+/// range. Every key is less than it, except keys starting with it, which are rare and are
+/// removed one by one.
+const UNBOUNDED_END: [u8; 32] = [0xff; 32];
+
 impl RocksDBEngine {
     /// Open a Engine base on rocksdb to use snapshot.
     ///
@@ -76,6 +81,38 @@ impl RocksDBEngine {
 
         return iter.kv();
     }
+
+    /// _keys_from returns keys in a column family not less than `from`, including keys set by
+    /// `entrys`.
+    fn _keys_from(
+        &self,
+        cf: DBColumnFamily,
+        from: &[u8],
+        entrys: &Vec<WriteEntry>,
+    ) -> Result<Vec<Vec<u8>>, StorageError> {
+        let cfh = self._make_cf_handle(cf)?;
+        let mut iter = self.db.iter_cf(cfh);
+        iter.seek(SeekKey::from(from));
+
+        let mut keys = vec![];
+        while iter.valid() {
+            match iter.kv() {
+                Some(kv) => keys.push(kv.0),
+                None => break,
+            }
+            iter.next();
+        }
+
+        for en in entrys {
+            if let WriteEntry::Set(c, k, _) = en {
+                if *c == cf && &k[..] >= from {
+                    keys.push(k.clone());
+                }
+            }
+        }
+
+        Ok(keys)
+    }
 }
 
 impl Base for RocksDBEngine {
@@ -116,6 +153,26 @@ impl Base for RocksDBEngine {
                     let cfh = self._make_cf_handle(*cf)?;
                     batch.delete_cf(cfh, k)?;
                 }
+                WriteEntry::DeleteRange(cf, start, end) => {
+                    let cfh = self._make_cf_handle(*cf)?;
+
+                    // rocksdb requires a bounded range.
+                    let e = match end {
+                        Some(e) => &e[..],
+                        None => &UNBOUNDED_END[..],
+                    };
+
+                    if &start[..] < e {
+                        batch.delete_range_cf(cfh, start, e)?;
+                    }
+
+                    if end.is_none() {
+                        let from = std::cmp::max(&start[..], e);
+                        for k in self._keys_from(*cf, from, entrys)? {
+                            batch.delete_cf(cfh, &k)?;
+                        }
+                    }
+                }
             }
         }
 
@@ -127,6 +184,7 @@ impl Base for RocksDBEngine {
 mod tests {
     use crate::test_engine::*;
     use crate::*;
+    use std::sync::Arc;
     use tempfile::Builder;
 
     fn new_eng() -> RocksDBEngine {
@@ -157,5 +215,15 @@ mod tests {
             let eng = new_eng();
             test_instance_trait(&eng);
         }
+
+        {
+            let eng = new_eng();
+            test_write_batch_atomic(Arc::new(eng));
+        }
+
+        {
+            let eng = new_eng();
+            test_delete_range(&eng);
+        }
     }
 }
@@ -1,5 +1,7 @@
 use crate::*;
 use prost::Message;
+use std::sync::Arc;
+use std::thread;
 
 #[derive(Clone, PartialEq, Message, Copy, Eq, Ord, PartialOrd, Hash)]
 pub struct TestInstance {
@@ -222,3 +224,135 @@ pub fn test_instance_trait(eng: &dyn InstanceEngine<TestId, TestInstance>) {
     let got = eng.get_instance(TestId { id: 0 }).unwrap();
     assert_eq!(Some(inst), got);
 }
+
+pub fn test_write_batch_atomic(eng: Arc<dyn Base>) {
+    let cf = DBColumnFamily::Default;
+    let ka = "a".as_bytes().to_vec();
+    let kb = "b".as_bytes().to_vec();
+    let kc = "c".as_bytes().to_vec();
+    let kref = "ref".as_bytes().to_vec();
+
+    let val = |i: u64| format!("{:016}", i).into_bytes();
+
+    {
+        // entries of all kinds and column families are applied.
+        eng.set(cf, &kc, &val(0)).unwrap();
+
+        let batch = vec![
+            WriteEntry::Nil,
+            WriteEntry::Set(cf, ka.clone(), val(0)),
+            WriteEntry::Set(cf, kb.clone(), val(0)),
+            WriteEntry::Delete(cf, kc.clone()),
+            WriteEntry::Set(DBColumnFamily::Status, kref.clone(), val(0)),
+        ];
+        eng.write_batch(&batch).unwrap();
+
+        assert_eq!(Some(val(0)), eng.get(cf, &ka).unwrap());
+        assert_eq!(Some(val(0)), eng.get(cf, &kb).unwrap());
+        assert_eq!(None, eng.get(cf, &kc).unwrap());
+        assert_eq!(
+            Some(val(0)),
+            eng.get(DBColumnFamily::Status, &kref).unwrap()
+        );
+    }
+
+    // A writer increases a and b in one batch, a first.
+    // If a batch is not atomic, a reader that reads b after a may see b < a.
+    let n = 1000;
+
+    let writer = {
+        let eng = eng.clone();
+        let (ka, kb, kref) = (ka.clone(), kb.clone(), kref.clone());
+        thread::spawn(move || {
+            for i in 1..=n {
+                let batch = vec![
+                    WriteEntry::Set(cf, ka.clone(), val(i)),
+                    WriteEntry::Set(cf, kb.clone(), val(i)),
+                    WriteEntry::Set(DBColumnFamily::Status, kref.clone(), val(i)),
+                ];
+                eng.write_batch(&batch).unwrap();
+            }
+        })
+    };
+
+    loop {
+        let a = eng.get(cf, &ka).unwrap().unwrap();
+        let b = eng.get(cf, &kb).unwrap().unwrap();
+        let r = eng.get(DBColumnFamily::Status, &kref).unwrap().unwrap();
+
+        assert!(b >= a, "a={:?} b={:?}", a, b);
+        assert!(r >= b, "b={:?} ref={:?}", b, r);
+
+        if a == val(n) {
+            break;
+        }
+    }
+
+    writer.join().unwrap();
+}
+
+pub fn test_delete_range(eng: &dyn Base) {
+    let cf = DBColumnFamily::Default;
+    let k = |s: &str| s.as_bytes().to_vec();
+    let keys = |eng: &dyn Base| {
+        let mut rst = vec![];
+        let mut cur = vec![];
+        while let Some((key, _)) = eng.next(cf, &cur, false) {
+            rst.push(String::from_utf8(key.clone()).unwrap());
+            cur = key;
+        }
+        rst
+    };
+
+    for key in ["a", "b", "bb", "c", "d"].iter() {
+        eng.set(cf, &k(key), &k("v")).unwrap();
+    }
+    eng.set(DBColumnFamily::Status, &k("b"), &k("v")).unwrap();
+
+    {
+        // only keys in the range of the column family are removed.
+        let batch = vec![WriteEntry::DeleteRange(cf, k("b"), Some(k("c")))];
+        eng.write_batch(&batch).unwrap();
+
+        assert_eq!(vec!["a", "c", "d"], keys(eng));
+        assert_eq!(
+            Some(k("v")),
+            eng.get(DBColumnFamily::Status, &k("b")).unwrap()
+        );
+    }
+
+    {
+        // keys set by earlier entries are removed too, and later ones are kept.
+        let batch = vec![
+            WriteEntry::Set(cf, k("x"), k("v")),
+            WriteEntry::DeleteRange(cf, k("c"), None),
+            WriteEntry::Set(cf, k("e"), k("v")),
+        ];
+        eng.write_batch(&batch).unwrap();
+
+        assert_eq!(vec!["a", "e"], keys(eng));
+    }
+
+    {
+        // an empty range removes nothing.
+        let batch = vec![
+            WriteEntry::DeleteRange(cf, k("b"), Some(k("b"))),
+            WriteEntry::DeleteRange(cf, k("f"), None),
+        ];
+        eng.write_batch(&batch).unwrap();
+
+        assert_eq!(vec!["a", "e"], keys(eng));
+    }
+
+    {
+        // keys starting with many 0xff are removed by an unbounded range too.
+        let big = [vec![0xff; 64], k("x")].concat();
+        eng.set(cf, &big, &k("v")).unwrap();
+
+        let batch = vec![WriteEntry::DeleteRange(cf, k("b"), None)];
+        eng.write_batch(&batch).unwrap();
+
+        assert_eq!(None, eng.get(cf, &big).unwrap());
+        assert_eq!(vec!["a"], keys(eng));
+    }
+}
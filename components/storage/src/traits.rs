@@ -3,11 +3,12 @@ use std::fmt::LowerHex;
 use crate::StorageError;
 use prost::Message;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum DBColumnFamily {
     Default,
     Instance,
     Status,
+    Collection,
 }
 
 impl DBColumnFamily {
@@ -16,6 +17,7 @@ impl DBColumnFamily {
             DBColumnFamily::Default,
             DBColumnFamily::Instance,
             DBColumnFamily::Status,
+            DBColumnFamily::Collection,
         ]
     }
 }
@@ -26,6 +28,7 @@ impl From<&DBColumnFamily> for &str {
             DBColumnFamily::Default => return "default",
             DBColumnFamily::Instance => return "instance",
             DBColumnFamily::Status => return "status",
+            DBColumnFamily::Collection => return "collection",
         }
     }
 }
@@ -40,6 +43,10 @@ pub enum WriteEntry {
     Nil,
     Set(DBColumnFamily, Vec<u8>, Vec<u8>),
     Delete(DBColumnFamily, Vec<u8>),
+
+    /// DeleteRange removes keys in `[start, end)`, including keys set by earlier entries in the
+    /// same batch. A `None` end is unbounded.
+    DeleteRange(DBColumnFamily, Vec<u8>, Option<Vec<u8>>),
 }
 
 pub fn make_ref_key<T>(typ: &str, id: T) -> Vec<u8>
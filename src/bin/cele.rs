@@ -1,14 +1,15 @@
 // TODO rename this file, choose a better bin name
 
-use clap::{App, Arg};
+use std::process;
 
-use std::sync::Arc;
+use clap::{App, Arg};
 
 use tokio;
 
+use cele::open_storage;
+use cele::storage_options;
 use cele::Server;
 use epaxos::conf::ClusterInfo;
-use storage::MemEngine;
 
 fn main() {
     // TODO standalone version file.
@@ -29,15 +30,51 @@ fn main() {
                 .takes_value(true)
                 .help("node id for this server. It must be one key of clusterconf.nodes"),
         )
+        .arg(
+            Arg::with_name("storage")
+                .long("storage")
+                .takes_value(true)
+                .possible_values(&["mem", "rocksdb"])
+                .help(
+                    "storage engine, overrides `storage` of the node in cluster config. \
+                     Data in mem is lost when server stops. [default: mem]",
+                ),
+        )
+        .arg(
+            Arg::with_name("data-dir")
+                .long("data-dir")
+                .takes_value(true)
+                .help(
+                    "dir to store data, used by rocksdb storage, \
+                     overrides `data_dir` of the node in cluster config. [default: ./data]",
+                ),
+        )
         .get_matches();
 
     let conffn = matches.value_of("cluster").unwrap();
     let node_id = matches.value_of("id").unwrap();
 
-    let sto = MemEngine::new().unwrap();
-
     let cluster = ClusterInfo::from_file(conffn).unwrap();
-    let server = Server::new(Arc::new(sto), cluster, node_id.into());
+    let node = cluster
+        .get(node_id)
+        .expect("node id not found in cluster config");
+
+    let (engine, data_dir) = storage_options(
+        node,
+        matches.value_of("storage"),
+        matches.value_of("data-dir"),
+    )
+    .unwrap();
+
+    let sto = open_storage(engine, &data_dir).unwrap();
+
+    let server = match Server::new(sto, cluster, node_id.into()) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("failed to load data of node {}: {:?}", node_id, e);
+            process::exit(1);
+        }
+    };
 
     start(server);
     println!("serve returned");
@@ -0,0 +1,174 @@
+use std::str::from_utf8;
+
+use epaxos::conf::key_hash_slot;
+use epaxos::conf::GroupInfo;
+use epaxos::conf::Node;
+use epaxos::ServerData;
+
+use parse::Response;
+
+/// cmd_cluster impl redis-command cluster: `CLUSTER SLOTS|SHARDS|NODES|KEYSLOT key|MYID`.
+/// It exposes the group layout in slot mode, so that cluster-aware clients route a key to a node
+/// hosting its group.
+///
+/// The first replica of a group is reported as the master, and the others as replicas, though
+/// every replica accepts writes.
+pub fn cmd_cluster(sd: &ServerData, tokens: &[Vec<u8>]) -> Result<Response, Response> {
+    if !sd.cluster.is_slot_mode() {
+        return Err(Response::Error(
+            "ERR This instance has cluster support disabled".to_owned(),
+        ));
+    }
+
+    let sub = from_utf8(&tokens[1]).unwrap_or("").to_lowercase();
+
+    let r = match (sub.as_str(), tokens.len()) {
+        ("slots", 2) => cluster_slots(sd),
+        ("shards", 2) => cluster_shards(sd),
+        ("nodes", 2) => Response::Data(cluster_nodes(sd).into_bytes()),
+        ("keyslot", 3) => Response::Integer(key_hash_slot(&tokens[2]) as i64),
+        ("myid", 2) => Response::Data(cluster_node_id(&sd.node_id).into_bytes()),
+        _ => {
+            return Err(Response::Error(format!(
+                "ERR Unknown subcommand or wrong number of arguments for '{}'",
+                String::from_utf8_lossy(&tokens[1])
+            )))
+        }
+    };
+
+    Ok(r)
+}
+
+/// moved_error builds the redirect to a node hosting the group of `key`, in slot mode.
+pub fn moved_error(key: &[u8], n: &Node) -> Response {
+    Response::Error(format!("MOVED {} {}", key_hash_slot(key), n.api_addr))
+}
+
+/// cluster_node_id converts a node id to a 40-characters id in redis-cluster convention, by hex
+/// encoding it.
+pub fn cluster_node_id(nid: &str) -> String {
+    let mut id: String = nid.bytes().map(|b| format!("{:02x}", b)).collect();
+    id.truncate(40);
+    format!("{:0<40}", id)
+}
+
+/// group_nodes returns nodes hosting replicas of a group, the master first.
+fn group_nodes<'a>(sd: &'a ServerData, g: &GroupInfo) -> Vec<&'a Node> {
+    let mut nodes: Vec<&Node> = vec![];
+    for (_, nid) in g.replicas.iter() {
+        if let Some(n) = sd.cluster.get(nid) {
+            if !nodes.iter().any(|x| x.node_id == n.node_id) {
+                nodes.push(n);
+            }
+        }
+    }
+    nodes
+}
+
+fn cluster_slots(sd: &ServerData) -> Response {
+    let mut rst = vec![];
+    for g in sd.cluster.groups.iter() {
+        let (a, b) = g.slots.unwrap();
+        let mut ent = vec![Response::Integer(a as i64), Response::Integer(b as i64)];
+
+        for n in group_nodes(sd, g) {
+            ent.push(Response::Array(vec![
+                Response::Data(n.api_addr.ip().to_string().into_bytes()),
+                Response::Integer(n.api_addr.port() as i64),
+                Response::Data(cluster_node_id(&n.node_id).into_bytes()),
+            ]));
+        }
+
+        rst.push(Response::Array(ent));
+    }
+    Response::Array(rst)
+}
+
+fn cluster_shards(sd: &ServerData) -> Response {
+    let kv = |k: &str, v: Response| (Response::Data(k.as_bytes().to_vec()), v);
+    let data = |v: String| Response::Data(v.into_bytes());
+
+    let mut rst = vec![];
+    for g in sd.cluster.groups.iter() {
+        let (a, b) = g.slots.unwrap();
+
+        let mut nodes = vec![];
+        for (i, n) in group_nodes(sd, g).iter().enumerate() {
+            let role = if i == 0 { "master" } else { "replica" };
+            nodes.push(Response::Map(vec![
+                kv("id", data(cluster_node_id(&n.node_id))),
+                kv("port", Response::Integer(n.api_addr.port() as i64)),
+                kv("ip", data(n.api_addr.ip().to_string())),
+                kv("endpoint", data(n.api_addr.ip().to_string())),
+                kv("role", data(role.to_owned())),
+                kv("replication-offset", Response::Integer(0)),
+                kv("health", data("online".to_owned())),
+            ]));
+        }
+
+        rst.push(Response::Map(vec![
+            kv(
+                "slots",
+                Response::Array(vec![
+                    Response::Integer(a as i64),
+                    Response::Integer(b as i64),
+                ]),
+            ),
+            kv("nodes", Response::Array(nodes)),
+        ]));
+    }
+    Response::Array(rst)
+}
+
+/// cluster_nodes builds the reply of `CLUSTER NODES`, one line per node:
+/// `<id> <ip:port@cport> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state>
+/// <slot> ...`, where cport is the replication port.
+///
+/// A node that is the master of any group is a master with the slots of these groups. Otherwise
+/// it is a replica of the master of the first group it hosts.
+fn cluster_nodes(sd: &ServerData) -> String {
+    let mut lines = String::new();
+
+    for (nid, n) in sd.cluster.nodes.iter() {
+        let mut slots = vec![];
+        let mut master = None;
+
+        for g in sd.cluster.groups.iter() {
+            let nodes = group_nodes(sd, g);
+            if nodes.first().map(|x| &x.node_id) == Some(nid) {
+                let (a, b) = g.slots.unwrap();
+                if a == b {
+                    slots.push(format!("{}", a));
+                } else {
+                    slots.push(format!("{}-{}", a, b));
+                }
+            } else if master.is_none() && nodes.iter().any(|x| &x.node_id == nid) {
+                master = nodes.first().map(|x| cluster_node_id(&x.node_id));
+            }
+        }
+
+        let myself = if nid == &sd.node_id { "myself," } else { "" };
+        let (role, master) = if slots.len() > 0 || master.is_none() {
+            ("master", "-".to_owned())
+        } else {
+            ("slave", master.unwrap())
+        };
+
+        lines.push_str(&format!(
+            "{} {}@{} {}{} {} 0 0 0 connected",
+            cluster_node_id(nid),
+            n.api_addr,
+            n.replication.port(),
+            myself,
+            role,
+            master
+        ));
+        for s in slots.iter() {
+            lines.push(' ');
+            lines.push_str(s);
+        }
+        lines.push('\n');
+    }
+
+    lines
+}
@@ -0,0 +1,180 @@
+use epaxos::qpaxos::Command;
+use epaxos::qpaxos::OpCode;
+use epaxos::replica::parse_score;
+use epaxos::replica::ExecuteResult;
+use epaxos::replica::WITHSCORES;
+
+use parse::Response;
+
+use crate::redisapi::get_value;
+use crate::redisapi::parse_i64;
+use crate::redisapi::to_data;
+use crate::redisapi::Cmd;
+
+/// build_collection_cmds converts a command on a hash, list, set or sorted set to the
+/// `Command`s to replicate in one instance. A command on several members is converted to a
+/// `Command` for every member.
+/// The arity is already checked.
+pub fn build_collection_cmds(cmd: Cmd, tokens: &[Vec<u8>]) -> Result<Vec<Command>, Response> {
+    let key = &tokens[1];
+    let of = |op: OpCode| Command::of(op, key, &[]);
+    let member = |op: OpCode, m: &[u8], v: &[u8]| Command::member(op, key, m, v);
+    let each =
+        |op: OpCode| -> Vec<Command> { tokens[2..].iter().map(|m| member(op, m, &[])).collect() };
+
+    let cmds = match cmd {
+        Cmd::HSet => {
+            if tokens.len() % 2 != 0 {
+                return Err(wrong_args(cmd));
+            }
+            tokens[2..]
+                .chunks(2)
+                .map(|fv| member(OpCode::HSet, &fv[0], &fv[1]))
+                .collect()
+        }
+        Cmd::HGet => vec![member(OpCode::HGet, &tokens[2], &[])],
+        Cmd::HDel => each(OpCode::HDel),
+        Cmd::HGetAll => vec![of(OpCode::HGetAll)],
+        Cmd::HLen => vec![of(OpCode::HLen)],
+        Cmd::HExists => vec![member(OpCode::HExists, &tokens[2], &[])],
+
+        Cmd::LPush | Cmd::RPush => {
+            let op = if cmd == Cmd::LPush {
+                OpCode::LPush
+            } else {
+                OpCode::RPush
+            };
+            tokens[2..]
+                .iter()
+                .map(|v| Command::of(op, key, v))
+                .collect()
+        }
+        Cmd::LPop => vec![of(OpCode::LPop)],
+        Cmd::RPop => vec![of(OpCode::RPop)],
+        Cmd::LRange => {
+            let (start, stop) = (parse_i64(&tokens[2])?, parse_i64(&tokens[3])?);
+            vec![Command::range(OpCode::LRange, key, start, stop)]
+        }
+        Cmd::LLen => vec![of(OpCode::LLen)],
+
+        Cmd::SAdd => each(OpCode::SAdd),
+        Cmd::SRem => each(OpCode::SRem),
+        Cmd::SMembers => vec![of(OpCode::SMembers)],
+        Cmd::SIsMember => vec![member(OpCode::SIsMember, &tokens[2], &[])],
+        Cmd::SCard => vec![of(OpCode::SCard)],
+
+        // options such as NX or INCR are not supported.
+        Cmd::ZAdd => {
+            if tokens.len() % 2 != 0 {
+                return Err(Response::Error("ERR syntax error".to_owned()));
+            }
+
+            let mut cmds = vec![];
+            for sm in tokens[2..].chunks(2) {
+                if parse_score(&sm[0]).is_none() {
+                    return Err(Response::Error("ERR value is not a valid float".to_owned()));
+                }
+                cmds.push(member(OpCode::ZAdd, &sm[1], &sm[0]));
+            }
+            cmds
+        }
+        Cmd::ZRem => each(OpCode::ZRem),
+        Cmd::ZRange => {
+            if tokens.len() > 5 || (tokens.len() == 5 && !is_withscores(&tokens[4])) {
+                return Err(Response::Error("ERR syntax error".to_owned()));
+            }
+            let (start, stop) = (parse_i64(&tokens[2])?, parse_i64(&tokens[3])?);
+            let mut c = Command::range(OpCode::ZRange, key, start, stop);
+            if tokens.len() == 5 {
+                c.value = WITHSCORES.to_vec();
+            }
+            vec![c]
+        }
+        Cmd::ZScore => vec![member(OpCode::ZScore, &tokens[2], &[])],
+        Cmd::ZCard => vec![of(OpCode::ZCard)],
+
+        Cmd::Type => vec![of(OpCode::Type)],
+        _ => {
+            return Err(Response::Error(format!(
+                "ERR {:?} is not a data command",
+                cmd
+            )))
+        }
+    };
+
+    Ok(cmds)
+}
+
+/// make_collection_reply builds the reply of a command built by `build_collection_cmds`.
+pub fn make_collection_reply(cmd: Cmd, rsts: &[ExecuteResult]) -> Result<Response, Response> {
+    let r = match cmd {
+        // the number of members added or removed.
+        Cmd::HSet | Cmd::HDel | Cmd::SAdd | Cmd::SRem | Cmd::ZAdd | Cmd::ZRem => {
+            let mut n = 0;
+            for i in 0..rsts.len() {
+                n += get_int(rsts, i)?;
+            }
+            Response::Integer(n)
+        }
+        Cmd::HLen | Cmd::HExists | Cmd::LLen | Cmd::SIsMember | Cmd::SCard | Cmd::ZCard => {
+            Response::Integer(get_int(rsts, 0)?)
+        }
+        // the length after the last element is pushed.
+        Cmd::LPush | Cmd::RPush => Response::Integer(get_int(rsts, rsts.len() - 1)?),
+        Cmd::HGet | Cmd::LPop | Cmd::RPop | Cmd::ZScore => to_data(get_value(rsts, 0)?),
+
+        Cmd::HGetAll => {
+            let values = get_values(rsts, 0)?;
+            let pairs = values
+                .chunks(2)
+                .map(|fv| (Response::Data(fv[0].clone()), Response::Data(fv[1].clone())))
+                .collect();
+            Response::Map(pairs)
+        }
+        Cmd::LRange | Cmd::ZRange => Response::Array(data_list(get_values(rsts, 0)?)),
+        Cmd::SMembers => Response::Set(data_list(get_values(rsts, 0)?)),
+        Cmd::Type => match get_value(rsts, 0)? {
+            Some(v) => Response::Status(String::from_utf8_lossy(v).to_string()),
+            None => return Err(Response::Error("unexpected execute result".into())),
+        },
+        _ => {
+            return Err(Response::Error(format!(
+                "ERR {:?} is not a data command",
+                cmd
+            )))
+        }
+    };
+
+    Ok(r)
+}
+
+/// get_values returns the values returned by the i-th command, which reads a collection.
+pub fn get_values(rsts: &[ExecuteResult], i: usize) -> Result<&Vec<Vec<u8>>, Response> {
+    match rsts.get(i) {
+        Some(ExecuteResult::SuccessWithVals { values }) => Ok(values),
+        Some(ExecuteResult::Error(msg)) => Err(Response::Error(msg.clone())),
+        _ => Err(Response::Error("unexpected execute result".into())),
+    }
+}
+
+fn get_int(rsts: &[ExecuteResult], i: usize) -> Result<i64, Response> {
+    match get_value(rsts, i)? {
+        Some(v) => parse_i64(v),
+        None => Err(Response::Error("unexpected execute result".into())),
+    }
+}
+
+fn data_list(values: &[Vec<u8>]) -> Vec<Response> {
+    values.iter().map(|v| Response::Data(v.clone())).collect()
+}
+
+fn is_withscores(v: &[u8]) -> bool {
+    v.eq_ignore_ascii_case(WITHSCORES)
+}
+
+fn wrong_args(cmd: Cmd) -> Response {
+    Response::Error(format!(
+        "ERR wrong number of arguments for '{}' command",
+        cmd.name()
+    ))
+}
@@ -0,0 +1,196 @@
+/// Cmd identifies a redis command RedisApi supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmd {
+    Get,
+    Set,
+    Del,
+    Exists,
+    MGet,
+    MSet,
+    SetNX,
+    GetSet,
+    Append,
+    StrLen,
+    Incr,
+    IncrBy,
+    Decr,
+    SetEx,
+    PSetEx,
+    Expire,
+    PExpire,
+    ExpireAt,
+    PExpireAt,
+    Ttl,
+    PTtl,
+    Persist,
+    Type,
+    HSet,
+    HGet,
+    HDel,
+    HGetAll,
+    HLen,
+    HExists,
+    LPush,
+    RPush,
+    LPop,
+    RPop,
+    LRange,
+    LLen,
+    SAdd,
+    SRem,
+    SMembers,
+    SIsMember,
+    SCard,
+    ZAdd,
+    ZRem,
+    ZRange,
+    ZScore,
+    ZCard,
+    Scan,
+    Keys,
+    DBSize,
+    Range,
+    DelRange,
+    FlushDB,
+    FlushAll,
+    Hello,
+    Multi,
+    Exec,
+    Discard,
+    Watch,
+    Unwatch,
+    Cluster,
+}
+
+impl Cmd {
+    /// is_data returns true if the command reads or writes data through `build_cmds`, and thus
+    /// could be queued in a transaction.
+    pub fn is_data(&self) -> bool {
+        match self {
+            Cmd::Scan
+            | Cmd::Keys
+            | Cmd::DBSize
+            | Cmd::Range
+            | Cmd::DelRange
+            | Cmd::FlushDB
+            | Cmd::FlushAll
+            | Cmd::Hello
+            | Cmd::Multi
+            | Cmd::Exec
+            | Cmd::Discard
+            | Cmd::Watch
+            | Cmd::Unwatch
+            | Cmd::Cluster => false,
+            _ => true,
+        }
+    }
+
+    /// name returns the name of the command in lower case.
+    pub fn name(&self) -> &'static str {
+        COMMANDS
+            .iter()
+            .find(|spec| spec.cmd == *self)
+            .map(|spec| spec.name)
+            .unwrap_or("")
+    }
+}
+
+/// CmdSpec describes a redis command.
+#[derive(Debug)]
+pub struct CmdSpec {
+    pub cmd: Cmd,
+
+    /// name in lower case.
+    pub name: &'static str,
+
+    /// number of tokens including the command name, in redis convention:
+    /// a positive arity requires exactly `arity` tokens and a negative one requires at least
+    /// `-arity` tokens.
+    pub arity: i32,
+}
+
+impl CmdSpec {
+    pub const fn new(cmd: Cmd, name: &'static str, arity: i32) -> Self {
+        CmdSpec { cmd, name, arity }
+    }
+
+    /// check_arity returns false if the number of tokens `n` does not match the arity.
+    pub fn check_arity(&self, n: usize) -> bool {
+        let n = n as i32;
+        if self.arity >= 0 {
+            n == self.arity
+        } else {
+            n >= -self.arity
+        }
+    }
+}
+
+/// COMMANDS is the dispatch table of all supported commands.
+pub const COMMANDS: &[CmdSpec] = &[
+    CmdSpec::new(Cmd::Get, "get", 2),
+    CmdSpec::new(Cmd::Set, "set", -3),
+    CmdSpec::new(Cmd::Del, "del", -2),
+    CmdSpec::new(Cmd::Exists, "exists", -2),
+    CmdSpec::new(Cmd::MGet, "mget", -2),
+    CmdSpec::new(Cmd::MSet, "mset", -3),
+    CmdSpec::new(Cmd::SetNX, "setnx", 3),
+    CmdSpec::new(Cmd::GetSet, "getset", 3),
+    CmdSpec::new(Cmd::Append, "append", 3),
+    CmdSpec::new(Cmd::StrLen, "strlen", 2),
+    CmdSpec::new(Cmd::Incr, "incr", 2),
+    CmdSpec::new(Cmd::IncrBy, "incrby", 3),
+    CmdSpec::new(Cmd::Decr, "decr", 2),
+    CmdSpec::new(Cmd::SetEx, "setex", 4),
+    CmdSpec::new(Cmd::PSetEx, "psetex", 4),
+    CmdSpec::new(Cmd::Expire, "expire", 3),
+    CmdSpec::new(Cmd::PExpire, "pexpire", 3),
+    CmdSpec::new(Cmd::ExpireAt, "expireat", 3),
+    CmdSpec::new(Cmd::PExpireAt, "pexpireat", 3),
+    CmdSpec::new(Cmd::Ttl, "ttl", 2),
+    CmdSpec::new(Cmd::PTtl, "pttl", 2),
+    CmdSpec::new(Cmd::Persist, "persist", 2),
+    CmdSpec::new(Cmd::Type, "type", 2),
+    CmdSpec::new(Cmd::HSet, "hset", -4),
+    CmdSpec::new(Cmd::HGet, "hget", 3),
+    CmdSpec::new(Cmd::HDel, "hdel", -3),
+    CmdSpec::new(Cmd::HGetAll, "hgetall", 2),
+    CmdSpec::new(Cmd::HLen, "hlen", 2),
+    CmdSpec::new(Cmd::HExists, "hexists", 3),
+    CmdSpec::new(Cmd::LPush, "lpush", -3),
+    CmdSpec::new(Cmd::RPush, "rpush", -3),
+    CmdSpec::new(Cmd::LPop, "lpop", 2),
+    CmdSpec::new(Cmd::RPop, "rpop", 2),
+    CmdSpec::new(Cmd::LRange, "lrange", 4),
+    CmdSpec::new(Cmd::LLen, "llen", 2),
+    CmdSpec::new(Cmd::SAdd, "sadd", -3),
+    CmdSpec::new(Cmd::SRem, "srem", -3),
+    CmdSpec::new(Cmd::SMembers, "smembers", 2),
+    CmdSpec::new(Cmd::SIsMember, "sismember", 3),
+    CmdSpec::new(Cmd::SCard, "scard", 2),
+    CmdSpec::new(Cmd::ZAdd, "zadd", -4),
+    CmdSpec::new(Cmd::ZRem, "zrem", -3),
+    CmdSpec::new(Cmd::ZRange, "zrange", -4),
+    CmdSpec::new(Cmd::ZScore, "zscore", 3),
+    CmdSpec::new(Cmd::ZCard, "zcard", 2),
+    CmdSpec::new(Cmd::Scan, "scan", -2),
+    CmdSpec::new(Cmd::Keys, "keys", 2),
+    CmdSpec::new(Cmd::DBSize, "dbsize", 1),
+    CmdSpec::new(Cmd::Range, "range", -3),
+    CmdSpec::new(Cmd::DelRange, "delrange", -3),
+    CmdSpec::new(Cmd::FlushDB, "flushdb", -1),
+    CmdSpec::new(Cmd::FlushAll, "flushall", -1),
+    CmdSpec::new(Cmd::Hello, "hello", -1),
+    CmdSpec::new(Cmd::Multi, "multi", 1),
+    CmdSpec::new(Cmd::Exec, "exec", 1),
+    CmdSpec::new(Cmd::Discard, "discard", 1),
+    CmdSpec::new(Cmd::Watch, "watch", -2),
+    CmdSpec::new(Cmd::Unwatch, "unwatch", 1),
+    CmdSpec::new(Cmd::Cluster, "cluster", -2),
+];
+
+/// lookup_command finds the spec of a command by name, case-insensitively.
+pub fn lookup_command(name: &[u8]) -> Option<&'static CmdSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name.as_bytes().eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+
+use redis::aio::Connection;
+use redis::RedisError;
+use redis::Value;
+
+use parse::Response;
+
+/// Forwards proxies requests on keys of a group this node does not host, to the api address of
+/// a node that hosts it.
+/// A connection to every such node is kept for the life of the client connection, thus
+/// forwarded requests from a client are handled in order.
+#[derive(Default)]
+pub struct Forwards {
+    conns: HashMap<SocketAddr, Connection>,
+}
+
+impl fmt::Debug for Forwards {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.conns.keys()).finish()
+    }
+}
+
+impl Forwards {
+    /// forward sends a request to `addr` as is and returns the reply.
+    /// The connection is dropped on any error other than an error reply, and is re-established
+    /// by the next request.
+    pub async fn forward(
+        &mut self,
+        addr: SocketAddr,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        if !self.conns.contains_key(&addr) {
+            let c = connect(addr).await.map_err(|e| forward_error(addr, e))?;
+            self.conns.insert(addr, c);
+        }
+
+        let mut req = redis::Cmd::new();
+        for t in tokens.iter() {
+            req.arg(&t[..]);
+        }
+
+        let con = self.conns.get_mut(&addr).unwrap();
+        let rst: Result<Value, RedisError> = req.query_async(con).await;
+
+        match rst {
+            Ok(v) => Ok(from_redis_value(v)),
+            Err(e) => {
+                if e.kind() == redis::ErrorKind::IoError {
+                    self.conns.remove(&addr);
+                    return Err(forward_error(addr, e));
+                }
+                Err(from_redis_error(e))
+            }
+        }
+    }
+}
+
+async fn connect(addr: SocketAddr) -> Result<Connection, RedisError> {
+    let client = redis::Client::open(format!("redis://{}/", addr).as_str())?;
+    client.get_async_connection().await
+}
+
+fn forward_error(addr: SocketAddr, e: RedisError) -> Response {
+    Response::Error(format!("ERR forward to {}: {}", addr, e))
+}
+
+/// from_redis_value converts a reply from another node into a `Response`.
+pub fn from_redis_value(v: Value) -> Response {
+    match v {
+        Value::Nil => Response::Nil,
+        Value::Int(i) => Response::Integer(i),
+        Value::Data(d) => Response::Data(d),
+        Value::Bulk(vs) => Response::Array(vs.into_iter().map(from_redis_value).collect()),
+        Value::Status(s) => Response::Status(s),
+        Value::Okay => Response::Status("OK".to_owned()),
+    }
+}
+
+/// from_redis_error converts an error reply from another node into a `Response`, with the
+/// error code preserved.
+pub fn from_redis_error(e: RedisError) -> Response {
+    match (e.code(), e.detail()) {
+        (Some(code), Some(detail)) => Response::Error(format!("{} {}", code, detail)),
+        (Some(code), None) => Response::Error(code.to_owned()),
+        _ => Response::Error(format!("ERR {}", e)),
+    }
+}
@@ -1,2 +1,23 @@
+mod commands;
+pub use commands::*;
+
+mod strings;
+pub use strings::*;
+
+mod collections;
+pub use collections::*;
+
+mod scan;
+pub use scan::*;
+
+mod range;
+pub use range::*;
+
+mod cluster;
+pub use cluster::*;
+
+mod forward;
+pub use forward::*;
+
 mod redisapi;
 pub use redisapi::*;
@@ -0,0 +1,326 @@
+use std::str::from_utf8;
+
+use epaxos::conf::GroupInfo;
+use epaxos::conf::KeyRange;
+use epaxos::propose;
+use epaxos::qpaxos::Command;
+use epaxos::replica::ExecuteResult;
+
+use parse::Response;
+
+use crate::redisapi::get_values;
+use crate::redisapi::parse_i64;
+use crate::redisapi::ConnState;
+use crate::redisapi::RedisApi;
+
+/// parse_range_opts parses options following the range of RANGE: `[LIMIT count] [REV]`.
+/// It returns the limit, 0 for unlimited, and whether to read in reverse order.
+pub fn parse_range_opts(tokens: &[Vec<u8>]) -> Result<(i64, bool), Response> {
+    let syntax = || Response::Error("ERR syntax error".to_owned());
+
+    let mut limit = 0;
+    let mut reverse = false;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let name = from_utf8(&tokens[i]).unwrap_or("").to_lowercase();
+        match name.as_str() {
+            "limit" if i + 1 < tokens.len() => {
+                limit = parse_i64(&tokens[i + 1])?;
+                if limit < 0 {
+                    return Err(syntax());
+                }
+                i += 2;
+            }
+            "rev" => {
+                reverse = true;
+                i += 1;
+            }
+            _ => return Err(syntax()),
+        }
+    }
+
+    Ok((limit, reverse))
+}
+
+/// overlap returns the part of `[start, end)` in a group range, or `None` if they do not
+/// overlap. A `None` end is unbounded.
+pub fn overlap(
+    r: &KeyRange,
+    start: &[u8],
+    end: Option<&[u8]>,
+) -> Option<(Vec<u8>, Option<Vec<u8>>)> {
+    let s = match r.start {
+        Some(ref s) if &s[..] > start => &s[..],
+        _ => start,
+    };
+
+    let e = match (&r.end, end) {
+        (Some(a), Some(b)) => Some(if &a[..] < b { &a[..] } else { b }),
+        (Some(a), None) => Some(&a[..]),
+        (None, b) => b,
+    };
+
+    if let Some(e) = e {
+        if s >= e {
+            return None;
+        }
+    }
+
+    Some((s.to_vec(), e.map(|x| x.to_vec())))
+}
+
+impl RedisApi {
+    /// cmd_range impl command range: `RANGE start end [LIMIT count] [REV]`, which is not a redis
+    /// command. It reads string keys and values in `[start, end)` as `[k1, v1, k2, v2, ...]`, in
+    /// key order, or in reverse order with `REV`. An empty end is unbounded and a count of 0 is
+    /// unlimited.
+    ///
+    /// Keys are range partitioned, thus groups overlapping the range are read one after another
+    /// in key order, each with a ReadRange command, and a group not hosted by this node is read by
+    /// forwarding RANGE to a node hosting it.
+    /// A ReadRange conflicts with every write in its range, thus it sees all writes to the group
+    /// ordered before it. Groups are not read at the same point, as a multi-group transaction
+    /// does.
+    pub(crate) async fn cmd_range(
+        &self,
+        conn: &mut ConnState,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        let sd = &self.server_data;
+        if sd.cluster.is_slot_mode() {
+            return Err(Response::Error(
+                "ERR RANGE is not supported in slot mode".to_owned(),
+            ));
+        }
+
+        let start = &tokens[1][..];
+        let end = if tokens[2].is_empty() {
+            None
+        } else {
+            Some(&tokens[2][..])
+        };
+        let (limit, reverse) = parse_range_opts(&tokens[3..])?;
+
+        let mut parts: Vec<_> = sd
+            .cluster
+            .groups
+            .iter()
+            .filter_map(|g| overlap(&g.range, start, end))
+            .collect();
+        if reverse {
+            parts.reverse();
+        }
+
+        let mut values = vec![];
+        for (s, e) in parts {
+            let left = if limit > 0 {
+                let left = limit - (values.len() / 2) as i64;
+                if left <= 0 {
+                    break;
+                }
+                left
+            } else {
+                0
+            };
+
+            let mut vs = self.read_group_range(conn, s, e, left, reverse).await?;
+            values.append(&mut vs);
+        }
+
+        Ok(Response::Array(
+            values.into_iter().map(Response::Data).collect(),
+        ))
+    }
+
+    /// cmd_delrange impl command delrange: `DELRANGE start end [SLOT slot]`, which is not a redis
+    /// command. It removes every key in `[start, end)`, strings and collections. An empty end is
+    /// unbounded.
+    /// In slot mode every group has keys in the range, thus the range is removed from all of
+    /// them, or only from the group serving `slot` if it is specified.
+    pub(crate) async fn cmd_delrange(
+        &self,
+        conn: &mut ConnState,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        let sd = &self.server_data;
+
+        let slot = match tokens.len() {
+            3 => None,
+            5 if tokens[3].eq_ignore_ascii_case(b"slot") => Some(parse_i64(&tokens[4])?),
+            _ => return Err(Response::Error("ERR syntax error".to_owned())),
+        };
+
+        let end = if tokens[2].is_empty() {
+            None
+        } else {
+            Some(&tokens[2][..])
+        };
+
+        let slot = match slot {
+            Some(v) => v,
+            None => return self.delete_range(conn, &tokens[1], end).await,
+        };
+
+        let g = sd.cluster.groups.iter().find(|g| match g.slots {
+            Some((a, b)) => a as i64 <= slot && slot <= b as i64,
+            None => false,
+        });
+        let g = match g {
+            Some(v) => v,
+            None => return Err(Response::Error("ERR no group serves the slot".to_owned())),
+        };
+
+        let e = end.map(|x| x.to_vec()).unwrap_or_default();
+        self.delete_group_range(conn, g, tokens[1].clone(), e)
+            .await?;
+        Ok(Response::Status("OK".to_owned()))
+    }
+
+    /// cmd_flushdb impl command flushdb and flushall: `FLUSHDB [ASYNC]`. There is only one
+    /// database, thus both remove every key, of all groups. `ASYNC` is accepted, but a flush
+    /// always returns after keys are removed.
+    pub(crate) async fn cmd_flushdb(
+        &self,
+        conn: &mut ConnState,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        match tokens.len() {
+            1 => {}
+            2 if tokens[1].eq_ignore_ascii_case(b"async") => {}
+            _ => return Err(Response::Error("ERR syntax error".to_owned())),
+        }
+
+        self.delete_range(conn, b"", None).await
+    }
+
+    /// delete_range removes `[start, end)` from every group overlapping it, one after another.
+    /// In slot mode it is removed from every group.
+    async fn delete_range(
+        &self,
+        conn: &mut ConnState,
+        start: &[u8],
+        end: Option<&[u8]>,
+    ) -> Result<Response, Response> {
+        let sd = &self.server_data;
+
+        let parts: Vec<_> = if sd.cluster.is_slot_mode() {
+            let e = end.map(|x| x.to_vec());
+            sd.cluster
+                .groups
+                .iter()
+                .map(|g| (g, start.to_vec(), e.clone()))
+                .collect()
+        } else {
+            sd.cluster
+                .groups
+                .iter()
+                .filter_map(|g| overlap(&g.range, start, end).map(|(s, e)| (g, s, e)))
+                .collect()
+        };
+
+        for (g, s, e) in parts {
+            self.delete_group_range(conn, g, s, e.unwrap_or_default())
+                .await?;
+        }
+
+        Ok(Response::Status("OK".to_owned()))
+    }
+
+    /// delete_group_range removes `[start, end)` from the group `g` with a DeleteRange command,
+    /// which is ordered with other commands on keys in the range like a write on every one of
+    /// them. An empty end is unbounded.
+    /// In slot mode a large range is removed by several DeleteRange commands one after another,
+    /// thus it is not atomic: a key written in the range meanwhile may or may not be removed.
+    /// If this node does not host `g`, DELRANGE is forwarded to a node hosting it, with the
+    /// first slot of `g` in slot mode.
+    async fn delete_group_range(
+        &self,
+        conn: &mut ConnState,
+        g: &GroupInfo,
+        start: Vec<u8>,
+        end: Vec<u8>,
+    ) -> Result<(), Response> {
+        let sd = &self.server_data;
+
+        if let Some(r) = sd.get_local_replica_for_group(g) {
+            // in slot mode a DeleteRange removes a limited number of keys, and replies where to
+            // continue if there are more.
+            let mut start = start;
+            loop {
+                let rsts = propose(&[Command::delete_range(&start, &end)], g, r).await?;
+                match rsts.into_iter().next() {
+                    Some(ExecuteResult::SuccessWithVal { value: Some(next) }) => start = next,
+                    _ => return Ok(()),
+                }
+            }
+        }
+
+        let n = match sd.get_remote_node_for_group(g) {
+            Some(v) => v,
+            None => return Err(Response::Error("ERR no node hosts the group".to_owned())),
+        };
+
+        let mut tokens = vec![b"DELRANGE".to_vec(), start, end];
+        if let Some((first, _)) = g.slots {
+            tokens.push(b"SLOT".to_vec());
+            tokens.push(first.to_string().into_bytes());
+        }
+
+        match conn.forwards.forward(n.api_addr, &tokens).await? {
+            Response::Status(_) => Ok(()),
+            _ => Err(Response::Error(
+                "ERR unexpected reply to DELRANGE".to_owned(),
+            )),
+        }
+    }
+
+    /// read_group_range reads `[start, end)` that is in one group.
+    async fn read_group_range(
+        &self,
+        conn: &mut ConnState,
+        start: Vec<u8>,
+        end: Option<Vec<u8>>,
+        limit: i64,
+        reverse: bool,
+    ) -> Result<Vec<Vec<u8>>, Response> {
+        let end = end.unwrap_or_default();
+
+        if let Some(n) = self.server_data.get_remote_node_for_key(&start)? {
+            let mut tokens = vec![b"RANGE".to_vec(), start, end];
+            if limit > 0 {
+                tokens.push(b"LIMIT".to_vec());
+                tokens.push(limit.to_string().into_bytes());
+            }
+            if reverse {
+                tokens.push(b"REV".to_vec());
+            }
+
+            let r = conn.forwards.forward(n.api_addr, &tokens).await?;
+            return parse_range_reply(r);
+        }
+
+        let cmd = Command::read_range(&start, &end, limit, reverse);
+        let rsts = self.propose(&[cmd]).await?;
+        Ok(get_values(&rsts, 0)?.clone())
+    }
+}
+
+/// parse_range_reply parses a reply to RANGE from another node.
+fn parse_range_reply(r: Response) -> Result<Vec<Vec<u8>>, Response> {
+    let unexpected = || Response::Error("ERR unexpected reply to RANGE".to_owned());
+
+    let vs = match r {
+        Response::Array(vs) => vs,
+        _ => return Err(unexpected()),
+    };
+
+    let mut rst = vec![];
+    for v in vs {
+        match v {
+            Response::Data(v) => rst.push(v),
+            _ => return Err(unexpected()),
+        }
+    }
+    Ok(rst)
+}
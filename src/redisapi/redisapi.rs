@@ -1,28 +1,79 @@
 use net2;
-use redis;
 
 use std::net::SocketAddr;
 use std::str::from_utf8;
 use std::sync::Arc;
+use std::time::Duration;
 
 // for boxed()
 use futures::future::FutureExt;
 
 use futures::Future;
+use futures::SinkExt;
+use futures::StreamExt;
 
 use tokio;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
 
+use epaxos::propose;
+use epaxos::qpaxos::now_ms;
 use epaxos::qpaxos::Command;
-use epaxos::qpaxos::OpCode;
-use epaxos::replicate;
-
+use epaxos::read::read;
+use epaxos::replica::ExecuteResult;
+use epaxos::replica::WRONGTYPE;
+use epaxos::txn::propose_txn;
+use epaxos::ReplicationError;
+
+use parse::ParseError;
+use parse::ProtocolVersion;
+use parse::RespCodec;
 use parse::Response;
 
 use epaxos::ServerData;
 
+use crate::redisapi::build_cmds;
+use crate::redisapi::cmd_cluster;
+use crate::redisapi::get_value;
+use crate::redisapi::lookup_command;
+use crate::redisapi::make_reply;
+use crate::redisapi::moved_error;
+use crate::redisapi::Cmd;
+use crate::redisapi::Forwards;
+
+/// Transaction is the commands queued after `MULTI`, which are replicated in one instance by
+/// `EXEC`.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    /// every queued command and the `Command`s built from it.
+    pub cmds: Vec<(Cmd, Vec<Command>)>,
+
+    /// set if a command failed to queue, e.g., an unknown command. `EXEC` then discards the
+    /// transaction.
+    pub dirty: bool,
+}
+
+/// ConnState is the state of a client connection.
+#[derive(Debug, Default)]
+pub struct ConnState {
+    /// the protocol version negotiated with `HELLO`.
+    pub proto: ProtocolVersion,
+
+    /// the transaction started by `MULTI`.
+    pub multi: Option<Transaction>,
+
+    /// keys watched by `WATCH` and their values when watched.
+    /// A transaction is aborted if any of them has changed when it is executed.
+    ///
+    /// Since a key is compared by value, a change that restores the watched value does not abort
+    /// a transaction.
+    pub watched: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+
+    /// connections to other nodes, to forward requests on keys this node does not host.
+    pub forwards: Forwards,
+}
+
 /// ReidsApi impl redis-protocol
 #[derive(Clone)]
 pub struct RedisApi {
@@ -70,122 +121,364 @@ impl RedisApi {
         Ok(())
     }
 
-    async fn handle_new_conn(self, mut sock: TcpStream) {
+    async fn handle_new_conn(self, sock: TcpStream) {
         println!("new connection");
 
-        loop {
-            let mut buf = vec![0u8; 1024];
+        let mut frames = Framed::new(sock, RespCodec::new());
+        let mut conn = ConnState::default();
+
+        // requests are handled one by one, thus replies to pipelined requests are sent in the
+        // same order.
+        while let Some(req) = frames.next().await {
+            let tokens = match req {
+                Ok(v) => v,
+                Err(ParseError::IO(e)) => {
+                    println!("read error: {:?}", e);
+                    return;
+                }
+                Err(e) => {
+                    // the stream can not be resynchronized after a bad frame, reply then close it.
+                    println!("parse error: {:}", e);
+                    let r = Response::Error(format!("ERR {}", e));
+                    let _ = frames.send(r).await;
+                    return;
+                }
+            };
 
-            let n = sock
-                .read(&mut buf)
-                .await
-                .expect("failed to read data from socket");
+            let r = self.exec_redis_cmd(&mut conn, &tokens).await;
+            println!("r={:?}", r);
 
-            println!("read buf: len={:}, {:?}", n, buf);
+            // the reply to HELLO is already in the negotiated version.
+            frames.codec_mut().proto = conn.proto;
 
-            if n == 0 {
-                println!("client closed");
+            if let Err(e) = frames.send(r).await {
+                println!("write error: {:?}", e);
                 return;
             }
+        }
 
-            let v = redis::parse_redis_value(&buf);
-            let v = match v {
-                Ok(q) => {
-                    println!("parsed redis value: {:?}", q);
-                    q
-                }
-                Err(err) => {
-                    // TODO bad protocol handling
-                    println!("parse error: {:}", err);
-                    panic!("bad redis protocol");
-                }
-            };
-            let r = self.exec_redis_cmd(v).await;
-            println!("r={:?}", r);
-            println!("response bytes:{:?}", r.as_bytes());
-            sock.write_all(&*r.as_bytes())
-                .await
-                .expect("failed to write data to socket");
+        println!("client closed");
+    }
+
+    async fn exec_redis_cmd(&self, conn: &mut ConnState, tokens: &[Vec<u8>]) -> Response {
+        let r = self._exec_redis_cmd(conn, tokens).await;
+
+        match r {
+            Ok(rr) => rr,
+            Err(rr) => rr,
         }
     }
 
-    async fn exec_redis_cmd(&self, v: redis::Value) -> Response {
-        // cmd is a nested array: ["set", "a", "1"] or ["set", ["b", "c"], ...]
-        // A "set" or "get" redis command is serialized as non-nested array.
-        //
-        // Flatten one level:
-        // tokens is a vec[Value].
-        let tokens = match v {
-            redis::Value::Bulk(tokens) => tokens,
-            _ => vec![],
+    async fn _exec_redis_cmd(
+        &self,
+        conn: &mut ConnState,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        let spec = match lookup_command(&tokens[0]) {
+            Some(v) => v,
+            None => {
+                mark_dirty(conn);
+                let name = String::from_utf8_lossy(&tokens[0]);
+                return Err(Response::Error(format!("ERR unknown command `{}`", name)));
+            }
         };
 
-        // the first token is instruction, e.g. "set" or "get".
-        let tok0 = &tokens[0];
+        if !spec.check_arity(tokens.len()) {
+            mark_dirty(conn);
+            return Err(Response::Error(format!(
+                "ERR wrong number of arguments for '{}' command",
+                spec.name
+            )));
+        }
 
-        let t = match tok0 {
-            redis::Value::Data(d) => d,
-            _ => {
-                println!("tok0 is not a Data!!!");
-                return Response::Error("invalid command".to_owned());
+        match spec.cmd {
+            Cmd::Multi => self.cmd_multi(conn),
+            Cmd::Exec => self.cmd_exec(conn).await,
+            Cmd::Discard => self.cmd_discard(conn),
+            Cmd::Watch => self.cmd_watch(conn, tokens).await,
+            Cmd::Unwatch => {
+                conn.watched.clear();
+                Ok(Response::Status("OK".to_owned()))
             }
-        };
+            _ if conn.multi.is_some() => self.queue_cmd(conn, spec.cmd, tokens),
+            Cmd::Scan => self.cmd_scan(conn, tokens).await,
+            Cmd::Keys => self.cmd_keys(conn, tokens).await,
+            Cmd::DBSize => self.cmd_dbsize(conn).await,
+            Cmd::Range => self.cmd_range(conn, tokens).await,
+            Cmd::DelRange => self.cmd_delrange(conn, tokens).await,
+            Cmd::FlushDB | Cmd::FlushAll => self.cmd_flushdb(conn, tokens).await,
+            Cmd::Hello => self.cmd_hello(conn, tokens),
+            Cmd::Cluster => cmd_cluster(&self.server_data, tokens),
+            _ => self.cmd_data(conn, spec.cmd, tokens).await,
+        }
+    }
 
-        println!("instruction: {:?}", t);
-        let tok0str = from_utf8(&t).unwrap();
+    /// cmd_data executes a data command in one instance.
+    /// If this node does not host the group serving its first key, the command is forwarded to a
+    /// node that does, or in slot mode, the client is redirected to it with `MOVED`.
+    async fn cmd_data(
+        &self,
+        conn: &mut ConnState,
+        cmd: Cmd,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        let cmds = build_cmds(cmd, tokens)?;
+
+        if let Some(n) = self.server_data.get_remote_node_for_key(&cmds[0].key)? {
+            if self.server_data.cluster.is_slot_mode() {
+                return Err(moved_error(&cmds[0].key, n));
+            }
+            return conn.forwards.forward(n.api_addr, tokens).await;
+        }
 
-        // execute the command
+        // reply only after the instance is executed.
+        let rsts = self.propose(&cmds).await?;
 
-        let r = match tok0str {
-            "SET" => self.cmd_set(&tokens).await,
-            "FLUSHDB" => Ok(Response::Status("OK".to_owned())),
-            "GET" => Ok(Response::Integer(42)),
-            _ => Err(Response::Error("invalid command".to_owned())),
-        };
+        if rsts.iter().any(|x| x == &ExecuteResult::Aborted) {
+            return Err(Response::Error(
+                "TXNABORT keys are locked by another transaction, try again".to_owned(),
+            ));
+        }
 
-        match r {
-            Ok(rr) => rr,
-            Err(rr) => rr,
+        make_reply(cmd, &rsts)
+    }
+
+    /// cmd_multi impl redis-command multi: it starts a transaction.
+    fn cmd_multi(&self, conn: &mut ConnState) -> Result<Response, Response> {
+        if conn.multi.is_some() {
+            return Err(Response::Error(
+                "ERR MULTI calls can not be nested".to_owned(),
+            ));
         }
+
+        conn.multi = Some(Transaction::default());
+        Ok(Response::Status("OK".to_owned()))
     }
 
-    /// cmd_set impl redis-command set. TODO impl it.
-    async fn cmd_set(&self, tokens: &[redis::Value]) -> Result<Response, Response> {
-        let cmd = OpCode::Set;
-        let key = match tokens[1] {
-            redis::Value::Data(ref d) => d,
-            _ => {
-                println!("expect tokens[1] to be key but not a Data");
-                return Err(Response::Error("invalid key".to_owned()));
+    /// queue_cmd adds a command to the transaction. Only data commands can be queued.
+    fn queue_cmd(
+        &self,
+        conn: &mut ConnState,
+        cmd: Cmd,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        let txn = conn.multi.as_mut().unwrap();
+
+        if !cmd.is_data() {
+            txn.dirty = true;
+            return Err(Response::Error(format!(
+                "ERR {:?} is not allowed in a transaction",
+                cmd
+            )));
+        }
+
+        match build_cmds(cmd, tokens) {
+            Ok(cmds) => txn.cmds.push((cmd, cmds)),
+            Err(e) => {
+                txn.dirty = true;
+                return Err(e);
             }
-        };
-        let value = match tokens[2] {
-            redis::Value::Data(ref d) => d,
-            _ => {
-                println!("expect tokens[2] to be value but not a Data");
-                return Err(Response::Error("invalid value".to_owned()));
+        }
+
+        Ok(Response::Status("QUEUED".to_owned()))
+    }
+
+    /// cmd_discard impl redis-command discard: it drops the transaction and unwatches all keys.
+    fn cmd_discard(&self, conn: &mut ConnState) -> Result<Response, Response> {
+        if conn.multi.take().is_none() {
+            return Err(Response::Error("ERR DISCARD without MULTI".to_owned()));
+        }
+
+        conn.watched.clear();
+        Ok(Response::Status("OK".to_owned()))
+    }
+
+    /// cmd_watch impl redis-command watch: `WATCH key [key ...]`.
+    /// It reads and remembers the current values of keys. A key already watched keeps the value
+    /// read the first time.
+    /// A collection is watched as an absent string, thus changes to its members are not detected.
+    async fn cmd_watch(
+        &self,
+        conn: &mut ConnState,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        if conn.multi.is_some() {
+            return Err(Response::Error(
+                "ERR WATCH inside MULTI is not allowed".to_owned(),
+            ));
+        }
+
+        let rsts = self.propose(&build_cmds(Cmd::MGet, tokens)?).await?;
+
+        for (i, key) in tokens[1..].iter().enumerate() {
+            let v = match rsts.get(i) {
+                Some(ExecuteResult::Error(msg)) if msg == WRONGTYPE => None,
+                _ => get_value(&rsts, i)?,
+            };
+            if conn.watched.iter().find(|(k, _)| k == key).is_none() {
+                conn.watched.push((key.clone(), v.cloned()));
             }
+        }
+
+        Ok(Response::Status("OK".to_owned()))
+    }
+
+    /// cmd_exec impl redis-command exec: it replicates all queued commands in one instance, or in
+    /// a transaction across groups if keys are served by different groups, thus they are applied
+    /// atomically.
+    /// A `Watch` command for every watched key is placed in the same instance. If any watched key
+    /// has changed, none of the commands is applied and it replies a null array.
+    ///
+    /// A command failing when executed, e.g., INCR a non-integer, does not affect the others.
+    async fn cmd_exec(&self, conn: &mut ConnState) -> Result<Response, Response> {
+        let txn = match conn.multi.take() {
+            Some(v) => v,
+            None => return Err(Response::Error("ERR EXEC without MULTI".to_owned())),
         };
+        let watched = std::mem::replace(&mut conn.watched, vec![]);
 
-        let cmd = Command::of(cmd, key, value);
-        let cmds = vec![cmd];
+        if txn.dirty {
+            return Err(Response::Error(
+                "EXECABORT Transaction discarded because of previous errors.".to_owned(),
+            ));
+        }
 
-        let (g, r) = self.server_data.get_local_replica_for_key(key)?;
+        let mut cmds: Vec<_> = watched
+            .iter()
+            .map(|(k, v)| Command::watch(k, v.as_ref().map(|x| &x[..])))
+            .collect();
 
-        let mut st = replicate(&cmds, g, r).await?;
-        let inst = &mut st.instance;
-        inst.committed = true;
-        let rst = r.storage.set_instance(inst);
+        let mut spans = vec![];
+        for (cmd, cs) in txn.cmds.into_iter() {
+            let start = cmds.len();
+            cmds.extend(cs);
+            spans.push((cmd, start..cmds.len()));
+        }
 
-        match rst {
-            Ok(_v) => {}
-            Err(_e) => {
-                return Ok(Response::Error("local commit error".into()));
+        if cmds.len() == 0 {
+            return Ok(Response::Array(vec![]));
+        }
+
+        let rsts = self.propose(&cmds).await?;
+
+        if rsts.iter().any(|x| x == &ExecuteResult::Aborted) {
+            return Ok(Response::NilArray);
+        }
+
+        let replies = spans
+            .into_iter()
+            .map(|(cmd, rng)| match make_reply(cmd, &rsts[rng]) {
+                Ok(r) => r,
+                Err(r) => r,
+            })
+            .collect();
+
+        Ok(Response::Array(replies))
+    }
+
+    /// cmd_hello impl redis-command hello: `HELLO [protover]`.
+    /// It switches the protocol version of the connection if `protover` is specified, and replies
+    /// a map of server properties. The `mode` is `cluster` if keys are assigned to groups by hash
+    /// slots, i.e., the cluster commands are supported, otherwise `standalone`.
+    /// Other arguments such as `AUTH` or `SETNAME` are ignored.
+    fn cmd_hello(&self, conn: &mut ConnState, tokens: &[Vec<u8>]) -> Result<Response, Response> {
+        if let Some(ver) = tokens.get(1) {
+            let ver = from_utf8(ver).ok().and_then(|x| x.parse::<i64>().ok());
+            conn.proto = match ver {
+                Some(2) => ProtocolVersion::Resp2,
+                Some(3) => ProtocolVersion::Resp3,
+                Some(_) => {
+                    return Err(Response::Error(
+                        "NOPROTO unsupported protocol version".to_owned(),
+                    ))
+                }
+                None => {
+                    return Err(Response::Error(
+                        "ERR Protocol version is not an integer or out of range".to_owned(),
+                    ))
+                }
+            };
+        }
+
+        let kv = |k: &str, v: Response| (Response::Data(k.as_bytes().to_vec()), v);
+        let data = |v: &str| Response::Data(v.as_bytes().to_vec());
+
+        let mode = if self.server_data.cluster.is_slot_mode() {
+            "cluster"
+        } else {
+            "standalone"
+        };
+
+        Ok(Response::Map(vec![
+            kv("server", data("celeritasdb")),
+            kv("version", data(env!("CARGO_PKG_VERSION"))),
+            kv("proto", Response::Integer(conn.proto as i64)),
+            kv("mode", data(mode)),
+            kv("role", data("master")),
+            kv("modules", Response::Array(vec![])),
+        ]))
+    }
+
+    /// propose replicates `cmds` in one instance if their keys are served by the same group
+    /// hosted by this node. Otherwise they are committed atomically by a transaction across
+    /// groups, which may be hosted by other nodes.
+    /// Read-only commands of one group are served by the local replica instead, by the read
+    /// consistency of the cluster.
+    /// It returns the `ExecuteResult` of every command, after they are executed.
+    ///
+    /// If a transaction is aborted, e.g., some key is locked by another transaction, every result
+    /// is `Aborted`, as if a watched key has changed.
+    pub(crate) async fn propose(&self, cmds: &[Command]) -> Result<Vec<ExecuteResult>, Response> {
+        let sd = &self.server_data;
+
+        // every replica evaluates expiry as of the time a command is received.
+        let now = now_ms();
+        let cmds: Vec<Command> = cmds
+            .iter()
+            .map(|c| Command {
+                now_ms: if c.now_ms == 0 { now } else { c.now_ms },
+                ..c.clone()
+            })
+            .collect();
+        let cmds = &cmds[..];
+
+        let g = sd.get_group_for_key(&cmds[0].key)?;
+
+        let mut one_group = true;
+        for cmd in cmds.iter() {
+            let cg = sd.get_group_for_key(&cmd.key)?;
+            if !std::ptr::eq(cg, g) {
+                one_group = false;
             }
         }
 
-        // TODO bcast commit
+        let local = if one_group {
+            sd.get_local_replica_for_group(g)
+        } else {
+            None
+        };
 
-        Ok(Response::Status("OK".to_owned()))
+        let rst = match local {
+            Some(r) if cmds.iter().all(|c| !c.is_write()) => {
+                let max_stale = Duration::from_millis(sd.cluster.read_stale_ms);
+                read(cmds, g, r, sd.cluster.read_consistency, max_stale).await
+            }
+            Some(r) => propose(cmds, g, r).await,
+            None => propose_txn(sd, cmds).await,
+        };
+
+        match rst {
+            Ok(v) => Ok(v),
+            Err(ReplicationError::TxnAborted(_)) => Ok(vec![ExecuteResult::Aborted; cmds.len()]),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// mark_dirty discards the transaction in progress if there is one, when a command fails to
+/// queue.
+fn mark_dirty(conn: &mut ConnState) {
+    if let Some(txn) = conn.multi.as_mut() {
+        txn.dirty = true;
     }
 }
@@ -0,0 +1,413 @@
+use std::str::from_utf8;
+
+use epaxos::qpaxos::now_ms;
+use epaxos::KeyIter;
+use epaxos::ServerData;
+
+use parse::Response;
+
+use crate::redisapi::parse_i64;
+use crate::redisapi::ConnState;
+use crate::redisapi::RedisApi;
+
+/// default number of keys SCAN examines in one call.
+pub const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// number of keys examined in one step by commands walking through the whole key space, such as
+/// KEYS or DBSIZE.
+const SCAN_BATCH: usize = 1000;
+
+/// ScanOpts are the options of SCAN: `[MATCH pattern] [COUNT count] [TYPE type]`.
+#[derive(Debug, Clone)]
+pub struct ScanOpts {
+    /// the max number of keys to examine, including those filtered out.
+    pub count: usize,
+    pub pattern: Option<Vec<u8>>,
+    pub typ: Option<String>,
+}
+
+impl Default for ScanOpts {
+    fn default() -> Self {
+        ScanOpts {
+            count: DEFAULT_SCAN_COUNT,
+            pattern: None,
+            typ: None,
+        }
+    }
+}
+
+impl ScanOpts {
+    /// parse parses options following the cursor of SCAN.
+    pub fn parse(tokens: &[Vec<u8>]) -> Result<ScanOpts, Response> {
+        let syntax = || Response::Error("ERR syntax error".to_owned());
+
+        let mut opts = ScanOpts::default();
+        for kv in tokens.chunks(2) {
+            if kv.len() != 2 {
+                return Err(syntax());
+            }
+
+            let name = from_utf8(&kv[0]).unwrap_or("").to_lowercase();
+            match name.as_str() {
+                "match" => opts.pattern = Some(kv[1].clone()),
+                "count" => {
+                    let n = parse_i64(&kv[1])?;
+                    if n < 1 {
+                        return Err(syntax());
+                    }
+                    opts.count = n as usize;
+                }
+                "type" => {
+                    opts.typ = Some(String::from_utf8_lossy(&kv[1]).to_lowercase());
+                }
+                _ => return Err(syntax()),
+            }
+        }
+
+        Ok(opts)
+    }
+
+    /// to_tokens builds a SCAN request with these options, to forward to another node.
+    pub fn to_tokens(&self, cursor: &[u8]) -> Vec<Vec<u8>> {
+        let mut tokens = vec![
+            b"SCAN".to_vec(),
+            cursor.to_vec(),
+            b"COUNT".to_vec(),
+            self.count.to_string().into_bytes(),
+        ];
+        if let Some(ref p) = self.pattern {
+            tokens.push(b"MATCH".to_vec());
+            tokens.push(p.clone());
+        }
+        if let Some(ref t) = self.typ {
+            tokens.push(b"TYPE".to_vec());
+            tokens.push(t.clone().into_bytes());
+        }
+        tokens
+    }
+
+    pub fn matches(&self, key: &[u8], typ: &str) -> bool {
+        if let Some(ref t) = self.typ {
+            if t != typ {
+                return false;
+            }
+        }
+
+        match self.pattern {
+            Some(ref p) => glob_match(p, key),
+            None => true,
+        }
+    }
+}
+
+/// encode_cursor encodes the position to continue a scan from, as a SCAN cursor, which is a
+/// decimal integer as redis uses. Both the very beginning and the end of a scan are `0`.
+/// Any other position `p` is encoded as the big-endian integer of the bytes `1 + p`, thus a
+/// position of any length could be encoded, and the cursor of a long key exceeds 64 bits.
+pub fn encode_cursor(pos: Option<&[u8]>) -> Vec<u8> {
+    let pos = match pos {
+        Some(p) if !p.is_empty() => p,
+        _ => return b"0".to_vec(),
+    };
+
+    // base-256 digits, the most significant first.
+    let mut n = [&[1u8][..], pos].concat();
+    let mut digits = vec![];
+    while !n.is_empty() {
+        let mut rem = 0;
+        for b in n.iter_mut() {
+            let cur = rem * 256 + *b as u32;
+            *b = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        digits.push(b'0' + rem as u8);
+
+        let zeros = n.iter().take_while(|x| **x == 0).count();
+        n.drain(..zeros);
+    }
+
+    digits.reverse();
+    digits
+}
+
+/// decode_cursor decodes a SCAN cursor built by `encode_cursor` to the position to continue
+/// from. The beginning of a scan is an empty position.
+pub fn decode_cursor(cursor: &[u8]) -> Result<Vec<u8>, Response> {
+    let invalid = || Response::Error("ERR invalid cursor".to_owned());
+
+    if cursor.is_empty() || !cursor.iter().all(|x| x.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    // base-256 digits, the most significant first.
+    let mut n: Vec<u8> = vec![];
+    for d in cursor.iter() {
+        let mut carry = (d - b'0') as u32;
+        for b in n.iter_mut().rev() {
+            let cur = *b as u32 * 10 + carry;
+            *b = cur as u8;
+            carry = cur >> 8;
+        }
+        if carry > 0 {
+            n.insert(0, carry as u8);
+        }
+    }
+
+    match n.split_first() {
+        None => Ok(vec![]),
+        Some((1, pos)) => Ok(pos.to_vec()),
+        _ => Err(invalid()),
+    }
+}
+
+/// glob_match returns true if `s` matches a glob-style pattern, as redis KEYS does:
+/// `*` matches any bytes, `?` matches one byte, `[abc]`, `[^abc]` and `[a-z]` match one byte in
+/// or not in a class, and `\` escapes the following byte.
+pub fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => s.is_empty(),
+        Some((b'*', rest)) => {
+            if rest.first() == Some(&b'*') {
+                return glob_match(rest, s);
+            }
+            (0..=s.len()).any(|i| glob_match(rest, &s[i..]))
+        }
+        Some((b'?', rest)) => !s.is_empty() && glob_match(rest, &s[1..]),
+        Some((b'[', rest)) if !s.is_empty() => match match_class(rest, s[0]) {
+            Some((ok, rest)) => ok && glob_match(rest, &s[1..]),
+            // an unterminated class is a literal `[`.
+            None => s[0] == b'[' && glob_match(rest, &s[1..]),
+        },
+        Some((b'\\', rest)) if !rest.is_empty() => {
+            s.first() == Some(&rest[0]) && glob_match(&rest[1..], &s[1..])
+        }
+        Some((c, rest)) => s.first() == Some(c) && glob_match(rest, &s[1..]),
+    }
+}
+
+/// match_class matches `c` against a class, of which the pattern `p` is the part after `[`.
+/// It returns whether `c` matches and the pattern after the closing `]`, or `None` if the class
+/// is not terminated.
+fn match_class(p: &[u8], c: u8) -> Option<(bool, &[u8])> {
+    let (negate, p) = match p.first() {
+        Some(b'^') => (true, &p[1..]),
+        _ => (false, p),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    loop {
+        match p.get(i)? {
+            b']' => return Some((found != negate, &p[i + 1..])),
+            b'\\' if i + 1 < p.len() => {
+                found |= p[i + 1] == c;
+                i += 2;
+            }
+            x if i + 2 < p.len() && p[i + 1] == b'-' && p[i + 2] != b']' => {
+                let (lo, hi) = if *x <= p[i + 2] {
+                    (*x, p[i + 2])
+                } else {
+                    (p[i + 2], *x)
+                };
+                found |= lo <= c && c <= hi;
+                i += 3;
+            }
+            x => {
+                found |= *x == c;
+                i += 1;
+            }
+        }
+    }
+}
+
+/// scan_local examines at most `opts.count` keys in local storage, from `start` and before
+/// `end`, and returns the matching ones, along with the position to continue from.
+/// The position is `end` if no more keys are before it, which is `None` for an unbounded end.
+///
+/// In slot mode keys of groups not hosted by this node are skipped.
+///
+/// Keys are read from what is executed, thus a command replicated but not yet executed on this
+/// node is not seen.
+pub fn scan_local(
+    sd: &ServerData,
+    start: &[u8],
+    end: Option<&[u8]>,
+    opts: &ScanOpts,
+) -> (Vec<Vec<u8>>, Option<Vec<u8>>) {
+    let slot_mode = sd.cluster.is_slot_mode();
+
+    let mut keys = vec![];
+    let mut n = 0;
+
+    for (k, typ) in KeyIter::new(&sd.storage, start, now_ms()) {
+        if let Some(e) = end {
+            if &k[..] >= e {
+                break;
+            }
+        }
+
+        if slot_mode && !is_local(sd, &k) {
+            continue;
+        }
+
+        if opts.matches(&k, typ) {
+            keys.push(k.clone());
+        }
+
+        n += 1;
+        if n >= opts.count {
+            let mut next = k;
+            next.push(0);
+            return (keys, Some(next));
+        }
+    }
+
+    (keys, end.map(|x| x.to_vec()))
+}
+
+/// is_local returns true if a replica of the group serving `key` is on this node.
+fn is_local(sd: &ServerData, key: &[u8]) -> bool {
+    match sd.get_remote_node_for_key(key) {
+        Ok(None) => true,
+        _ => false,
+    }
+}
+
+/// parse_scan_reply parses a reply to SCAN from another node.
+fn parse_scan_reply(r: Response) -> Result<(Vec<Vec<u8>>, Option<Vec<u8>>), Response> {
+    let unexpected = || Response::Error("ERR unexpected reply to SCAN".to_owned());
+
+    let mut vs = match r {
+        Response::Array(vs) if vs.len() == 2 => vs,
+        _ => return Err(unexpected()),
+    };
+
+    let keys = match vs.pop().unwrap() {
+        Response::Array(ks) => ks,
+        _ => return Err(unexpected()),
+    };
+    let cursor = match vs.pop().unwrap() {
+        Response::Data(c) => c,
+        _ => return Err(unexpected()),
+    };
+
+    let mut rst = vec![];
+    for k in keys {
+        match k {
+            Response::Data(k) => rst.push(k),
+            _ => return Err(unexpected()),
+        }
+    }
+
+    let next = decode_cursor(&cursor)?;
+    let next = if next.is_empty() { None } else { Some(next) };
+
+    Ok((rst, next))
+}
+
+impl RedisApi {
+    /// cmd_scan impl redis-command scan: `SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]`.
+    /// The cursor is a decimal integer encoding the key to continue from, thus a key present
+    /// during the whole scan is returned exactly once, no matter how keys are added or removed
+    /// between calls.
+    pub(crate) async fn cmd_scan(
+        &self,
+        conn: &mut ConnState,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        let start = decode_cursor(&tokens[1])?;
+        let opts = ScanOpts::parse(&tokens[2..])?;
+
+        let (keys, next) = self.scan_step(conn, start, &opts).await?;
+
+        Ok(Response::Array(vec![
+            Response::Data(encode_cursor(next.as_ref().map(|x| &x[..]))),
+            Response::Array(keys.into_iter().map(Response::Data).collect()),
+        ]))
+    }
+
+    /// cmd_keys impl redis-command keys: `KEYS pattern`. It scans the whole key space.
+    pub(crate) async fn cmd_keys(
+        &self,
+        conn: &mut ConnState,
+        tokens: &[Vec<u8>],
+    ) -> Result<Response, Response> {
+        let opts = ScanOpts {
+            count: SCAN_BATCH,
+            pattern: Some(tokens[1].clone()),
+            typ: None,
+        };
+
+        let keys = self.scan_all(conn, &opts).await?;
+        Ok(Response::Array(
+            keys.into_iter().map(Response::Data).collect(),
+        ))
+    }
+
+    /// cmd_dbsize impl redis-command dbsize: the number of keys in the whole key space.
+    pub(crate) async fn cmd_dbsize(&self, conn: &mut ConnState) -> Result<Response, Response> {
+        let opts = ScanOpts {
+            count: SCAN_BATCH,
+            ..Default::default()
+        };
+
+        let keys = self.scan_all(conn, &opts).await?;
+        Ok(Response::Integer(keys.len() as i64))
+    }
+
+    async fn scan_all(
+        &self,
+        conn: &mut ConnState,
+        opts: &ScanOpts,
+    ) -> Result<Vec<Vec<u8>>, Response> {
+        let mut rst = vec![];
+        let mut start = vec![];
+        loop {
+            let (mut keys, next) = self.scan_step(conn, start, opts).await?;
+            rst.append(&mut keys);
+            match next {
+                Some(v) => start = v,
+                None => return Ok(rst),
+            }
+        }
+    }
+
+    /// scan_step scans keys from the position `start`, within the group covering it, or the
+    /// next group if none covers it. Groups are defined by `ClusterInfo.groups`, and a group not
+    /// hosted by this node is scanned by forwarding SCAN to a node hosting it.
+    /// It returns the keys found and the position to continue from, or `None` if all groups are
+    /// scanned.
+    ///
+    /// In slot mode keys are not ordered by group, thus only keys on this node are scanned.
+    async fn scan_step(
+        &self,
+        conn: &mut ConnState,
+        start: Vec<u8>,
+        opts: &ScanOpts,
+    ) -> Result<(Vec<Vec<u8>>, Option<Vec<u8>>), Response> {
+        let sd = &self.server_data;
+
+        if sd.cluster.is_slot_mode() {
+            return Ok(scan_local(sd, &start, None, opts));
+        }
+
+        let g = match sd.cluster.get_group_from(&start) {
+            Some(v) => v,
+            None => return Ok((vec![], None)),
+        };
+
+        let start = match g.range.start {
+            Some(ref s) if s > &start => s.clone(),
+            _ => start,
+        };
+
+        if let Some(n) = sd.get_remote_node_for_key(&start)? {
+            let tokens = opts.to_tokens(&encode_cursor(Some(&start)));
+            let r = conn.forwards.forward(n.api_addr, &tokens).await?;
+            return parse_scan_reply(r);
+        }
+
+        let end = g.range.end.as_ref().map(|x| &x[..]);
+        Ok(scan_local(sd, &start, end, opts))
+    }
+}
@@ -0,0 +1,271 @@
+use std::str::from_utf8;
+
+use epaxos::qpaxos::now_ms;
+use epaxos::qpaxos::Command;
+use epaxos::qpaxos::OpCode;
+use epaxos::replica::ExecuteResult;
+use epaxos::replica::WRONGTYPE;
+
+use parse::Response;
+
+use crate::redisapi::build_collection_cmds;
+use crate::redisapi::make_collection_reply;
+use crate::redisapi::Cmd;
+
+/// build_cmds converts a data command to the `Command`s to replicate in one instance.
+/// The arity is already checked.
+pub fn build_cmds(cmd: Cmd, tokens: &[Vec<u8>]) -> Result<Vec<Command>, Response> {
+    let key = &tokens[1];
+    let get = |k: &[u8]| Command::of(OpCode::Get, k, &[]);
+    let typ = |k: &[u8]| Command::of(OpCode::Type, k, &[]);
+
+    let cmds = match cmd {
+        Cmd::Get | Cmd::StrLen => vec![get(key)],
+        Cmd::Set => vec![build_set(tokens)?],
+
+        // the type of every key is read then the key is deleted, to count the keys that existed.
+        Cmd::Del => tokens[1..]
+            .iter()
+            .flat_map(|k| vec![typ(k), Command::of(OpCode::Delete, k, &[])])
+            .collect(),
+
+        Cmd::Exists => tokens[1..].iter().map(|k| typ(k)).collect(),
+        Cmd::MGet => tokens[1..].iter().map(|k| get(k)).collect(),
+        Cmd::MSet => {
+            if tokens.len() % 2 != 1 {
+                return Err(Response::Error(
+                    "ERR wrong number of arguments for 'mset' command".to_owned(),
+                ));
+            }
+
+            tokens[1..]
+                .chunks(2)
+                .map(|kv| Command::of(OpCode::Set, &kv[0], &kv[1]))
+                .collect()
+        }
+        Cmd::SetNX => vec![Command::of(OpCode::SetIfAbsent, key, &tokens[2])],
+        Cmd::GetSet => vec![get(key), Command::of(OpCode::Set, key, &tokens[2])],
+        Cmd::Append => vec![Command::of(OpCode::Append, key, &tokens[2])],
+        Cmd::Incr => vec![Command::of(OpCode::Incr, key, b"1")],
+        Cmd::IncrBy => {
+            parse_i64(&tokens[2])?;
+            vec![Command::of(OpCode::Incr, key, &tokens[2])]
+        }
+        Cmd::Decr => vec![Command::of(OpCode::Incr, key, b"-1")],
+        Cmd::SetEx | Cmd::PSetEx => {
+            let unit = if cmd == Cmd::SetEx { 1000 } else { 1 };
+            let now = now_ms();
+            let at = parse_expire_at(&tokens[2], unit, now, true, cmd.name())?;
+            vec![with_expire(
+                Command::of(OpCode::Set, key, &tokens[3]),
+                at,
+                now,
+            )]
+        }
+        Cmd::Expire | Cmd::PExpire | Cmd::ExpireAt | Cmd::PExpireAt => {
+            let unit = match cmd {
+                Cmd::Expire | Cmd::ExpireAt => 1000,
+                _ => 1,
+            };
+            let now = now_ms();
+            let base = match cmd {
+                Cmd::Expire | Cmd::PExpire => now,
+                _ => 0,
+            };
+            let at = parse_expire_at(&tokens[2], unit, base, false, cmd.name())?;
+            vec![with_expire(Command::expire(key, at), at, now)]
+        }
+        Cmd::Ttl | Cmd::PTtl => vec![Command::of(OpCode::Ttl, key, &[])],
+        Cmd::Persist => vec![Command::of(OpCode::Persist, key, &[])],
+        _ => build_collection_cmds(cmd, tokens)?,
+    };
+
+    Ok(cmds)
+}
+
+/// make_reply builds the reply of a data command from the results of the `Command`s built by
+/// `build_cmds`.
+pub fn make_reply(cmd: Cmd, rsts: &[ExecuteResult]) -> Result<Response, Response> {
+    let r = match cmd {
+        Cmd::Get | Cmd::GetSet => to_data(get_value(rsts, 0)?),
+        // SET with NX is a SetIfAbsent, which returns the value before setting.
+        Cmd::Set => match rsts.get(0) {
+            Some(ExecuteResult::SuccessWithVal { value: Some(_) }) => Response::Nil,
+            _ => Response::Status("OK".to_owned()),
+        },
+        Cmd::MSet | Cmd::SetEx | Cmd::PSetEx => Response::Status("OK".to_owned()),
+        Cmd::StrLen | Cmd::Append => {
+            let v = get_value(rsts, 0)?;
+            Response::Integer(v.map(|x| x.len()).unwrap_or(0) as i64)
+        }
+        Cmd::Del => {
+            let mut n = 0;
+            for i in (0..rsts.len()).step_by(2) {
+                if is_present(rsts, i)? {
+                    n += 1;
+                }
+            }
+            Response::Integer(n)
+        }
+        // a key specified more than once is counted more than once.
+        Cmd::Exists => {
+            let mut n = 0;
+            for i in 0..rsts.len() {
+                if is_present(rsts, i)? {
+                    n += 1;
+                }
+            }
+            Response::Integer(n)
+        }
+        // a key that is not a string is nil.
+        Cmd::MGet => {
+            let mut vals = vec![];
+            for i in 0..rsts.len() {
+                match rsts.get(i) {
+                    Some(ExecuteResult::Error(msg)) if msg == WRONGTYPE => vals.push(Response::Nil),
+                    _ => vals.push(to_data(get_value(rsts, i)?)),
+                }
+            }
+            Response::Array(vals)
+        }
+        // SetIfAbsent returns the value before setting, which is absent iff it is set.
+        Cmd::SetNX => Response::Integer(get_value(rsts, 0)?.is_none() as i64),
+        Cmd::Incr | Cmd::IncrBy | Cmd::Decr => match get_value(rsts, 0)? {
+            Some(v) => Response::Integer(parse_i64(v)?),
+            None => return Err(Response::Error("unexpected execute result".into())),
+        },
+        Cmd::Expire | Cmd::PExpire | Cmd::ExpireAt | Cmd::PExpireAt | Cmd::Persist => {
+            match get_value(rsts, 0)? {
+                Some(v) => Response::Integer(parse_i64(v)?),
+                None => return Err(Response::Error("unexpected execute result".into())),
+            }
+        }
+        // Ttl returns the milliseconds to live, or a negative value if it never expires or the
+        // key does not exist.
+        Cmd::Ttl | Cmd::PTtl => {
+            let ms = match get_value(rsts, 0)? {
+                Some(v) => parse_i64(v)?,
+                None => return Err(Response::Error("unexpected execute result".into())),
+            };
+            if cmd == Cmd::Ttl && ms >= 0 {
+                Response::Integer((ms + 500) / 1000)
+            } else {
+                Response::Integer(ms)
+            }
+        }
+        _ => return make_collection_reply(cmd, rsts),
+    };
+
+    Ok(r)
+}
+
+/// get_value returns the value returned by the i-th command.
+pub fn get_value(rsts: &[ExecuteResult], i: usize) -> Result<Option<&Vec<u8>>, Response> {
+    match rsts.get(i) {
+        Some(ExecuteResult::SuccessWithVal { value }) => Ok(value.as_ref()),
+        Some(ExecuteResult::Error(msg)) => Err(Response::Error(msg.clone())),
+        _ => Err(Response::Error("unexpected execute result".into())),
+    }
+}
+
+/// is_present returns true if the i-th command, a `Type`, finds the key.
+fn is_present(rsts: &[ExecuteResult], i: usize) -> Result<bool, Response> {
+    Ok(get_value(rsts, i)?.map(|x| &x[..] != b"none") == Some(true))
+}
+
+/// to_data converts a value to a bulk string reply, or nil if the key does not exist.
+pub fn to_data(v: Option<&Vec<u8>>) -> Response {
+    match v {
+        Some(v) => Response::Data(v.clone()),
+        None => Response::Nil,
+    }
+}
+
+pub fn parse_i64(v: &[u8]) -> Result<i64, Response> {
+    from_utf8(v)
+        .ok()
+        .and_then(|x| x.parse::<i64>().ok())
+        .ok_or(Response::Error(
+            "ERR value is not an integer or out of range".to_owned(),
+        ))
+}
+
+/// build_set builds the command of `SET key value [NX] [EX s|PX ms|EXAT ts|PXAT ms-ts|KEEPTTL]`.
+fn build_set(tokens: &[Vec<u8>]) -> Result<Command, Response> {
+    let syntax_err = || Response::Error("ERR syntax error".to_owned());
+
+    let mut nx = false;
+    let mut expire_at = None;
+    let now = now_ms();
+
+    let mut i = 3;
+    while i < tokens.len() {
+        let opt = String::from_utf8_lossy(&tokens[i]).to_ascii_uppercase();
+        let (unit, base) = match &opt[..] {
+            "NX" => {
+                nx = true;
+                i += 1;
+                continue;
+            }
+            "KEEPTTL" => {
+                if expire_at.is_some() {
+                    return Err(syntax_err());
+                }
+                expire_at = Some(-1);
+                i += 1;
+                continue;
+            }
+            "EX" => (1000, now),
+            "PX" => (1, now),
+            "EXAT" => (1000, 0),
+            "PXAT" => (1, 0),
+            _ => return Err(syntax_err()),
+        };
+
+        if expire_at.is_some() || i + 1 >= tokens.len() {
+            return Err(syntax_err());
+        }
+        expire_at = Some(parse_expire_at(&tokens[i + 1], unit, base, true, "set")?);
+        i += 2;
+    }
+
+    let op = if nx { OpCode::SetIfAbsent } else { OpCode::Set };
+    let cmd = Command::of(op, &tokens[1], &tokens[2]);
+    Ok(with_expire(cmd, expire_at.unwrap_or(0), now))
+}
+
+/// parse_expire_at converts a time in `unit` milliseconds, relative to `base`, to an absolute
+/// expire-at in milliseconds since epoch.
+/// A non-positive time is invalid if `positive` is true.
+fn parse_expire_at(
+    v: &[u8],
+    unit: i64,
+    base: i64,
+    positive: bool,
+    name: &str,
+) -> Result<i64, Response> {
+    let t = parse_i64(v)?;
+    let invalid = || Response::Error(format!("ERR invalid expire time in '{}' command", name));
+
+    if positive && t <= 0 {
+        return Err(invalid());
+    }
+
+    let at = t
+        .checked_mul(unit)
+        .and_then(|x| x.checked_add(base))
+        .ok_or_else(invalid)?;
+
+    // 0 and -1 are reserved for no expire-at and KEEPTTL. A time that early has expired.
+    Ok(if at <= 0 { 1 } else { at })
+}
+
+/// with_expire sets the expire-at of a command and the time it is evaluated as of, thus a
+/// relative time to live is counted from the time the expire-at is calculated.
+fn with_expire(cmd: Command, expire_at_ms: i64, now_ms: i64) -> Command {
+    Command {
+        expire_at_ms,
+        now_ms,
+        ..cmd
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use epaxos::conf::Node;
+use epaxos::Storage;
+use storage::MemEngine;
+use storage::RocksDBEngine;
+
+use crate::ServerError;
+
+/// StorageEngine is the kind of storage a server runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageEngine {
+    /// MemEngine: all data is lost when a server stops.
+    Mem,
+    /// RocksDBEngine: data is persisted in a data directory and is reloaded when a server
+    /// restarts.
+    RocksDB,
+}
+
+impl FromStr for StorageEngine {
+    type Err = ServerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mem" => Ok(StorageEngine::Mem),
+            "rocksdb" => Ok(StorageEngine::RocksDB),
+            _ => Err(ServerError::UnknownStorage(s.to_string())),
+        }
+    }
+}
+
+/// default storage engine of a node, if neither the command line nor the config specifies one.
+pub const DEFAULT_STORAGE: &str = "mem";
+
+/// default data dir of a node, if neither the command line nor the config specifies one.
+pub const DEFAULT_DATA_DIR: &str = "./data";
+
+/// storage_options returns the storage engine and the data dir of `node`.
+/// `engine` and `data_dir` from the command line override those in the config of the node, and
+/// `DEFAULT_STORAGE` and `DEFAULT_DATA_DIR` are used if neither specifies one.
+pub fn storage_options(
+    node: &Node,
+    engine: Option<&str>,
+    data_dir: Option<&str>,
+) -> Result<(StorageEngine, String), ServerError> {
+    let engine = engine
+        .or_else(|| node.storage.as_deref())
+        .unwrap_or(DEFAULT_STORAGE);
+    let data_dir = data_dir
+        .or_else(|| node.data_dir.as_deref())
+        .unwrap_or(DEFAULT_DATA_DIR);
+
+    Ok((engine.parse()?, data_dir.to_string()))
+}
+
+/// open_storage opens a storage engine.
+/// `data_dir` is created if it does not exist. It is ignored by `StorageEngine::Mem`.
+pub fn open_storage(engine: StorageEngine, data_dir: &str) -> Result<Storage, ServerError> {
+    let sto: Storage = match engine {
+        StorageEngine::Mem => Arc::new(MemEngine::new()?),
+        StorageEngine::RocksDB => {
+            fs::create_dir_all(data_dir)?;
+            Arc::new(RocksDBEngine::new(data_dir)?)
+        }
+    };
+
+    Ok(sto)
+}
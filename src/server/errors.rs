@@ -1,7 +1,24 @@
+use std::io;
+
+use epaxos::ServerDataError;
+use storage::StorageError;
+
 quick_error! {
     #[derive(Debug)]
     pub enum ServerError {
         RxClosed {}
         NotStarted {}
+        UnknownStorage(name: String) {
+            display("unknown storage engine: {}, expect mem or rocksdb", name)
+        }
+        Storage(e: StorageError) {
+            from(e: StorageError) -> (e)
+        }
+        ServerData(e: ServerDataError) {
+            from(e: ServerDataError) -> (e)
+        }
+        IO(e: io::Error) {
+            from(e: io::Error) -> (e)
+        }
     }
 }
@@ -1,5 +1,7 @@
+mod engine;
 mod errors;
 mod server;
 
+pub use engine::*;
 pub use errors::*;
 pub use server::*;
@@ -1,5 +1,7 @@
+use std::collections::HashSet;
 use std::mem::replace;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 
 use futures::Future;
@@ -12,7 +14,15 @@ use tonic;
 
 use epaxos::conf::ClusterInfo;
 use epaxos::conf::NodeId;
+use epaxos::expire::sweep_expired;
+use epaxos::expire::DEFAULT_SWEEP_LIMIT;
+use epaxos::qpaxos::InstanceId;
 use epaxos::qpaxos::QPaxosServer;
+use epaxos::qpaxos::ReplicaId;
+use epaxos::recover;
+use epaxos::txn::recover_txns;
+use epaxos::txn::IntentAges;
+use epaxos::txn::DEFAULT_TXN_TIMEOUT;
 use epaxos::MyQPaxos;
 use epaxos::ServerData;
 use epaxos::Storage;
@@ -28,12 +38,13 @@ pub struct Server {
 }
 
 impl Server {
-    pub fn new(sto: Storage, cluster: ClusterInfo, node_id: NodeId) -> Server {
-        Server {
-            server_data: Arc::new(ServerData::new(sto, cluster, node_id)),
+    /// new creates a server of node `node_id`, with the replicas it hosts loaded from `sto`.
+    pub fn new(sto: Storage, cluster: ClusterInfo, node_id: NodeId) -> Result<Server, ServerError> {
+        Ok(Server {
+            server_data: Arc::new(ServerData::new(sto, cluster, node_id)?),
             stop_txs: Vec::new(),
             join_handle: Vec::new(),
-        }
+        })
     }
 
     /// Starts api server and repolication server
@@ -49,6 +60,9 @@ impl Server {
         let (tx1, rx1) = tokio::sync::oneshot::channel::<()>();
         let (tx2, rx2) = tokio::sync::oneshot::channel::<()>();
         let (tx3, rx3) = tokio::sync::oneshot::channel::<()>();
+        let (tx4, rx4) = tokio::sync::oneshot::channel::<()>();
+        let (tx5, rx5) = tokio::sync::oneshot::channel::<()>();
+        let (tx6, rx6) = tokio::sync::oneshot::channel::<()>();
 
         let fut = Server::_start_servers(self.server_data.clone(), rx1, rx2);
         let j = tokio::spawn(fut);
@@ -58,12 +72,58 @@ impl Server {
         let j = tokio::spawn(fut);
         self.join_handle.push(j);
 
+        let sd = self.server_data.clone();
+        let j = spawn_periodic("heartbeat", Duration::from_millis(100), rx4, move || {
+            // reconnect peers that are marked dead.
+            let sd = sd.clone();
+            async move { sd.peer_clients.heartbeat().await }
+        });
+        self.join_handle.push(j);
+
+        let sd = self.server_data.clone();
+        let ages = Arc::new(IntentAges::default());
+        let j = spawn_periodic(
+            "txn-recovery",
+            Duration::from_millis(1_000),
+            rx5,
+            move || {
+                // resolve transactions whose coordinator is considered crashed.
+                let sd = sd.clone();
+                let ages = ages.clone();
+                async move {
+                    for e in recover_txns(&sd, &ages, DEFAULT_TXN_TIMEOUT).await {
+                        println!("{:?} while recover transactions", e);
+                    }
+                }
+            },
+        );
+        self.join_handle.push(j);
+
+        let sd = self.server_data.clone();
+        let j = spawn_periodic(
+            "expire-sweep",
+            Duration::from_millis(1_000),
+            rx6,
+            move || {
+                // delete expired keys.
+                let sd = sd.clone();
+                async move { sweep_expired(&sd, DEFAULT_SWEEP_LIMIT).await }
+            },
+        );
+        self.join_handle.push(j);
+
         self.stop_txs.push(("api", tx1));
         self.stop_txs.push(("replication", tx2));
         self.stop_txs.push(("exec", tx3));
+        self.stop_txs.push(("heartbeat", tx4));
+        self.stop_txs.push(("txn-recovery", tx5));
+        self.stop_txs.push(("expire-sweep", tx6));
     }
 
     async fn _start_replica_exec(sd: Arc<ServerData>, mut rx: Receiver<()>) {
+        // instances being recovered, which are not recovered again until it finishes.
+        let recovering: Arc<Mutex<HashSet<InstanceId>>> = Arc::new(Mutex::new(HashSet::new()));
+
         loop {
             let mut exec_count = 0;
             for r in sd.local_replicas.values() {
@@ -80,6 +140,14 @@ impl Server {
                         continue;
                     }
                 }
+
+                // instances blocking execution: the leader may crash before committing them.
+                // They are recovered in other tasks, thus a slow one does not stall execution.
+                for iid in r.take_recover_insts() {
+                    if recovering.lock().unwrap().insert(iid) {
+                        spawn_recover(sd.clone(), r.replica_id, iid, recovering.clone());
+                    }
+                }
             }
 
             if exec_count == 0 {
@@ -121,8 +189,7 @@ impl Server {
 
         println!("serving: {}", api_addr);
 
-        // TODO load cluster conf
-        let qp = MyQPaxos::default();
+        let qp = MyQPaxos::new(sd.clone());
         let s = tonic::transport::Server::builder().add_service(QPaxosServer::new(qp));
 
         let j2 = tokio::spawn(async move {
@@ -158,6 +225,59 @@ impl Server {
     }
 }
 
+/// spawn_recover recovers an instance with the local replica `rid` in a new task, and then
+/// removes it from `recovering`.
+fn spawn_recover(
+    sd: Arc<ServerData>,
+    rid: ReplicaId,
+    iid: InstanceId,
+    recovering: Arc<Mutex<HashSet<InstanceId>>>,
+) {
+    tokio::spawn(async move {
+        let r = &sd.local_replicas[&rid];
+        match recover(iid, r).await {
+            Ok(inst) => println!("success to recover instance {}", inst),
+            Err(e) => println!("{:?} while recover instance {}", e, iid),
+        }
+        recovering.lock().unwrap().remove(&iid);
+    });
+}
+
+/// spawn_periodic spawns a task that runs `f` every `interval`, until a stop signal is received
+/// from `rx` or the sender is dropped.
+fn spawn_periodic<F, Fut>(
+    name: &'static str,
+    interval: Duration,
+    mut rx: Receiver<()>,
+    f: F,
+) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            f().await;
+
+            tokio::time::delay_for(interval).await;
+
+            match rx.try_recv() {
+                Ok(_) => {
+                    println!("exit {} thread with recv stop signal", name);
+                    break;
+                }
+                Err(e) => match e {
+                    TryRecvError::Empty => {}
+                    TryRecvError::Closed => {
+                        println!("exit {} thread with the sender had been dropped", name);
+                        break;
+                    }
+                },
+            }
+        }
+    })
+}
+
 impl Drop for Server {
     fn drop(&mut self) {
         let _ = self.stop();
@@ -7,7 +7,10 @@ use redis;
 
 use std::env;
 use std::fs;
+use std::io::Read;
 use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
 use std::process;
 use std::thread::sleep;
 use std::time::Duration;
@@ -49,7 +52,7 @@ groups:
         let sto = MemEngine::new().unwrap();
         let sto = Arc::new(sto);
         let cluster = ClusterInfo::from_str(cluster).unwrap();
-        let mut server = Server::new(sto.clone(), cluster, node_id.into());
+        let mut server = Server::new(sto.clone(), cluster, node_id.into()).unwrap();
         server.start();
 
         let server_port = 6379;
@@ -86,6 +89,224 @@ groups:
     }
 }
 
+/// ClusterBuilder builds the config of a cluster of in-process nodes for a test.
+/// Every node is given a free replication port and a free redis api port, thus tests do not
+/// have to pick ports that no other test uses.
+/// Replica ids are assigned from 1, in the order groups are added.
+pub struct ClusterBuilder {
+    /// replication port and redis api port of every node.
+    ports: Vec<(u16, u16)>,
+    groups: Vec<String>,
+    n_replicas: usize,
+    confs: Vec<String>,
+}
+
+impl ClusterBuilder {
+    /// new creates a cluster of `n` nodes without any group.
+    pub fn new(n: usize) -> Self {
+        let ports = free_ports(n * 2);
+        ClusterBuilder {
+            ports: ports.chunks(2).map(|x| (x[0], x[1])).collect(),
+            groups: vec![],
+            n_replicas: 0,
+            confs: vec![],
+        }
+    }
+
+    /// group adds a group of keys in `[start, end)` with a replica on every node in `nodes`.
+    pub fn group(self, start: &str, end: &str, nodes: &[usize]) -> Self {
+        self.add_group(format!("-   range: [{}, {}]", start, end), nodes)
+    }
+
+    /// slot_group adds a group like `group` does, which also serves hash slots `[first, last]`.
+    pub fn slot_group(self, start: &str, end: &str, slots: (u16, u16), nodes: &[usize]) -> Self {
+        self.add_group(
+            format!(
+                "-   range: [{}, {}]\n    slots: [{}, {}]",
+                start, end, slots.0, slots.1
+            ),
+            nodes,
+        )
+    }
+
+    fn add_group(mut self, mut g: String, nodes: &[usize]) -> Self {
+        g.push_str("\n    replicas:");
+        for i in nodes.iter() {
+            self.n_replicas += 1;
+            g.push_str(&format!(
+                "\n        {}: {}",
+                self.n_replicas,
+                self.node_id(*i)
+            ));
+        }
+        self.groups.push(g);
+        self
+    }
+
+    /// conf adds a top level config entry, such as `read_consistency`.
+    pub fn conf(mut self, key: &str, value: &str) -> Self {
+        self.confs.push(format!("{}: {}", key, value));
+        self
+    }
+
+    /// node_id returns the id of node `i`, which is also its replication address.
+    pub fn node_id(&self, i: usize) -> String {
+        format!("127.0.0.1:{}", self.repl_port(i))
+    }
+
+    /// repl_port returns the port node `i` serves replication at.
+    pub fn repl_port(&self, i: usize) -> u16 {
+        self.ports[i].0
+    }
+
+    /// api_port returns the port node `i` serves redis protocol at.
+    pub fn api_port(&self, i: usize) -> u16 {
+        self.ports[i].1
+    }
+
+    /// yaml returns the config of the cluster in yaml.
+    pub fn yaml(&self) -> String {
+        let mut yaml = "nodes:".to_string();
+        for i in 0..self.ports.len() {
+            yaml.push_str(&format!(
+                "
+    {}:
+        api_addr: 127.0.0.1:{}
+        replication: {}",
+                self.node_id(i),
+                self.api_port(i),
+                self.node_id(i)
+            ));
+        }
+
+        yaml.push_str("\ngroups:");
+        for g in self.groups.iter() {
+            yaml.push_str("\n");
+            yaml.push_str(g);
+        }
+
+        for c in self.confs.iter() {
+            yaml.push_str("\n");
+            yaml.push_str(c);
+        }
+        yaml.push_str("\n");
+
+        yaml
+    }
+
+    /// build returns the config of the cluster.
+    pub fn build(&self) -> ClusterInfo {
+        ClusterInfo::from_str(&self.yaml()).unwrap()
+    }
+
+    /// start starts node `i` with a storage in memory, and connects to it.
+    pub fn start(&self, i: usize) -> (Server, redis::Connection) {
+        self.start_with(i, Arc::new(MemEngine::new().unwrap()))
+    }
+
+    /// start_with starts node `i` with storage `sto`, and connects to it.
+    pub fn start_with(&self, i: usize, sto: Storage) -> (Server, redis::Connection) {
+        let mut server = Server::new(sto, self.build(), self.node_id(i)).unwrap();
+        server.start();
+
+        let con = redis_connect(self.api_port(i));
+        (server, con)
+    }
+}
+
+/// free_ports returns `n` distinct ports that are not in use.
+pub fn free_ports(n: usize) -> Vec<u16> {
+    // hold all of them until every port is chosen, or a port might be returned twice.
+    let listeners: Vec<_> = (0..n)
+        .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+        .collect();
+
+    listeners
+        .iter()
+        .map(|x| x.local_addr().unwrap().port())
+        .collect()
+}
+
+/// redis_connect connects to a redis api port, waiting until the server is listening.
+pub fn redis_connect(port: u16) -> redis::Connection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{}/", port).as_str()).unwrap();
+    loop {
+        match client.get_connection() {
+            Ok(con) => return con,
+            Err(err) => {
+                if err.is_connection_refusal() {
+                    sleep(Duration::from_millis(50));
+                } else {
+                    panic!("Could not connect: {}", err);
+                }
+            }
+        }
+    }
+}
+
+/// InProcCluster setup a cluster of `n` in-process servers, each with its own storage.
+/// There is one group and node i has replica i + 1.
+pub struct InProcCluster {
+    pub servers: Vec<Server>,
+    pub storages: Vec<Storage>,
+    pub clients: Vec<redis::Client>,
+    pub repl_addrs: Vec<String>,
+}
+
+impl InProcCluster {
+    pub fn new(n: usize) -> Self {
+        let nodes: Vec<usize> = (0..n).collect();
+        let cb = ClusterBuilder::new(n).group("a", "z", &nodes);
+
+        let mut servers = vec![];
+        let mut storages = vec![];
+        let mut clients = vec![];
+        let mut repl_addrs = vec![];
+
+        for i in 0..n {
+            let sto: Storage = Arc::new(MemEngine::new().unwrap());
+            let (server, _con) = cb.start_with(i, sto.clone());
+
+            let client =
+                redis::Client::open(format!("redis://127.0.0.1:{}/", cb.api_port(i)).as_str())
+                    .unwrap();
+
+            servers.push(server);
+            storages.push(sto);
+            clients.push(client);
+            repl_addrs.push(cb.node_id(i));
+        }
+
+        InProcCluster {
+            servers,
+            storages,
+            clients,
+            repl_addrs,
+        }
+    }
+}
+
+/// raw_connect connects to a redis api port without a redis client, to send raw bytes.
+pub fn raw_connect(port: u16) -> TcpStream {
+    let sock = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    sock
+}
+
+/// read_n reads exactly `n` bytes, or until the server closes the connection.
+pub fn read_n(sock: &mut TcpStream, n: usize) -> Vec<u8> {
+    let mut buf = vec![];
+    let mut chunk = [0u8; 4096];
+    while buf.len() < n {
+        let got = sock.read(&mut chunk).unwrap();
+        if got == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..got]);
+    }
+    buf
+}
+
 #[derive(PartialEq)]
 enum ServerType {
     Tcp,
@@ -0,0 +1,103 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::io::Write;
+
+use redis::Value;
+
+use cele::cluster_node_id;
+
+use crate::support::*;
+
+mod support;
+
+fn data(s: &str) -> Value {
+    Value::Data(s.as_bytes().to_vec())
+}
+
+#[test]
+fn test_cluster() {
+    _test_cluster();
+}
+
+#[tokio::main]
+async fn _test_cluster() {
+    // node 0 hosts slots [0, 8191] and node 1 hosts slots [8192, 16383].
+    let cb = ClusterBuilder::new(2)
+        .slot_group("a", "m", (0, 8191), &[0])
+        .slot_group("m", "z", (8192, 16383), &[1]);
+
+    let (mut s1, mut con1) = cb.start(0);
+    let (mut s2, mut con2) = cb.start(1);
+
+    let id1 = cluster_node_id(&cb.node_id(0));
+    let id2 = cluster_node_id(&cb.node_id(1));
+    let (api1, api2) = (cb.api_port(0), cb.api_port(1));
+
+    {
+        let v: i64 = redis::cmd("CLUSTER")
+            .arg("KEYSLOT")
+            .arg("foo")
+            .query(&mut con1)
+            .unwrap();
+        assert_eq!(12182, v);
+
+        let v: String = redis::cmd("CLUSTER").arg("MYID").query(&mut con2).unwrap();
+        assert_eq!(id2, v);
+    }
+
+    {
+        let v: Value = redis::cmd("CLUSTER").arg("SLOTS").query(&mut con1).unwrap();
+        assert_eq!(
+            Value::Bulk(vec![
+                Value::Bulk(vec![
+                    Value::Int(0),
+                    Value::Int(8191),
+                    Value::Bulk(vec![data("127.0.0.1"), Value::Int(api1 as i64), data(&id1)]),
+                ]),
+                Value::Bulk(vec![
+                    Value::Int(8192),
+                    Value::Int(16383),
+                    Value::Bulk(vec![data("127.0.0.1"), Value::Int(api2 as i64), data(&id2)]),
+                ]),
+            ]),
+            v
+        );
+    }
+
+    {
+        let v: String = redis::cmd("CLUSTER").arg("NODES").query(&mut con1).unwrap();
+        assert_eq!(
+            format!(
+                "{} 127.0.0.1:{}@{} myself,master - 0 0 0 connected 0-8191\n\
+                 {} 127.0.0.1:{}@{} master - 0 0 0 connected 8192-16383\n",
+                id1,
+                api1,
+                cb.repl_port(0),
+                id2,
+                api2,
+                cb.repl_port(1)
+            ),
+            v
+        );
+    }
+
+    {
+        // foo is in slot 12182, served by node 1.
+        redis::cmd("SET").arg("foo").arg("1").execute(&mut con2);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("foo").query(&mut con2).unwrap();
+        assert_eq!(Some(b"1".to_vec()), v);
+
+        let mut sock = raw_connect(api1);
+        sock.write_all(b"GET foo\r\n").unwrap();
+        let want = format!("-MOVED 12182 127.0.0.1:{}\r\n", api2);
+        assert_eq!(want.as_bytes().to_vec(), read_n(&mut sock, want.len()));
+    }
+
+    drop(con1);
+    drop(con2);
+    s1.stop().unwrap();
+    s2.stop().unwrap();
+    s1.join().await.unwrap();
+    s2.join().await.unwrap();
+}
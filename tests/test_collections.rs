@@ -0,0 +1,267 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::collections::HashSet;
+
+use redis::RedisResult;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_collections() {
+    let ctx = InProcContext::new();
+    let mut con = ctx.client.get_connection().unwrap();
+
+    {
+        // hash
+        let n: i64 = redis::cmd("HSET")
+            .arg("h")
+            .arg("f1")
+            .arg("v1")
+            .arg("f2")
+            .arg("v2")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(2, n);
+
+        let n: i64 = redis::cmd("HSET")
+            .arg("h")
+            .arg("f1")
+            .arg("v3")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(0, n);
+
+        let v: Option<String> = redis::cmd("HGET")
+            .arg("h")
+            .arg("f1")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(Some("v3".to_owned()), v);
+
+        let v: Vec<String> = redis::cmd("HGETALL").arg("h").query(&mut con).unwrap();
+        assert_eq!(vec!["f1", "v3", "f2", "v2"], v);
+
+        let n: i64 = redis::cmd("HDEL")
+            .arg("h")
+            .arg("f1")
+            .arg("nosuchfield")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+
+        let n: i64 = redis::cmd("HLEN").arg("h").query(&mut con).unwrap();
+        assert_eq!(1, n);
+        let n: i64 = redis::cmd("HEXISTS")
+            .arg("h")
+            .arg("f2")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+
+        let r: RedisResult<()> = redis::cmd("HSET")
+            .arg("h")
+            .arg("f1")
+            .arg("v1")
+            .arg("f2")
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("wrong number of arguments"));
+    }
+
+    {
+        // list
+        let n: i64 = redis::cmd("RPUSH")
+            .arg("l")
+            .arg("b")
+            .arg("c")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(2, n);
+        let n: i64 = redis::cmd("LPUSH")
+            .arg("l")
+            .arg("a")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(3, n);
+
+        let v: Vec<String> = redis::cmd("LRANGE")
+            .arg("l")
+            .arg(0)
+            .arg(-1)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec!["a", "b", "c"], v);
+
+        let v: Option<String> = redis::cmd("RPOP").arg("l").query(&mut con).unwrap();
+        assert_eq!(Some("c".to_owned()), v);
+        let v: Option<String> = redis::cmd("LPOP").arg("l").query(&mut con).unwrap();
+        assert_eq!(Some("a".to_owned()), v);
+        let n: i64 = redis::cmd("LLEN").arg("l").query(&mut con).unwrap();
+        assert_eq!(1, n);
+    }
+
+    {
+        // set
+        let n: i64 = redis::cmd("SADD")
+            .arg("s")
+            .arg("x")
+            .arg("y")
+            .arg("x")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(2, n);
+
+        let v: HashSet<String> = redis::cmd("SMEMBERS").arg("s").query(&mut con).unwrap();
+        let want: HashSet<String> = vec!["x".to_owned(), "y".to_owned()].into_iter().collect();
+        assert_eq!(want, v);
+
+        let n: i64 = redis::cmd("SREM")
+            .arg("s")
+            .arg("x")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+        let n: i64 = redis::cmd("SISMEMBER")
+            .arg("s")
+            .arg("x")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(0, n);
+        let n: i64 = redis::cmd("SCARD").arg("s").query(&mut con).unwrap();
+        assert_eq!(1, n);
+    }
+
+    {
+        // sorted set
+        let n: i64 = redis::cmd("ZADD")
+            .arg("z")
+            .arg(3)
+            .arg("a")
+            .arg(1.5)
+            .arg("b")
+            .arg(2)
+            .arg("c")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(3, n);
+
+        let v: Vec<String> = redis::cmd("ZRANGE")
+            .arg("z")
+            .arg(0)
+            .arg(-1)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec!["b", "c", "a"], v);
+
+        let v: Vec<String> = redis::cmd("ZRANGE")
+            .arg("z")
+            .arg(0)
+            .arg(1)
+            .arg("WITHSCORES")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec!["b", "1.5", "c", "2"], v);
+
+        let v: Option<String> = redis::cmd("ZSCORE")
+            .arg("z")
+            .arg("a")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(Some("3".to_owned()), v);
+
+        let n: i64 = redis::cmd("ZREM")
+            .arg("z")
+            .arg("a")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+        let n: i64 = redis::cmd("ZCARD").arg("z").query(&mut con).unwrap();
+        assert_eq!(2, n);
+
+        let r: RedisResult<()> = redis::cmd("ZADD")
+            .arg("z")
+            .arg("x")
+            .arg("a")
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("not a valid float"));
+    }
+
+    {
+        // TYPE and commands on keys of the wrong type
+        redis::cmd("SET").arg("str").arg("v").execute(&mut con);
+
+        for (k, want) in &[
+            ("str", "string"),
+            ("h", "hash"),
+            ("l", "list"),
+            ("s", "set"),
+            ("z", "zset"),
+            ("nosuchkey", "none"),
+        ] {
+            let v: String = redis::cmd("TYPE").arg(*k).query(&mut con).unwrap();
+            assert_eq!(*want, v);
+        }
+
+        let r: RedisResult<()> = redis::cmd("GET").arg("h").query(&mut con);
+        assert!(format!("{:?}", r).contains("WRONGTYPE"));
+        let r: RedisResult<()> = redis::cmd("LPUSH").arg("str").arg("a").query(&mut con);
+        assert!(format!("{:?}", r).contains("WRONGTYPE"));
+
+        let v: Vec<Option<String>> = redis::cmd("MGET")
+            .arg("str")
+            .arg("h")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec![Some("v".to_owned()), None], v);
+
+        // the meta of h is stored at "mh" too, but in another column family.
+        redis::cmd("SET").arg("mh").arg("v").execute(&mut con);
+        let v: String = redis::cmd("GET").arg("mh").query(&mut con).unwrap();
+        assert_eq!("v", v);
+        let v: String = redis::cmd("TYPE").arg("h").query(&mut con).unwrap();
+        assert_eq!("hash", v);
+    }
+
+    {
+        // EXISTS, EXPIRE and DEL on collections
+        let n: i64 = redis::cmd("EXISTS")
+            .arg("h")
+            .arg("l")
+            .arg("nosuchkey")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(2, n);
+
+        let n: i64 = redis::cmd("EXPIRE")
+            .arg("s")
+            .arg(100)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+        let n: i64 = redis::cmd("TTL").arg("s").query(&mut con).unwrap();
+        assert_eq!(100, n);
+
+        let n: i64 = redis::cmd("DEL")
+            .arg("h")
+            .arg("l")
+            .arg("s")
+            .arg("z")
+            .arg("nosuchkey")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(4, n);
+
+        let n: i64 = redis::cmd("HLEN").arg("h").query(&mut con).unwrap();
+        assert_eq!(0, n);
+        let n: i64 = redis::cmd("TTL").arg("s").query(&mut con).unwrap();
+        assert_eq!(-2, n);
+
+        // a string replaces a collection
+        redis::cmd("SADD").arg("s").arg("x").execute(&mut con);
+        redis::cmd("SET").arg("s").arg("v").execute(&mut con);
+        let v: String = redis::cmd("GET").arg("s").query(&mut con).unwrap();
+        assert_eq!("v", v);
+    }
+}
@@ -0,0 +1,253 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use prost::Message;
+use redis::RedisResult;
+use redis::Value;
+
+use epaxos::qpaxos::Command;
+use epaxos::qpaxos::TxnInfo;
+use epaxos::qpaxos::TxnIntent;
+use epaxos::replica::decision_key;
+use epaxos::replica::intent_key;
+use epaxos::Storage;
+use storage::DBColumnFamily;
+use storage::MemEngine;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_cross_group() {
+    _test_cross_group();
+}
+
+#[test]
+fn test_cross_group_remote() {
+    _test_cross_group_remote();
+}
+
+#[tokio::main]
+async fn _test_cross_group() {
+    // keys in [a, m) and keys in [m, z) are served by two groups on the same node.
+    let cb = ClusterBuilder::new(1)
+        .group("a", "m", &[0])
+        .group("m", "z", &[0]);
+
+    let sto: Storage = Arc::new(MemEngine::new().unwrap());
+    let (mut server, mut con) = cb.start_with(0, sto.clone());
+
+    {
+        // one command on keys of two groups
+        redis::cmd("MSET")
+            .arg("b")
+            .arg("1")
+            .arg("x")
+            .arg("2")
+            .execute(&mut con);
+        let v: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg("b")
+            .arg("x")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec![Some(b"1".to_vec()), Some(b"2".to_vec())], v);
+    }
+
+    {
+        // a transaction on keys of two groups, results are in the order of commands.
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("INCR").arg("x").execute(&mut con);
+        redis::cmd("APPEND").arg("b").arg("0").execute(&mut con);
+        redis::cmd("GET").arg("x").execute(&mut con);
+
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(
+            Value::Bulk(vec![
+                Value::Int(3),
+                Value::Int(2),
+                Value::Data(b"3".to_vec()),
+            ]),
+            v
+        );
+    }
+
+    {
+        // a watched key changed: nothing applied.
+        redis::cmd("WATCH").arg("b").execute(&mut con);
+        redis::cmd("SET").arg("b").arg("5").execute(&mut con);
+
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("SET").arg("b").arg("6").execute(&mut con);
+        redis::cmd("SET").arg("x").arg("6").execute(&mut con);
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(Value::Nil, v);
+
+        let v: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg("b")
+            .arg("x")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec![Some(b"5".to_vec()), Some(b"3".to_vec())], v);
+    }
+
+    {
+        // an intent left by a crashed coordinator locks x, until it is aborted by recovery.
+        let intent = TxnIntent {
+            txn: Some(TxnInfo {
+                id: b"crashed".to_vec(),
+                primary: b"c".to_vec(),
+                ctime_ms: 1,
+                seq: 0,
+            }),
+            cmds: vec![Command::from(("Set", "x", "9"))],
+            keys: vec![b"c".to_vec(), b"x".to_vec()],
+        };
+        let mut v = vec![];
+        intent.encode(&mut v).unwrap();
+        sto.set(DBColumnFamily::Status, &intent_key(b"x"), &v)
+            .unwrap();
+
+        let r: RedisResult<()> = redis::cmd("MSET")
+            .arg("c")
+            .arg("7")
+            .arg("x")
+            .arg("7")
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("TXNABORT"));
+
+        // the decision is removed too once the intent is aborted, after it is seen by the server
+        // for `DEFAULT_TXN_TIMEOUT`, no matter when it is created.
+        let mut locked = true;
+        for _ in 0..100 {
+            sleep(Duration::from_millis(100));
+            let get = |k: &[u8]| sto.get(DBColumnFamily::Status, k).unwrap();
+            if get(&intent_key(b"x")).is_none() && get(&decision_key(b"crashed")).is_none() {
+                locked = false;
+                break;
+            }
+        }
+        assert!(!locked);
+
+        redis::cmd("MSET")
+            .arg("c")
+            .arg("7")
+            .arg("x")
+            .arg("7")
+            .execute(&mut con);
+        let v: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg("c")
+            .arg("x")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec![Some(b"7".to_vec()), Some(b"7".to_vec())], v);
+    }
+
+    drop(con);
+    server.stop().unwrap();
+    server.join().await.unwrap();
+}
+
+#[tokio::main]
+async fn _test_cross_group_remote() {
+    // keys in [a, m) are served by node 0 and keys in [m, z) by node 1.
+    let cb = ClusterBuilder::new(2)
+        .group("a", "m", &[0])
+        .group("m", "z", &[1]);
+
+    let (mut s0, mut con) = cb.start(0);
+    let (mut s1, mut con1) = cb.start(1);
+
+    let mget = |con: &mut redis::Connection, keys: &[&str]| -> Vec<Option<String>> {
+        redis::cmd("MGET").arg(keys).query(con).unwrap()
+    };
+
+    // coordinated by node 0, with the group on node 1 proposed remotely.
+    redis::cmd("MSET")
+        .arg("b")
+        .arg("1")
+        .arg("x")
+        .arg("2")
+        .execute(&mut con);
+    assert_eq!(
+        vec![Some("1".to_owned()), Some("2".to_owned())],
+        mget(&mut con, &["b", "x"])
+    );
+    assert_eq!(
+        vec![Some("1".to_owned()), Some("2".to_owned())],
+        mget(&mut con1, &["b", "x"])
+    );
+
+    // forwarded by the first key, and coordinated by node 1.
+    redis::cmd("MSET")
+        .arg("x")
+        .arg("3")
+        .arg("b")
+        .arg("4")
+        .execute(&mut con);
+    assert_eq!(
+        vec![Some("4".to_owned()), Some("3".to_owned())],
+        mget(&mut con, &["b", "x"])
+    );
+
+    {
+        // a transaction on keys of two nodes, results are in the order of commands.
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("INCR").arg("x").execute(&mut con);
+        redis::cmd("APPEND").arg("b").arg("0").execute(&mut con);
+        redis::cmd("GET").arg("x").execute(&mut con);
+
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(
+            Value::Bulk(vec![
+                Value::Int(4),
+                Value::Int(2),
+                Value::Data(b"4".to_vec()),
+            ]),
+            v
+        );
+    }
+
+    {
+        // a transaction on keys of a remote group only.
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("SET").arg("x").arg("5").execute(&mut con);
+        redis::cmd("SET").arg("y").arg("6").execute(&mut con);
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(Value::Bulk(vec![Value::Okay, Value::Okay]), v);
+    }
+
+    {
+        // a watched key on node 1 changed: nothing applied.
+        redis::cmd("WATCH").arg("x").execute(&mut con);
+        redis::cmd("SET").arg("x").arg("7").execute(&mut con1);
+
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("SET").arg("b").arg("8").execute(&mut con);
+        redis::cmd("SET").arg("x").arg("8").execute(&mut con);
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(Value::Nil, v);
+
+        assert_eq!(
+            vec![Some("40".to_owned()), Some("7".to_owned())],
+            mget(&mut con, &["b", "x"])
+        );
+    }
+
+    // keys of one remote group are still served.
+    assert_eq!(
+        vec![Some("7".to_owned()), Some("6".to_owned())],
+        mget(&mut con, &["x", "y"])
+    );
+
+    drop(con);
+    drop(con1);
+    s0.stop().unwrap();
+    s1.stop().unwrap();
+    s0.join().await.unwrap();
+    s1.join().await.unwrap();
+}
@@ -0,0 +1,188 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use redis::RedisResult;
+
+use storage::DBColumnFamily;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_expire() {
+    let ctx = InProcContext::new();
+    let mut con = ctx.client.get_connection().unwrap();
+
+    let ttl = |con: &mut redis::Connection, name: &str, k: &str| -> i64 {
+        redis::cmd(name).arg(k).query(con).unwrap()
+    };
+
+    {
+        // SET with EX, PX and KEEPTTL
+        redis::cmd("SET")
+            .arg("foo")
+            .arg("bar")
+            .arg("ex")
+            .arg(100)
+            .execute(&mut con);
+        assert_eq!(100, ttl(&mut con, "TTL", "foo"));
+
+        redis::cmd("SET")
+            .arg("foo")
+            .arg("bar")
+            .arg("KEEPTTL")
+            .execute(&mut con);
+        assert_eq!(100, ttl(&mut con, "TTL", "foo"));
+
+        redis::cmd("SET")
+            .arg("foo")
+            .arg("bar")
+            .arg("PX")
+            .arg(50_000)
+            .execute(&mut con);
+        let ms = ttl(&mut con, "PTTL", "foo");
+        assert!(ms > 49_000 && ms <= 50_000, "pttl: {}", ms);
+
+        // a plain SET removes the ttl
+        redis::cmd("SET").arg("foo").arg("bar").execute(&mut con);
+        assert_eq!(-1, ttl(&mut con, "TTL", "foo"));
+        assert_eq!(-2, ttl(&mut con, "TTL", "nosuchkey"));
+    }
+
+    {
+        // SET NX
+        let r: Option<String> = redis::cmd("SET")
+            .arg("foo")
+            .arg("x")
+            .arg("NX")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(None, r);
+
+        let r: Option<String> = redis::cmd("SET")
+            .arg("nx")
+            .arg("x")
+            .arg("NX")
+            .arg("EX")
+            .arg(10)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(Some("OK".to_owned()), r);
+        assert_eq!(10, ttl(&mut con, "TTL", "nx"));
+    }
+
+    {
+        // bad options
+        let r: RedisResult<()> = redis::cmd("SET")
+            .arg("foo")
+            .arg("bar")
+            .arg("EX")
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("syntax error"));
+
+        let r: RedisResult<()> = redis::cmd("SET")
+            .arg("foo")
+            .arg("bar")
+            .arg("EX")
+            .arg(1)
+            .arg("PX")
+            .arg(1)
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("syntax error"));
+
+        let r: RedisResult<()> = redis::cmd("SET")
+            .arg("foo")
+            .arg("bar")
+            .arg("EX")
+            .arg(0)
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("invalid expire time in 'set' command"));
+
+        let r: RedisResult<()> = redis::cmd("SETEX")
+            .arg("foo")
+            .arg("abc")
+            .arg("bar")
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("not an integer"));
+    }
+
+    {
+        // SETEX, EXPIRE, EXPIREAT, PERSIST
+        redis::cmd("SETEX")
+            .arg("foo")
+            .arg(20)
+            .arg("bar")
+            .execute(&mut con);
+        assert_eq!(20, ttl(&mut con, "TTL", "foo"));
+
+        let n: i64 = redis::cmd("EXPIRE")
+            .arg("foo")
+            .arg(30)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+        assert_eq!(30, ttl(&mut con, "TTL", "foo"));
+
+        let n: i64 = redis::cmd("EXPIRE")
+            .arg("nosuchkey")
+            .arg(30)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(0, n);
+
+        let n: i64 = redis::cmd("EXPIREAT")
+            .arg("foo")
+            .arg(4_000_000_000i64)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+        assert!(ttl(&mut con, "TTL", "foo") > 100_000);
+
+        let n: i64 = redis::cmd("PERSIST").arg("foo").query(&mut con).unwrap();
+        assert_eq!(1, n);
+        let n: i64 = redis::cmd("PERSIST").arg("foo").query(&mut con).unwrap();
+        assert_eq!(0, n);
+        assert_eq!(-1, ttl(&mut con, "TTL", "foo"));
+
+        // an expire time in the past deletes the key
+        let n: i64 = redis::cmd("PEXPIREAT")
+            .arg("foo")
+            .arg(1)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+        let v: Option<String> = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(None, v);
+    }
+
+    {
+        // an expired key is absent at once, and removed from storage by the sweep
+        redis::cmd("PSETEX")
+            .arg("gone")
+            .arg(100)
+            .arg("v")
+            .execute(&mut con);
+        let v: Option<String> = redis::cmd("GET").arg("gone").query(&mut con).unwrap();
+        assert_eq!(Some("v".to_owned()), v);
+
+        sleep(Duration::from_millis(200));
+
+        let v: Option<String> = redis::cmd("GET").arg("gone").query(&mut con).unwrap();
+        assert_eq!(None, v);
+        let n: i64 = redis::cmd("EXISTS").arg("gone").query(&mut con).unwrap();
+        assert_eq!(0, n);
+        assert_eq!(-2, ttl(&mut con, "PTTL", "gone"));
+
+        sleep(Duration::from_millis(2_500));
+
+        let v = ctx
+            .storage
+            .get(DBColumnFamily::Default, &b"gone".to_vec())
+            .unwrap();
+        assert_eq!(None, v);
+    }
+}
@@ -0,0 +1,192 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use crate::support::*;
+
+mod support;
+
+fn keys(con: &mut redis::Connection) -> Vec<String> {
+    let mut ks: Vec<String> = redis::cmd("KEYS").arg("*").query(con).unwrap();
+    ks.sort();
+    ks
+}
+
+#[test]
+fn test_flush() {
+    _test_flush();
+}
+
+#[tokio::main]
+async fn _test_flush() {
+    // node 0 hosts only the group of [a, m) and node 1 hosts only the group of [m, z).
+    let cb = ClusterBuilder::new(2)
+        .group("a", "m", &[0])
+        .group("m", "z", &[1]);
+
+    let (mut s1, mut con1) = cb.start(0);
+    let (mut s2, mut con2) = cb.start(1);
+
+    let fill = |con: &mut redis::Connection| {
+        for k in ["b", "k", "n", "x"].iter() {
+            redis::cmd("SET").arg(*k).arg("v").execute(con);
+        }
+        redis::cmd("HSET").arg("c").arg("f").arg("v").execute(con);
+        redis::cmd("SADD").arg("o").arg("m").execute(con);
+        redis::cmd("SET")
+            .arg("e")
+            .arg("v")
+            .arg("EX")
+            .arg("100")
+            .execute(con);
+    };
+
+    let all = vec!["b", "c", "e", "k", "n", "o", "x"];
+
+    {
+        // DELRANGE removes strings and collections in a range, across groups.
+        fill(&mut con1);
+        assert_eq!(all, keys(&mut con2));
+
+        let r: String = redis::cmd("DELRANGE")
+            .arg("c")
+            .arg("o")
+            .query(&mut con1)
+            .unwrap();
+        assert_eq!("OK", r);
+        assert_eq!(vec!["b", "o", "x"], keys(&mut con1));
+        assert_eq!(vec!["b", "o", "x"], keys(&mut con2));
+
+        let n: i64 = redis::cmd("HLEN").arg("c").query(&mut con2).unwrap();
+        assert_eq!(0, n);
+    }
+
+    {
+        // a key set again after a flush does not inherit its expire-at.
+        redis::cmd("FLUSHDB").execute(&mut con2);
+        let n: i64 = redis::cmd("DBSIZE").query(&mut con1).unwrap();
+        assert_eq!(0, n);
+
+        redis::cmd("SET").arg("e").arg("v").execute(&mut con1);
+        let ttl: i64 = redis::cmd("TTL").arg("e").query(&mut con1).unwrap();
+        assert_eq!(-1, ttl);
+    }
+
+    {
+        // FLUSHALL and FLUSHDB ASYNC remove every key too.
+        for args in [vec!["FLUSHALL"], vec!["FLUSHDB", "ASYNC"]].iter() {
+            fill(&mut con1);
+            assert_eq!(all, keys(&mut con1));
+
+            let r: String = redis::cmd(args[0])
+                .arg(&args[1..])
+                .query(&mut con2)
+                .unwrap();
+            assert_eq!("OK", r);
+            assert_eq!(Vec::<String>::new(), keys(&mut con1));
+            assert_eq!(Vec::<String>::new(), keys(&mut con2));
+        }
+    }
+
+    {
+        let r: redis::RedisResult<redis::Value> = redis::cmd("FLUSHDB").arg("FOO").query(&mut con1);
+        assert!(r.is_err());
+    }
+
+    s1.stop().unwrap();
+    s2.stop().unwrap();
+    s1.join().await.unwrap();
+    s2.join().await.unwrap();
+}
+
+#[test]
+fn test_flush_slots() {
+    _test_flush_slots();
+}
+
+#[tokio::main]
+async fn _test_flush_slots() {
+    // "bar" is in slot 5061, served by node 0 and 1, and "foo" is in slot 12182, served only by
+    // node 1, on which keys of both groups are in one storage.
+    let cb = ClusterBuilder::new(2)
+        .slot_group("a", "m", (0, 8191), &[0, 1])
+        .slot_group("m", "z", (8192, 16383), &[1]);
+
+    let (mut s1, mut con1) = cb.start(0);
+    let (mut s2, mut con2) = cb.start(1);
+
+    let get = |con: &mut redis::Connection, k: &str| -> Option<String> {
+        redis::cmd("GET").arg(k).query(con).unwrap()
+    };
+
+    let fill = |con1: &mut redis::Connection, con2: &mut redis::Connection| {
+        redis::cmd("SET").arg("bar").arg("v").execute(con1);
+        redis::cmd("HSET")
+            .arg("{bar}h")
+            .arg("f")
+            .arg("v")
+            .execute(con1);
+        redis::cmd("SET").arg("foo").arg("v").execute(con2);
+        redis::cmd("HSET")
+            .arg("{foo}h")
+            .arg("f")
+            .arg("v")
+            .execute(con2);
+    };
+
+    {
+        // FLUSHDB removes keys of every group, including one not hosted by the node.
+        fill(&mut con1, &mut con2);
+
+        let r: String = redis::cmd("FLUSHDB").query(&mut con1).unwrap();
+        assert_eq!("OK", r);
+        assert_eq!(None, get(&mut con1, "bar"));
+        assert_eq!(None, get(&mut con2, "bar"));
+        assert_eq!(None, get(&mut con2, "foo"));
+
+        let n: i64 = redis::cmd("HLEN").arg("{foo}h").query(&mut con2).unwrap();
+        assert_eq!(0, n);
+        let n: i64 = redis::cmd("HLEN").arg("{bar}h").query(&mut con2).unwrap();
+        assert_eq!(0, n);
+    }
+
+    {
+        // DELRANGE with SLOT removes keys only from the group serving the slot.
+        fill(&mut con1, &mut con2);
+
+        let r: String = redis::cmd("DELRANGE")
+            .arg("")
+            .arg("")
+            .arg("SLOT")
+            .arg("9000")
+            .query(&mut con1)
+            .unwrap();
+        assert_eq!("OK", r);
+        assert_eq!(None, get(&mut con2, "foo"));
+        assert_eq!(Some("v".to_string()), get(&mut con2, "bar"));
+
+        let n: i64 = redis::cmd("HLEN").arg("{bar}h").query(&mut con2).unwrap();
+        assert_eq!(1, n);
+    }
+
+    {
+        let cases = vec![
+            vec!["DELRANGE", "", "", "SLOT"],
+            vec!["DELRANGE", "", "", "SLOT", "x"],
+            vec!["DELRANGE", "", "", "SLOT", "16384"],
+            vec!["DELRANGE", "", "", "FOO", "1"],
+        ];
+        for args in cases.iter() {
+            let mut c = redis::cmd(args[0]);
+            c.arg(&args[1..]);
+            let r: redis::RedisResult<redis::Value> = c.query(&mut con1);
+            assert!(r.is_err(), "{:?}", args);
+        }
+    }
+
+    drop(con1);
+    drop(con2);
+    s1.stop().unwrap();
+    s2.stop().unwrap();
+    s1.join().await.unwrap();
+    s2.join().await.unwrap();
+}
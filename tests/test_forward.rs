@@ -0,0 +1,58 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use redis::RedisResult;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_forward() {
+    _test_forward();
+}
+
+#[tokio::main]
+async fn _test_forward() {
+    // node 0 hosts only the group of [a, m) and node 1 hosts only the group of [m, z).
+    let cb = ClusterBuilder::new(2)
+        .group("a", "m", &[0])
+        .group("m", "z", &[1]);
+
+    let (mut s1, mut con1) = cb.start(0);
+    let (mut s2, mut con2) = cb.start(1);
+
+    {
+        // x is served by node 1, the request to node 0 is forwarded.
+        redis::cmd("SET").arg("x").arg("1").execute(&mut con1);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("x").query(&mut con1).unwrap();
+        assert_eq!(Some(b"1".to_vec()), v);
+
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("x").query(&mut con2).unwrap();
+        assert_eq!(Some(b"1".to_vec()), v);
+
+        let v: i64 = redis::cmd("INCR").arg("x").query(&mut con1).unwrap();
+        assert_eq!(2, v);
+    }
+
+    {
+        // error replies are forwarded as is.
+        redis::cmd("SET").arg("y").arg("foo").execute(&mut con2);
+        let r: RedisResult<i64> = redis::cmd("INCR").arg("y").query(&mut con1);
+        assert!(format!("{:?}", r).contains("not an integer"));
+    }
+
+    {
+        // keys of a local group are not forwarded.
+        redis::cmd("SET").arg("b").arg("2").execute(&mut con1);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("b").query(&mut con2).unwrap();
+        assert_eq!(Some(b"2".to_vec()), v);
+    }
+
+    drop(con1);
+    drop(con2);
+    s1.stop().unwrap();
+    s2.stop().unwrap();
+    s1.join().await.unwrap();
+    s2.join().await.unwrap();
+}
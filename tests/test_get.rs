@@ -0,0 +1,47 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_get() {
+    _test_get();
+}
+
+#[tokio::main]
+async fn _test_get() {
+    let ctx = InProcContext::new();
+    let mut con = ctx.client.get_connection().unwrap();
+
+    {
+        // get a key that is never set
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(None, v);
+    }
+
+    {
+        // read after write
+        redis::cmd("SET").arg("foo").arg("bar").execute(&mut con);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(Some(b"bar".to_vec()), v);
+    }
+
+    {
+        // read after overriding
+        redis::cmd("SET").arg("foo").arg(42).execute(&mut con);
+        let v: i64 = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(42, v);
+    }
+
+    {
+        // other keys are not affected
+        redis::cmd("SET").arg("x").arg("y").execute(&mut con);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("x").query(&mut con).unwrap();
+        assert_eq!(Some(b"y".to_vec()), v);
+
+        let v: i64 = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(42, v);
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::io::Write;
+
+use crate::support::*;
+
+mod support;
+
+fn hello_reply(proto: i64) -> Vec<u8> {
+    hello_reply_mode(proto, "standalone")
+}
+
+fn hello_reply_mode(proto: i64, mode: &str) -> Vec<u8> {
+    let version = env!("CARGO_PKG_VERSION");
+    let head = if proto == 3 { "%6" } else { "*12" };
+    format!(
+        "{}\r\n$6\r\nserver\r\n$11\r\nceleritasdb\r\n$7\r\nversion\r\n${}\r\n{}\r\n\
+         $5\r\nproto\r\n:{}\r\n$4\r\nmode\r\n${}\r\n{}\r\n$4\r\nrole\r\n$6\r\nmaster\r\n\
+         $7\r\nmodules\r\n*0\r\n",
+        head,
+        version.len(),
+        version,
+        proto,
+        mode.len(),
+        mode
+    )
+    .into_bytes()
+}
+
+#[test]
+fn test_hello() {
+    let _ctx = InProcContext::new();
+
+    {
+        // HELLO without version keeps RESP2
+        let mut sock = raw_connect(6379);
+        sock.write_all(b"HELLO\r\nGET nosuchkey\r\n").unwrap();
+
+        let want = [hello_reply(2), b"$-1\r\n".to_vec()].concat();
+        assert_eq!(want, read_n(&mut sock, want.len()));
+    }
+
+    {
+        // switch to RESP3: the HELLO reply and later replies are in RESP3
+        let mut sock = raw_connect(6379);
+        sock.write_all(b"HELLO 3\r\nGET nosuchkey\r\n").unwrap();
+
+        let want = [hello_reply(3), b"_\r\n".to_vec()].concat();
+        assert_eq!(want, read_n(&mut sock, want.len()));
+
+        // and back to RESP2
+        sock.write_all(b"HELLO 2\r\nGET nosuchkey\r\n").unwrap();
+
+        let want = [hello_reply(2), b"$-1\r\n".to_vec()].concat();
+        assert_eq!(want, read_n(&mut sock, want.len()));
+    }
+
+    {
+        // other connections are not affected
+        let mut sock = raw_connect(6379);
+        sock.write_all(b"GET nosuchkey\r\n").unwrap();
+
+        let want = b"$-1\r\n";
+        assert_eq!(want.to_vec(), read_n(&mut sock, want.len()));
+    }
+
+    {
+        // unsupported version
+        let mut sock = raw_connect(6379);
+        sock.write_all(b"HELLO 4\r\nHELLO x\r\n").unwrap();
+
+        let want = [
+            &b"-NOPROTO unsupported protocol version\r\n"[..],
+            &b"-ERR Protocol version is not an integer or out of range\r\n"[..],
+        ]
+        .concat();
+        assert_eq!(want, read_n(&mut sock, want.len()));
+    }
+}
+
+#[test]
+fn test_hello_cluster_mode() {
+    _test_hello_cluster_mode();
+}
+
+#[tokio::main]
+async fn _test_hello_cluster_mode() {
+    // keys are assigned to groups by hash slots
+    let cb = ClusterBuilder::new(1).slot_group("a", "z", (0, 16383), &[0]);
+    let (mut server, con) = cb.start(0);
+
+    let mut sock = raw_connect(cb.api_port(0));
+    sock.write_all(b"HELLO 3\r\n").unwrap();
+
+    let want = hello_reply_mode(3, "cluster");
+    assert_eq!(want, read_n(&mut sock, want.len()));
+
+    drop(sock);
+    drop(con);
+    server.stop().unwrap();
+    server.join().await.unwrap();
+}
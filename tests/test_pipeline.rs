@@ -0,0 +1,88 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::io::Write;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_pipeline() {
+    let _ctx = InProcContext::new();
+
+    {
+        // a request split into several writes
+        let mut sock = raw_connect(6379);
+        let req = b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n";
+        for part in req.chunks(5) {
+            sock.write_all(part).unwrap();
+            sock.flush().unwrap();
+            sleep(Duration::from_millis(10));
+        }
+
+        let want = b"+OK\r\n";
+        assert_eq!(want.to_vec(), read_n(&mut sock, want.len()));
+    }
+
+    {
+        // pipelined requests in one write are replied in order
+        let mut sock = raw_connect(6379);
+        let req = [
+            &b"*3\r\n$3\r\nSET\r\n$1\r\nx\r\n$1\r\n1\r\n"[..],
+            &b"*2\r\n$3\r\nGET\r\n$1\r\nx\r\n"[..],
+            &b"*3\r\n$3\r\nSET\r\n$1\r\nx\r\n$1\r\n2\r\n"[..],
+            &b"*2\r\n$3\r\nGET\r\n$1\r\nx\r\n"[..],
+            &b"GET foo\r\n"[..],
+        ]
+        .concat();
+        sock.write_all(&req).unwrap();
+
+        let want = b"+OK\r\n$1\r\n1\r\n+OK\r\n$1\r\n2\r\n$3\r\nbar\r\n";
+        assert_eq!(want.to_vec(), read_n(&mut sock, want.len()));
+    }
+
+    {
+        // a value larger than a socket read
+        let mut sock = raw_connect(6379);
+        let val = vec![b'v'; 100 * 1024];
+        let req = [
+            format!("*3\r\n$3\r\nSET\r\n$3\r\nbig\r\n${}\r\n", val.len()).as_bytes(),
+            &val[..],
+            b"\r\n*2\r\n$3\r\nGET\r\n$3\r\nbig\r\n",
+        ]
+        .concat();
+        sock.write_all(&req).unwrap();
+
+        let want = [
+            format!("+OK\r\n${}\r\n", val.len()).as_bytes(),
+            &val[..],
+            b"\r\n",
+        ]
+        .concat();
+        assert_eq!(want, read_n(&mut sock, want.len()));
+    }
+
+    {
+        // malformed input gets an error reply, then the connection is closed
+        let mut sock = raw_connect(6379);
+        sock.write_all(b"*x\r\n").unwrap();
+
+        let got = read_n(&mut sock, 1024);
+        assert_eq!(
+            b"-ERR Protocol error: invalid multibulk length\r\n".to_vec(),
+            got
+        );
+    }
+
+    {
+        // the server is still serving
+        let mut sock = raw_connect(6379);
+        sock.write_all(b"*2\r\n$3\r\nGET\r\n$1\r\nx\r\n").unwrap();
+
+        let want = b"$1\r\n2\r\n";
+        assert_eq!(want.to_vec(), read_n(&mut sock, want.len()));
+    }
+}
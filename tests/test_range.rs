@@ -0,0 +1,107 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use crate::support::*;
+
+mod support;
+
+/// pairs builds the reply of RANGE from keys, of which the value is `v<key>`.
+fn pairs(keys: &[&str]) -> Vec<String> {
+    let mut rst = vec![];
+    for k in keys.iter() {
+        rst.push(k.to_string());
+        rst.push(format!("v{}", k));
+    }
+    rst
+}
+
+#[test]
+fn test_range() {
+    _test_range();
+}
+
+#[tokio::main]
+async fn _test_range() {
+    // node 0 hosts only the group of [a, m) and node 1 hosts only the group of [m, z).
+    let cb = ClusterBuilder::new(2)
+        .group("a", "m", &[0])
+        .group("m", "z", &[1]);
+
+    let (mut s1, mut con1) = cb.start(0);
+    let (mut s2, mut con2) = cb.start(1);
+
+    for k in ["b", "a", "k", "n", "m", "x"].iter() {
+        redis::cmd("SET")
+            .arg(*k)
+            .arg(format!("v{}", k))
+            .execute(&mut con1);
+    }
+    redis::cmd("HSET")
+        .arg("c")
+        .arg("f")
+        .arg("v")
+        .execute(&mut con1);
+
+    {
+        // ranges across groups, in key order or in reverse order.
+        let cases: Vec<(Vec<&str>, Vec<&str>)> = vec![
+            (vec!["a", "z"], vec!["a", "b", "k", "m", "n", "x"]),
+            (vec!["", ""], vec!["a", "b", "k", "m", "n", "x"]),
+            (vec!["b", "n"], vec!["b", "k", "m"]),
+            (vec!["m", ""], vec!["m", "n", "x"]),
+            (vec!["a", "z", "LIMIT", "4"], vec!["a", "b", "k", "m"]),
+            (vec!["a", "z", "REV"], vec!["x", "n", "m", "k", "b", "a"]),
+            (vec!["a", "", "LIMIT", "4", "REV"], vec!["x", "n", "m", "k"]),
+            (vec!["b", "n", "rev", "limit", "2"], vec!["m", "k"]),
+            (
+                vec!["a", "z", "LIMIT", "0"],
+                vec!["a", "b", "k", "m", "n", "x"],
+            ),
+            (vec!["c", "j"], vec![]),
+            (vec!["n", "m"], vec![]),
+        ];
+
+        for (args, want) in cases.iter() {
+            for con in [&mut con1, &mut con2].iter_mut() {
+                let got: Vec<String> = redis::cmd("RANGE")
+                    .arg(&args[..])
+                    .query(&mut **con)
+                    .unwrap();
+                assert_eq!(pairs(want), got, "{:?}", args);
+            }
+        }
+    }
+
+    {
+        // a read sees what is written before it.
+        redis::cmd("DEL").arg("b").execute(&mut con2);
+        redis::cmd("SET").arg("o").arg("vo").execute(&mut con1);
+
+        let got: Vec<String> = redis::cmd("RANGE")
+            .arg("b")
+            .arg("p")
+            .query(&mut con1)
+            .unwrap();
+        assert_eq!(pairs(&["k", "m", "n", "o"]), got);
+    }
+
+    {
+        // bad args
+        let cases = vec![
+            vec!["a", "z", "LIMIT"],
+            vec!["a", "z", "LIMIT", "-1"],
+            vec!["a", "z", "LIMIT", "x"],
+            vec!["a", "z", "FOO"],
+        ];
+        for args in cases.iter() {
+            let r: redis::RedisResult<redis::Value> =
+                redis::cmd("RANGE").arg(&args[..]).query(&mut con1);
+            assert!(r.is_err(), "{:?}", args);
+        }
+    }
+
+    s1.stop().unwrap();
+    s2.stop().unwrap();
+    s1.join().await.unwrap();
+    s2.join().await.unwrap();
+}
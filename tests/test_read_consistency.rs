@@ -0,0 +1,97 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_read_linearizable() {
+    _test_read_consistency("linearizable", true);
+}
+
+#[test]
+fn test_read_bounded_stale() {
+    _test_read_consistency("bounded_stale", false);
+}
+
+#[test]
+fn test_read_stale() {
+    _test_read_consistency("stale", false);
+}
+
+/// _test_read_consistency writes through one node and reads through every node.
+/// If `fresh`, a read through any node sees a write completed before it. Otherwise only the
+/// node written through does at once, and others do eventually.
+#[tokio::main]
+async fn _test_read_consistency(consistency: &str, fresh: bool) {
+    // 3 nodes serving one group.
+    let cb = ClusterBuilder::new(3)
+        .group("a", "z", &[0, 1, 2])
+        .conf("read_consistency", consistency)
+        .conf("read_stale_ms", "100");
+
+    let mut servers = vec![];
+    let mut cons = vec![];
+    for i in 0..3 {
+        let (s, con) = cb.start(i);
+        servers.push(s);
+        cons.push(con);
+    }
+
+    for n in 0..5 {
+        let v = format!("v{}", n);
+        let w = n % 3;
+
+        redis::cmd("SET").arg("k").arg(&v).execute(&mut cons[w]);
+        redis::cmd("HSET")
+            .arg("h")
+            .arg("f")
+            .arg(&v)
+            .execute(&mut cons[w]);
+
+        for (i, con) in cons.iter_mut().enumerate() {
+            let must = fresh || i == w;
+
+            let mut got: Option<String> = None;
+            for _ in 0..100 {
+                got = redis::cmd("GET").arg("k").query(con).unwrap();
+                if must || got.as_ref() == Some(&v) {
+                    break;
+                }
+                sleep(Duration::from_millis(10));
+            }
+            assert_eq!(Some(&v), got.as_ref(), "node {} written through {}", i, w);
+
+            if must {
+                let got: String = redis::cmd("HGET").arg("h").arg("f").query(con).unwrap();
+                assert_eq!(v, got, "node {} written through {}", i, w);
+            }
+        }
+    }
+
+    {
+        // reads in a transaction are served locally too.
+        let (k, h): (String, String) = redis::pipe()
+            .atomic()
+            .cmd("GET")
+            .arg("k")
+            .cmd("HGET")
+            .arg("h")
+            .arg("f")
+            .query(&mut cons[1])
+            .unwrap();
+        assert_eq!("v4", k);
+        assert_eq!("v4", h);
+    }
+
+    for s in servers.iter_mut() {
+        s.stop().unwrap();
+    }
+    for s in servers.iter_mut() {
+        s.join().await.unwrap();
+    }
+}
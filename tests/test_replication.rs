@@ -0,0 +1,105 @@
+#[macro_use]
+extern crate epaxos;
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::time::Duration;
+
+use epaxos::qpaxos::*;
+use epaxos::replica::InstanceStatus;
+use epaxos::Storage;
+use tokio::time::delay_for;
+
+use crate::support::*;
+
+mod support;
+
+async fn connect(addr: &str) -> QPaxosClient<tonic::transport::Channel> {
+    loop {
+        match QPaxosClient::connect(format!("http://{}", addr)).await {
+            Ok(c) => return c,
+            Err(_) => delay_for(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+async fn wait_status(sto: &Storage, iid: InstanceId, st: InstanceStatus) -> Instance {
+    for _ in 0..100 {
+        if let Some(inst) = sto.get_instance(iid).unwrap() {
+            if inst.status() >= st {
+                return inst;
+            }
+        }
+        delay_for(Duration::from_millis(10)).await;
+    }
+    panic!("instance {} does not reach {:?}", iid, st);
+}
+
+#[test]
+fn test_replication_reaches_follower_storage() {
+    _test_replication_reaches_follower_storage();
+}
+
+#[tokio::main]
+async fn _test_replication_reaches_follower_storage() {
+    let cluster = InProcCluster::new(3);
+
+    // act as replica 2 and replicate an instance to replica 3 on node 2.
+    let mut client = connect(&cluster.repl_addrs[2]).await;
+    let sto = &cluster.storages[2];
+
+    let mut inst = inst!(
+        (2, 0),
+        (0, 0, _),
+        [("Set", "a", "b")],
+        [(1, -1), (2, -1), (3, -1)],
+        "withdeps"
+    );
+    let iid = inst.instance_id.unwrap();
+
+    let req = MakeRequest::fast_accept(3, &inst, &[false, false, false]);
+    let repl = client.replicate(req).await.unwrap().into_inner();
+    assert_eq!(None, repl.err);
+
+    let got = sto.get_instance(iid).unwrap().unwrap();
+    assert_eq!(InstanceStatus::FastAccepted, got.status());
+    assert_eq!(inst.cmds, got.cmds);
+    assert_eq!(inst.deps, got.deps);
+
+    inst.final_deps = inst.deps.clone();
+    let req = MakeRequest::accept(3, &inst);
+    let repl = client.replicate(req).await.unwrap().into_inner();
+    assert_eq!(None, repl.err);
+
+    let got = sto.get_instance(iid).unwrap().unwrap();
+    assert_eq!(InstanceStatus::Accepted, got.status());
+    assert_eq!(inst.final_deps, got.final_deps);
+
+    let req = MakeRequest::commit(3, &inst);
+    let repl = client.replicate(req).await.unwrap().into_inner();
+    assert_eq!(None, repl.err);
+
+    let got = wait_status(sto, iid, InstanceStatus::Committed).await;
+    assert_eq!(inst.cmds, got.cmds);
+    assert_eq!(inst.final_deps, got.final_deps);
+
+    // a write through node 0 reaches followers' storage.
+    let mut con = cluster.clients[0].get_connection().unwrap();
+    let rst: String = redis::cmd("SET")
+        .arg("foo")
+        .arg("bar")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!("OK", rst);
+
+    for i in 1..3 {
+        let got = wait_status(
+            &cluster.storages[i],
+            (1, 0).into(),
+            InstanceStatus::Committed,
+        )
+        .await;
+        assert_eq!(cmds![("Set", "foo", "bar")], got.cmds);
+    }
+}
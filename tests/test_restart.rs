@@ -0,0 +1,118 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use tempfile;
+
+use cele::open_storage;
+use cele::storage_options;
+use cele::Server;
+use cele::ServerError;
+use cele::StorageEngine;
+use cele::DEFAULT_DATA_DIR;
+use epaxos::conf::ClusterInfo;
+
+use crate::support::*;
+
+mod support;
+
+/// start_server starts a node with rocksdb storage in `data_dir`.
+fn start_server(cb: &ClusterBuilder, data_dir: &str) -> (Server, redis::Connection) {
+    let sto = open_storage(StorageEngine::RocksDB, data_dir).unwrap();
+    cb.start_with(0, sto)
+}
+
+#[test]
+fn test_storage_engine_from_str() {
+    assert_eq!(StorageEngine::Mem, "mem".parse().unwrap());
+    assert_eq!(StorageEngine::RocksDB, "rocksdb".parse().unwrap());
+
+    let rst = "foo".parse::<StorageEngine>();
+    match &rst {
+        Err(ServerError::UnknownStorage(name)) => assert_eq!("foo", name),
+        _ => panic!("expect UnknownStorage but: {:?}", rst),
+    }
+}
+
+#[test]
+fn test_storage_options() {
+    let ci = ClusterInfo::from_str(
+        "
+nodes:
+    127.0.0.1:4441:
+        api_addr: 127.0.0.1:3331
+        replication: 127.0.0.1:4441
+        storage: rocksdb
+        data_dir: /tmp/cele
+    127.0.0.1:4442:
+        api_addr: 127.0.0.1:3332
+        replication: 127.0.0.1:4442
+groups: []
+",
+    )
+    .unwrap();
+    let n1 = ci.get("127.0.0.1:4441").unwrap();
+    let n2 = ci.get("127.0.0.1:4442").unwrap();
+
+    // from the config of the node.
+    let (engine, dir) = storage_options(n1, None, None).unwrap();
+    assert_eq!(StorageEngine::RocksDB, engine);
+    assert_eq!("/tmp/cele", dir);
+
+    // the command line overrides the config.
+    let (engine, dir) = storage_options(n1, Some("mem"), Some("/data")).unwrap();
+    assert_eq!(StorageEngine::Mem, engine);
+    assert_eq!("/data", dir);
+
+    // defaults.
+    let (engine, dir) = storage_options(n2, None, None).unwrap();
+    assert_eq!(StorageEngine::Mem, engine);
+    assert_eq!(DEFAULT_DATA_DIR, dir);
+
+    let rst = storage_options(n2, Some("foo"), None);
+    match &rst {
+        Err(ServerError::UnknownStorage(name)) => assert_eq!("foo", name),
+        _ => panic!("expect UnknownStorage but: {:?}", rst),
+    }
+}
+
+#[test]
+fn test_restart() {
+    _test_restart();
+}
+
+#[tokio::main]
+async fn _test_restart() {
+    let tmp = tempfile::tempdir().unwrap();
+    let data_dir = format!("{}/data", tmp.path().display());
+
+    let cb = ClusterBuilder::new(1).group("a", "z", &[0]);
+
+    {
+        let (mut server, mut con) = start_server(&cb, &data_dir);
+
+        redis::cmd("SET").arg("foo").arg("bar").execute(&mut con);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(Some(b"bar".to_vec()), v);
+
+        drop(con);
+        server.stop().unwrap();
+        server.join().await.unwrap();
+    }
+
+    {
+        // reopen on the same data dir.
+        let (mut server, mut con) = start_server(&cb, &data_dir);
+
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(Some(b"bar".to_vec()), v);
+
+        // new instances are appended after the reloaded ones.
+        redis::cmd("SET").arg("foo").arg("baz").execute(&mut con);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+        assert_eq!(Some(b"baz".to_vec()), v);
+
+        drop(con);
+        server.stop().unwrap();
+        server.join().await.unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::support::*;
+
+mod support;
+
+/// scan_all walks through all keys with SCAN and returns them in the order returned.
+fn scan_all(con: &mut redis::Connection, args: &[&str]) -> Vec<String> {
+    let mut cursor = "0".to_string();
+    let mut keys = vec![];
+    loop {
+        let (next, mut ks): (String, Vec<String>) = redis::cmd("SCAN")
+            .arg(&cursor)
+            .arg(args)
+            .query(con)
+            .unwrap();
+        keys.append(&mut ks);
+        if next == "0" {
+            return keys;
+        }
+        cursor = next;
+    }
+}
+
+#[test]
+fn test_scan() {
+    _test_scan();
+}
+
+#[tokio::main]
+async fn _test_scan() {
+    // node 0 hosts only the group of [a, m) and node 1 hosts only the group of [m, z).
+    let cb = ClusterBuilder::new(2)
+        .group("a", "m", &[0])
+        .group("m", "z", &[1]);
+
+    let (mut s1, mut con1) = cb.start(0);
+    let (mut s2, mut con2) = cb.start(1);
+
+    for k in ["b", "a1", "c", "n", "m1", "x"].iter() {
+        redis::cmd("SET").arg(*k).arg("v").execute(&mut con1);
+    }
+    redis::cmd("HSET")
+        .arg("h")
+        .arg("f")
+        .arg("v")
+        .execute(&mut con1);
+    redis::cmd("SADD").arg("s").arg("m").execute(&mut con2);
+    redis::cmd("SET")
+        .arg("e")
+        .arg("v")
+        .arg("PX")
+        .arg("1")
+        .execute(&mut con1);
+    sleep(Duration::from_millis(10));
+
+    let all = vec!["a1", "b", "c", "h", "m1", "n", "s", "x"];
+
+    {
+        // keys of both groups, in key order, without expired keys.
+        let keys: Vec<String> = redis::cmd("KEYS").arg("*").query(&mut con1).unwrap();
+        assert_eq!(all, keys);
+
+        let keys: Vec<String> = redis::cmd("KEYS").arg("*").query(&mut con2).unwrap();
+        assert_eq!(all, keys);
+
+        let n: i64 = redis::cmd("DBSIZE").query(&mut con1).unwrap();
+        assert_eq!(8, n);
+    }
+
+    {
+        // patterns
+        let cases: Vec<(&str, Vec<&str>)> = vec![
+            ("?1", vec!["a1", "m1"]),
+            ("[a-c]*", vec!["a1", "b", "c"]),
+            ("[^a-c]", vec!["h", "n", "s", "x"]),
+            ("*1", vec!["a1", "m1"]),
+            ("m\\1", vec!["m1"]),
+            ("zz*", vec![]),
+        ];
+
+        for (pattern, want) in cases.iter() {
+            let keys: Vec<String> = redis::cmd("KEYS").arg(*pattern).query(&mut con1).unwrap();
+            assert_eq!(*want, keys, "pattern: {}", pattern);
+        }
+    }
+
+    {
+        // every key is returned exactly once, whatever the count.
+        for count in ["1", "2", "3", "100"].iter() {
+            assert_eq!(all, scan_all(&mut con1, &["COUNT", count]));
+            assert_eq!(all, scan_all(&mut con2, &["COUNT", count]));
+        }
+
+        assert_eq!(vec!["h"], scan_all(&mut con1, &["TYPE", "hash"]));
+        assert_eq!(
+            vec!["m1", "n"],
+            scan_all(&mut con1, &["MATCH", "[m-n]*", "TYPE", "string"])
+        );
+
+        // the cursor is the position to continue from, as a decimal integer: "b" is 0x0162.
+        let (next, keys): (String, Vec<String>) = redis::cmd("SCAN")
+            .arg("0")
+            .arg("COUNT")
+            .arg("2")
+            .query(&mut con1)
+            .unwrap();
+        assert_eq!(vec!["a1", "b"], keys);
+        assert_eq!("354", next);
+
+        redis::cmd("DEL")
+            .arg("a1")
+            .arg("b")
+            .arg("c")
+            .execute(&mut con1);
+        let (_, keys): (String, Vec<String>) = redis::cmd("SCAN")
+            .arg(&next)
+            .arg("COUNT")
+            .arg("2")
+            .query(&mut con1)
+            .unwrap();
+        assert_eq!(vec!["h"], keys);
+    }
+
+    {
+        // bad args
+        let cases = vec![
+            vec!["SCAN", "xyz"],
+            vec!["SCAN", "-1"],
+            vec!["SCAN", "2"],
+            vec!["SCAN", "0", "COUNT", "0"],
+            vec!["SCAN", "0", "COUNT", "a"],
+            vec!["SCAN", "0", "MATCH"],
+            vec!["SCAN", "0", "LIMIT", "1"],
+        ];
+        for args in cases.iter() {
+            let mut c = redis::cmd(args[0]);
+            c.arg(&args[1..]);
+            let r: redis::RedisResult<redis::Value> = c.query(&mut con1);
+            assert!(r.is_err(), "{:?}", args);
+        }
+    }
+
+    s1.stop().unwrap();
+    s2.stop().unwrap();
+    s1.join().await.unwrap();
+    s2.join().await.unwrap();
+}
@@ -64,13 +64,11 @@ fn test_getset() {
     redis::cmd("SET").arg("foo").arg(42).execute(&mut con);
     assert_eq!(redis::cmd("GET").arg("foo").query(&mut con), Ok(42));
 
-    // TODO This test not passed:
-    //
-    // redis::cmd("SET").arg("bar").arg("foo").execute(&mut con);
-    // assert_eq!(
-    //     redis::cmd("GET").arg("bar").query(&mut con),
-    //     Ok(b"foo".to_vec())
-    // );
+    redis::cmd("SET").arg("bar").arg("foo").execute(&mut con);
+    assert_eq!(
+        redis::cmd("GET").arg("bar").query(&mut con),
+        Ok(b"foo".to_vec())
+    );
 }
 
 #[test]
@@ -0,0 +1,174 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use redis::RedisResult;
+
+use crate::support::*;
+
+mod support;
+
+#[test]
+fn test_string_cmds() {
+    let ctx = InProcContext::new();
+    let mut con = ctx.client.get_connection().unwrap();
+
+    {
+        // command names are case-insensitive
+        redis::cmd("set").arg("foo").arg("bar").execute(&mut con);
+        let v: Option<Vec<u8>> = redis::cmd("gEt").arg("foo").query(&mut con).unwrap();
+        assert_eq!(Some(b"bar".to_vec()), v);
+    }
+
+    {
+        // unknown command and wrong arity
+        let r: RedisResult<()> = redis::cmd("NOSUCHCMD").query(&mut con);
+        assert!(r.is_err());
+        assert!(format!("{:?}", r).contains("unknown command"));
+
+        let r: RedisResult<()> = redis::cmd("GET").arg("a").arg("b").query(&mut con);
+        assert!(format!("{:?}", r).contains("wrong number of arguments"));
+
+        let r: RedisResult<()> = redis::cmd("MSET")
+            .arg("a")
+            .arg("b")
+            .arg("c")
+            .query(&mut con);
+        assert!(format!("{:?}", r).contains("wrong number of arguments"));
+    }
+
+    {
+        // MSET, MGET, EXISTS, DEL
+        redis::cmd("MSET")
+            .arg("k1")
+            .arg("v1")
+            .arg("k2")
+            .arg("v2")
+            .execute(&mut con);
+
+        let v: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg("k1")
+            .arg("k2")
+            .arg("k3")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(vec![Some(b"v1".to_vec()), Some(b"v2".to_vec()), None], v);
+
+        let n: i64 = redis::cmd("EXISTS")
+            .arg("k1")
+            .arg("k1")
+            .arg("k3")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(2, n);
+
+        let n: i64 = redis::cmd("DEL")
+            .arg("k1")
+            .arg("k1")
+            .arg("k3")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+
+        let n: i64 = redis::cmd("EXISTS")
+            .arg("k1")
+            .arg("k2")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+    }
+
+    {
+        // SETNX, GETSET
+        let n: i64 = redis::cmd("SETNX")
+            .arg("nx")
+            .arg("1")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(1, n);
+        let n: i64 = redis::cmd("SETNX")
+            .arg("nx")
+            .arg("2")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(0, n);
+
+        let v: Option<Vec<u8>> = redis::cmd("GETSET")
+            .arg("nx")
+            .arg("3")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(Some(b"1".to_vec()), v);
+        let v: Option<Vec<u8>> = redis::cmd("GETSET")
+            .arg("gs")
+            .arg("x")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(None, v);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("nx").query(&mut con).unwrap();
+        assert_eq!(Some(b"3".to_vec()), v);
+    }
+
+    {
+        // APPEND, STRLEN
+        let n: i64 = redis::cmd("APPEND")
+            .arg("ap")
+            .arg("ab")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(2, n);
+        let n: i64 = redis::cmd("APPEND")
+            .arg("ap")
+            .arg("cde")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(5, n);
+
+        let n: i64 = redis::cmd("STRLEN").arg("ap").query(&mut con).unwrap();
+        assert_eq!(5, n);
+        let n: i64 = redis::cmd("STRLEN")
+            .arg("nosuchkey")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(0, n);
+    }
+
+    {
+        // INCR, INCRBY, DECR
+        let n: i64 = redis::cmd("INCR").arg("cnt").query(&mut con).unwrap();
+        assert_eq!(1, n);
+        let n: i64 = redis::cmd("INCRBY")
+            .arg("cnt")
+            .arg(10)
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(11, n);
+        let n: i64 = redis::cmd("DECR").arg("cnt").query(&mut con).unwrap();
+        assert_eq!(10, n);
+
+        let v: i64 = redis::cmd("GET").arg("cnt").query(&mut con).unwrap();
+        assert_eq!(10, v);
+
+        let r: RedisResult<i64> = redis::cmd("INCR").arg("foo").query(&mut con);
+        assert!(format!("{:?}", r).contains("not an integer"));
+
+        let r: RedisResult<i64> = redis::cmd("INCRBY").arg("cnt").arg("x").query(&mut con);
+        assert!(format!("{:?}", r).contains("not an integer"));
+    }
+
+    {
+        // binary keys
+        let k: &[u8] = b"b\xff\x00k";
+        redis::cmd("SET").arg(k).arg("bin").execute(&mut con);
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg(k).query(&mut con).unwrap();
+        assert_eq!(Some(b"bin".to_vec()), v);
+
+        // not served by any group.
+        let k: &[u8] = b"\xff\xfe";
+        let r: RedisResult<()> = redis::cmd("SET").arg(k).arg("bin").query(&mut con);
+        assert!(r.is_err());
+
+        // the connection is still usable.
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("ap").query(&mut con).unwrap();
+        assert_eq!(Some(b"abcde".to_vec()), v);
+    }
+}
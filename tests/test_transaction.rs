@@ -0,0 +1,187 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+use std::io::Write;
+
+use redis::RedisResult;
+use redis::Value;
+
+use crate::support::*;
+
+mod support;
+
+fn status(s: &str) -> Value {
+    Value::Status(s.to_string())
+}
+
+fn data(s: &str) -> Value {
+    Value::Data(s.as_bytes().to_vec())
+}
+
+#[test]
+fn test_transaction() {
+    let ctx = InProcContext::new();
+    let mut con = ctx.client.get_connection().unwrap();
+    let mut con2 = ctx.client.get_connection().unwrap();
+
+    {
+        // commands are queued and replied in one array
+        redis::cmd("SET").arg("a").arg("1").execute(&mut con);
+
+        let v: Value = redis::cmd("MULTI").query(&mut con).unwrap();
+        assert_eq!(status("OK"), v);
+
+        let v: Value = redis::cmd("INCR").arg("a").query(&mut con).unwrap();
+        assert_eq!(status("QUEUED"), v);
+        let v: Value = redis::cmd("MSET")
+            .arg("b")
+            .arg("x")
+            .arg("c")
+            .arg("y")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(status("QUEUED"), v);
+        let v: Value = redis::cmd("MGET")
+            .arg("a")
+            .arg("b")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!(status("QUEUED"), v);
+
+        // not applied before EXEC
+        let v: Option<Vec<u8>> = redis::cmd("GET").arg("b").query(&mut con2).unwrap();
+        assert_eq!(None, v);
+
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(
+            Value::Bulk(vec![
+                Value::Int(2),
+                status("OK"),
+                Value::Bulk(vec![data("2"), data("x")]),
+            ]),
+            v
+        );
+    }
+
+    {
+        // pipeline with MULTI/EXEC
+        let (a, c): (i64, String) = redis::pipe()
+            .atomic()
+            .cmd("INCRBY")
+            .arg("a")
+            .arg(10)
+            .cmd("GET")
+            .arg("c")
+            .query(&mut con)
+            .unwrap();
+        assert_eq!((12, "y".to_string()), (a, c));
+    }
+
+    {
+        // DISCARD
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("SET").arg("a").arg("100").execute(&mut con);
+        let v: Value = redis::cmd("DISCARD").query(&mut con).unwrap();
+        assert_eq!(status("OK"), v);
+
+        let v: i64 = redis::cmd("GET").arg("a").query(&mut con).unwrap();
+        assert_eq!(12, v);
+
+        let r: RedisResult<Value> = redis::cmd("EXEC").query(&mut con);
+        assert!(format!("{:?}", r).contains("EXEC without MULTI"));
+        let r: RedisResult<Value> = redis::cmd("DISCARD").query(&mut con);
+        assert!(format!("{:?}", r).contains("DISCARD without MULTI"));
+    }
+
+    {
+        // a command failed to queue discards the transaction
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("SET").arg("a").arg("100").execute(&mut con);
+
+        let r: RedisResult<Value> = redis::cmd("NOSUCHCMD").query(&mut con);
+        assert!(r.is_err());
+
+        let r: RedisResult<Value> = redis::cmd("EXEC").query(&mut con);
+        assert!(format!("{:?}", r).contains("EXECABORT"));
+
+        let v: i64 = redis::cmd("GET").arg("a").query(&mut con).unwrap();
+        assert_eq!(12, v);
+    }
+
+    {
+        // a command failed when executed does not affect others
+        let mut sock = raw_connect(6379);
+        sock.write_all(b"MULTI\r\nINCR b\r\nSET a 13\r\nEXEC\r\n")
+            .unwrap();
+
+        let want = [
+            &b"+OK\r\n+QUEUED\r\n+QUEUED\r\n"[..],
+            &b"*2\r\n-ERR value is not an integer or out of range\r\n+OK\r\n"[..],
+        ]
+        .concat();
+        assert_eq!(want, read_n(&mut sock, want.len()));
+
+        let v: i64 = redis::cmd("GET").arg("a").query(&mut con).unwrap();
+        assert_eq!(13, v);
+    }
+
+    {
+        // WATCH: a watched key changed by another client aborts the transaction
+        redis::cmd("WATCH").arg("a").arg("w").execute(&mut con);
+        redis::cmd("SET").arg("a").arg("20").execute(&mut con2);
+
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("SET").arg("c").arg("z").execute(&mut con);
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(Value::Nil, v);
+
+        let v: String = redis::cmd("GET").arg("c").query(&mut con).unwrap();
+        assert_eq!("y", v);
+
+        // EXEC unwatches all keys
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("SET").arg("c").arg("z").execute(&mut con);
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(Value::Bulk(vec![status("OK")]), v);
+    }
+
+    {
+        // an aborted EXEC replies a null array: `*-1` in RESP2 and `_` in RESP3.
+        let cases: Vec<(&[u8], &[u8])> = vec![(b"", b"*-1\r\n"), (b"HELLO 3\r\n", b"_\r\n")];
+
+        for (hello, want) in cases.iter() {
+            let mut sock = raw_connect(6379);
+            sock.write_all(&[*hello, &b"WATCH a\r\n"[..]].concat())
+                .unwrap();
+
+            // skip the HELLO reply, if any, till the `+OK` of WATCH.
+            let mut got = vec![];
+            while !got.ends_with(b"+OK\r\n") {
+                got.extend(read_n(&mut sock, 1));
+            }
+
+            redis::cmd("INCR").arg("a").execute(&mut con2);
+
+            sock.write_all(b"MULTI\r\nSET c z\r\nEXEC\r\n").unwrap();
+            let want = [&b"+OK\r\n+QUEUED\r\n"[..], want].concat();
+            assert_eq!(want, read_n(&mut sock, want.len()));
+        }
+    }
+
+    {
+        // WATCH: unchanged keys
+        redis::cmd("WATCH").arg("a").arg("w").execute(&mut con);
+        redis::cmd("SET").arg("other").arg("1").execute(&mut con2);
+
+        redis::cmd("MULTI").execute(&mut con);
+        redis::cmd("GET").arg("a").execute(&mut con);
+        let v: Value = redis::cmd("EXEC").query(&mut con).unwrap();
+        assert_eq!(Value::Bulk(vec![data("20")]), v);
+
+        let r: RedisResult<Value> = redis::cmd("MULTI").query(&mut con);
+        assert!(r.is_ok());
+        let r: RedisResult<Value> = redis::cmd("WATCH").arg("a").query(&mut con);
+        assert!(format!("{:?}", r).contains("WATCH inside MULTI"));
+        redis::cmd("DISCARD").execute(&mut con);
+    }
+}